@@ -5,6 +5,7 @@
 */
 
 extern crate tch;
+use std::convert::TryFrom;
 use tch::data::TextData;
 use tch::nn::{Linear, Module, OptimizerConfig, LSTM, RNN};
 use tch::{nn, Device, Kind, Tensor};
@@ -27,7 +28,7 @@ fn sample(data: &TextData, lstm: &LSTM, linear: &Linear, device: Device) -> Stri
         input.narrow(1, last_label, 1).fill_(1.0);
         state = lstm.step(&input, &state);
         let sampled_y = linear.forward(&state.h()).softmax(-1).multinomial(1, false);
-        last_label = i64::from(sampled_y);
+        last_label = i64::try_from(sampled_y).unwrap();
         result.push(data.label_to_char(last_label))
     }
     result
@@ -54,7 +55,7 @@ pub fn main() -> failure::Fallible<()> {
                 .view(&[BATCH_SIZE * SEQ_LEN, labels])
                 .cross_entropy_for_logits(&ys.to_device(device).view(&[BATCH_SIZE * SEQ_LEN]));
             opt.backward_step_clip(&loss, 0.5);
-            sum_loss += f64::from(loss);
+            sum_loss += f64::try_from(loss)?;
             cnt_loss += 1.0;
         }
         println!("Epoch: {}   loss: {:5.3}", epoch, sum_loss / cnt_loss);
@@ -12,7 +12,7 @@ use tch::{nn, Device};
 
 fn conv_bn(vs: &nn::Path, c_in: i64, c_out: i64) -> SequentialT {
     let conv2d_cfg = nn::ConvConfig {
-        padding: 1,
+        padding: nn::Padding::Explicit(1),
         bias: false,
         ..Default::default()
     };
@@ -26,7 +26,7 @@ fn tr2d(p: nn::Path, c_in: i64, c_out: i64, padding: i64, stride: i64) -> nn::Co
 fn conv2d(p: nn::Path, c_in: i64, c_out: i64, padding: i64, stride: i64) -> nn::Conv2D {
     let cfg = nn::ConvConfig {
         stride,
-        padding,
+        padding: nn::Padding::Explicit(padding),
         bias: false,
         ..Default::default()
     };
@@ -1,6 +1,7 @@
 // This should rearch 97% accuracy.
 
 extern crate tch;
+use std::convert::TryFrom;
 use tch::{nn, nn::Module, nn::OptimizerConfig, Device};
 
 const IMAGE_DIM: i64 = 784;
@@ -35,8 +36,8 @@ pub fn run() -> failure::Fallible<()> {
         println!(
             "epoch: {:4} train loss: {:8.5} test acc: {:5.2}%",
             epoch,
-            f64::from(&loss),
-            100. * f64::from(&test_accuracy),
+            f64::try_from(&loss)?,
+            100. * f64::try_from(&test_accuracy)?,
         );
     }
     Ok(())
@@ -5,6 +5,7 @@
 #[macro_use]
 extern crate failure;
 extern crate tch;
+use std::convert::TryFrom;
 use tch::vision::{imagenet, vgg};
 use tch::{nn, nn::OptimizerConfig, Device, Tensor};
 
@@ -65,7 +66,7 @@ pub fn main() -> failure::Fallible<()> {
         let loss = style_loss * STYLE_WEIGHT + content_loss;
         opt.backward_step(&loss);
         if step_idx % 1000 == 0 {
-            println!("{} {}", step_idx, f64::from(loss));
+            println!("{} {}", step_idx, f64::try_from(loss)?);
             imagenet::save_image(&input_var, &format!("out{}.jpg", step_idx))?;
         }
     }
@@ -7,6 +7,7 @@
    reference python implementation.
 */
 use super::vec_gym_env::VecGymEnv;
+use std::convert::TryFrom;
 use tch::kind::{FLOAT_CPU, INT64_CPU};
 use tch::{nn, nn::OptimizerConfig, Tensor};
 
@@ -102,8 +103,8 @@ pub fn train() -> cpython::PyResult<()> {
             let step = env.step(Vec::<i64>::from(&actions))?;
 
             sum_rewards += &step.reward;
-            total_rewards += f64::from((&sum_rewards * &step.is_done).sum());
-            total_episodes += f64::from(step.is_done.sum());
+            total_rewards += f64::try_from((&sum_rewards * &step.is_done).sum()).unwrap();
+            total_episodes += f64::try_from(step.is_done.sum()).unwrap();
 
             let masks = Tensor::from(1f32) - step.is_done;
             sum_rewards *= &masks;
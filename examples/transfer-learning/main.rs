@@ -6,6 +6,7 @@
 #[macro_use]
 extern crate failure;
 extern crate tch;
+use std::convert::TryFrom;
 use tch::nn::{self, OptimizerConfig};
 use tch::vision::{imagenet, resnet};
 
@@ -40,7 +41,7 @@ pub fn main() -> failure::Fallible<()> {
         let test_accuracy = test_images
             .apply(&linear)
             .accuracy_for_logits(&dataset.test_labels);
-        println!("{} {:.2}%", epoch_idx, 100. * f64::from(test_accuracy));
+        println!("{} {:.2}%", epoch_idx, 100. * f64::try_from(test_accuracy)?);
     }
     Ok(())
 }
@@ -1,9 +1,12 @@
 //! Dataset iterators.
 use crate::{kind, Device, Tensor};
 use failure::Fallible;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read, Result};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread::JoinHandle;
 
 /// An iterator over a pair of tensors which have the same first dimension
 /// size.
@@ -19,6 +22,73 @@ pub struct Iter2 {
     total_size: i64,
     device: Device,
     return_smaller_last_batch: bool,
+    prefetch_factor: usize,
+    pin_memory: bool,
+    prefetcher: Option<Prefetcher>,
+}
+
+// A worker thread preparing the upcoming batches of an epoch, including their
+// copy to the target device. The channel is bounded so that at most
+// `prefetch_factor` batches are ready at a time.
+#[derive(Debug)]
+struct Prefetcher {
+    receiver: Option<Receiver<(Tensor, Tensor)>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Prefetcher {
+    fn new(mut iter: Iter2, prefetch_factor: usize, pin_memory: bool) -> Prefetcher {
+        let (sender, receiver) = sync_channel(prefetch_factor);
+        let device = iter.device;
+        let pin_memory = pin_memory && device != Device::Cpu;
+        let worker = std::thread::spawn(move || {
+            while let Some((xs, ys)) = iter.next_batch() {
+                let batch = if pin_memory {
+                    // Pinned memory makes the copy to the device asynchronous so
+                    // that it overlaps with the computations on the previous
+                    // batches.
+                    (
+                        xs.pin_memory().to_device_non_blocking(device),
+                        ys.pin_memory().to_device_non_blocking(device),
+                    )
+                } else {
+                    (xs.to_device(device), ys.to_device(device))
+                };
+                // Sending fails when the receiving iterator has been dropped.
+                if sender.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+        Prefetcher {
+            receiver: Some(receiver),
+            worker: Some(worker),
+        }
+    }
+
+    // Returns None once the worker has produced all the batches, a panic in
+    // the worker thread is propagated.
+    fn next(&mut self) -> Option<(Tensor, Tensor)> {
+        let batch = self.receiver.as_ref().and_then(|r| r.recv().ok());
+        if batch.is_none() {
+            if let Some(worker) = self.worker.take() {
+                if let Err(err) = worker.join() {
+                    std::panic::resume_unwind(err)
+                }
+            }
+        }
+        batch
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        // Dropping the receiver first unblocks a worker waiting to send.
+        self.receiver.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
 }
 
 impl Iter2 {
@@ -51,6 +121,9 @@ impl Iter2 {
             total_size,
             device: Device::Cpu,
             return_smaller_last_batch: false,
+            prefetch_factor: 0,
+            pin_memory: false,
+            prefetcher: None,
         })
     }
 
@@ -93,24 +166,210 @@ impl Iter2 {
         self.return_smaller_last_batch = true;
         self
     }
-}
 
-impl Iterator for Iter2 {
-    type Item = (Tensor, Tensor);
+    /// Prepares up to `prefetch_factor` upcoming batches in a worker thread.
+    ///
+    /// The worker is started on the first call to `next` and also copies the
+    /// batches to the device set with `to_device`. It stops once all the
+    /// batches of the epoch have been produced or when the iterator is
+    /// dropped. A `prefetch_factor` of 0 disables prefetching.
+    pub fn prefetch(&mut self, prefetch_factor: usize) -> &mut Iter2 {
+        self.prefetch_factor = prefetch_factor;
+        self
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Copies the prefetched batches to pinned memory before transferring them
+    /// to the device set with `to_device`.
+    ///
+    /// The transfer is then asynchronous and overlaps with the computations
+    /// on previous batches. This has no effect when prefetching is disabled or
+    /// when the batches stay on the cpu.
+    pub fn pin_memory(&mut self) -> &mut Iter2 {
+        self.pin_memory = true;
+        self
+    }
+
+    // The start and size of the next batch, if any.
+    fn next_range(&self) -> Option<(i64, i64)> {
         let start = self.batch_index * self.batch_size;
         let size = std::cmp::min(self.batch_size, self.total_size - start);
         if size <= 0 || (!self.return_smaller_last_batch && size < self.batch_size) {
             None
         } else {
-            self.batch_index += 1;
-            Some((
-                self.xs.narrow(0, start, size).to_device(self.device),
-                self.ys.narrow(0, start, size).to_device(self.device),
-            ))
+            Some((start, size))
         }
     }
+
+    fn next_batch(&mut self) -> Option<(Tensor, Tensor)> {
+        let (start, size) = self.next_range()?;
+        self.batch_index += 1;
+        Some((
+            self.xs.narrow(0, start, size),
+            self.ys.narrow(0, start, size),
+        ))
+    }
+
+    fn next_prefetched(&mut self) -> Option<(Tensor, Tensor)> {
+        // A single worker produces all the remaining batches of the epoch.
+        if self.prefetcher.is_none() {
+            self.next_range()?;
+            let iter = Iter2 {
+                xs: self.xs.shallow_clone(),
+                ys: self.ys.shallow_clone(),
+                prefetch_factor: 0,
+                prefetcher: None,
+                ..*self
+            };
+            self.prefetcher = Some(Prefetcher::new(iter, self.prefetch_factor, self.pin_memory))
+        }
+        let batch = self.prefetcher.as_mut().and_then(|p| p.next());
+        match batch {
+            Some(_) => self.batch_index += 1,
+            // Once exhausted, `next_range` returns None so no new worker gets
+            // started.
+            None => self.prefetcher = None,
+        }
+        batch
+    }
+}
+
+impl Iterator for Iter2 {
+    type Item = (Tensor, Tensor);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.prefetch_factor == 0 {
+            let (xs, ys) = self.next_batch()?;
+            Some((xs.to_device(self.device), ys.to_device(self.device)))
+        } else {
+            self.next_prefetched()
+        }
+    }
+}
+
+/// A subset of a dataset made of a pair of tensors sharing their first
+/// dimension size.
+///
+/// A subset only stores the indexes of its elements, the data is selected
+/// from the parent tensors when accessed.
+#[derive(Debug)]
+pub struct Subset {
+    xs: Tensor,
+    ys: Tensor,
+    indexes: Vec<i64>,
+}
+
+impl Subset {
+    /// The number of elements in the subset.
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// Returns true if the subset does not contain any element.
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// The indexes of the subset elements in the parent dataset.
+    pub fn indexes(&self) -> &[i64] {
+        &self.indexes
+    }
+
+    /// Returns the element at `index` in the subset.
+    pub fn get(&self, index: usize) -> (Tensor, Tensor) {
+        let index = self.indexes[index];
+        (self.xs.get(index), self.ys.get(index))
+    }
+
+    /// Returns the subset elements as a pair of tensors, this copies the data.
+    pub fn tensors(&self) -> (Tensor, Tensor) {
+        let indexes = Tensor::of_slice(&self.indexes).to_device(self.xs.device());
+        let xs = self.xs.index_select(0, &indexes);
+        let indexes = indexes.to_device(self.ys.device());
+        (xs, self.ys.index_select(0, &indexes))
+    }
+
+    /// Returns an iterator over mini-batches of the subset elements.
+    pub fn iter(&self, batch_size: i64) -> Iter2 {
+        let (xs, ys) = self.tensors();
+        Iter2::new(&xs, &ys, batch_size)
+    }
+}
+
+fn dataset_size(xs: &Tensor, ys: &Tensor) -> Fallible<i64> {
+    let size = xs.size().first().cloned().unwrap_or(0);
+    ensure!(
+        ys.size().first() == Some(&size),
+        "different dimension for the two inputs {:?} {:?}",
+        xs.size(),
+        ys.size()
+    );
+    Ok(size)
+}
+
+fn shuffled_indexes(size: i64, seed: u64) -> Vec<i64> {
+    let mut indexes: Vec<i64> = (0..size).collect();
+    indexes.shuffle(&mut StdRng::seed_from_u64(seed));
+    indexes
+}
+
+/// Randomly splits a dataset in non-overlapping subsets of the given lengths.
+///
+/// The same seed always results in the same split. An error is returned if
+/// the lengths do not sum to the dataset size.
+pub fn random_split(xs: &Tensor, ys: &Tensor, lengths: &[i64], seed: u64) -> Fallible<Vec<Subset>> {
+    let size = dataset_size(xs, ys)?;
+    ensure!(
+        lengths.iter().all(|&l| l >= 0) && lengths.iter().sum::<i64>() == size,
+        "the lengths {:?} do not sum to the dataset size {}",
+        lengths,
+        size
+    );
+    let indexes = shuffled_indexes(size, seed);
+    let mut start = 0;
+    Ok(lengths
+        .iter()
+        .map(|&length| {
+            let end = start + length as usize;
+            let subset = Subset {
+                xs: xs.shallow_clone(),
+                ys: ys.shallow_clone(),
+                indexes: indexes[start..end].to_vec(),
+            };
+            start = end;
+            subset
+        })
+        .collect())
+}
+
+/// Splits a dataset in `k` folds for cross-validation and returns a pair of
+/// training and validation subsets for each fold.
+///
+/// The elements are shuffled using `seed` before being split, each element
+/// appears in exactly one validation subset. The fold sizes differ by at most
+/// one when `k` does not divide the dataset size.
+pub fn kfold(xs: &Tensor, ys: &Tensor, k: usize, seed: u64) -> Fallible<Vec<(Subset, Subset)>> {
+    let size = dataset_size(xs, ys)?;
+    ensure!(
+        k >= 2 && k as i64 <= size,
+        "kfold: k should be between 2 and the dataset size {}, got {}",
+        size,
+        k
+    );
+    let indexes = shuffled_indexes(size, seed);
+    let subset = |indexes: Vec<i64>| Subset {
+        xs: xs.shallow_clone(),
+        ys: ys.shallow_clone(),
+        indexes,
+    };
+    Ok((0..k)
+        .map(|fold| {
+            let start = fold * indexes.len() / k;
+            let end = (fold + 1) * indexes.len() / k;
+            let mut train = indexes[..start].to_vec();
+            train.extend_from_slice(&indexes[end..]);
+            (subset(train), subset(indexes[start..end].to_vec()))
+        })
+        .collect())
 }
 
 /// Text data holder.
@@ -0,0 +1,110 @@
+//! Decoding utilities for sequence models.
+use crate::{Device, Kind, Tensor};
+
+// A partial or finished hypothesis together with its log-probability.
+#[derive(Debug, Clone)]
+struct Hypothesis {
+    tokens: Vec<i64>,
+    log_prob: f64,
+}
+
+impl Hypothesis {
+    // The length normalized score, `length` includes the end of sequence token
+    // for finished hypotheses.
+    fn score(&self, length: usize, length_penalty: f64) -> f64 {
+        self.log_prob / (length.max(1) as f64).powf(length_penalty)
+    }
+}
+
+// Sorts the scored hypotheses by decreasing score with the NaN scores last.
+fn sort_by_score(hypotheses: &mut [(f64, Hypothesis)]) {
+    hypotheses.sort_by(|(s1, _), (s2, _)| s1.is_nan().cmp(&s2.is_nan()).then(s2.total_cmp(s1)))
+}
+
+/// Decodes sequences using beam search.
+///
+/// `step_fn` takes a tensor of shape [beams, len] with the tokens generated so
+/// far for each beam and returns the logits for the next token with shape
+/// [beams, vocab_size]. The tokens tensor is empty, with shape [1, 0], on the
+/// first call.
+///
+/// A hypothesis is finished when it produces `eos_id`, its score is then its
+/// log-probability divided by `length ^ length_penalty` where the length
+/// includes the end of sequence token. A `length_penalty` of 0 uses the raw
+/// log-probabilities which favors short sequences, larger values favor longer
+/// ones. Decoding stops after `max_len` steps or when the best active beam,
+/// scored at the current length, does not beat the `beam_size` finished
+/// hypotheses. Hypotheses still active after `max_len` steps are considered
+/// finished.
+///
+/// Returns up to `beam_size` sequences sorted by decreasing score, the end of
+/// sequence tokens are not included.
+pub fn beam_search<F>(
+    mut step_fn: F,
+    beam_size: usize,
+    max_len: usize,
+    eos_id: i64,
+    length_penalty: f64,
+) -> Vec<Vec<i64>>
+where
+    F: FnMut(&Tensor) -> Tensor,
+{
+    let _no_grad = crate::no_grad_guard();
+    let mut beams = vec![Hypothesis {
+        tokens: vec![],
+        log_prob: 0.,
+    }];
+    let mut finished: Vec<(f64, Hypothesis)> = vec![];
+    for len in 0..max_len {
+        let flat_tokens: Vec<i64> = beams.iter().flat_map(|h| h.tokens.clone()).collect();
+        let tokens = Tensor::of_slice(&flat_tokens).view(&[beams.len() as i64, len as i64]);
+        let log_probs = step_fn(&tokens)
+            .to_device(Device::Cpu)
+            .to_kind(Kind::Double)
+            .log_softmax(-1);
+        let vocab_size = log_probs.size()[1];
+        let beam_log_probs: Vec<f64> = beams.iter().map(|h| h.log_prob).collect();
+        let scores = log_probs + Tensor::of_slice(&beam_log_probs).unsqueeze(1);
+        // Keeping twice the beam size ensures that enough candidates remain
+        // after removing the finished ones.
+        let k = std::cmp::min(2 * beam_size as i64, scores.numel());
+        let (top_scores, top_indexes) = scores.view(&[-1]).topk(k, -1, true, true);
+        let top_scores = Vec::<f64>::from(&top_scores);
+        let top_indexes = Vec::<i64>::from(&top_indexes);
+        let mut new_beams = vec![];
+        for (&log_prob, &index) in top_scores.iter().zip(top_indexes.iter()) {
+            let (beam, token) = (index / vocab_size, index % vocab_size);
+            let mut tokens = beams[beam as usize].tokens.clone();
+            if token == eos_id {
+                let hypothesis = Hypothesis { tokens, log_prob };
+                let score = hypothesis.score(len + 1, length_penalty);
+                finished.push((score, hypothesis))
+            } else if new_beams.len() < beam_size {
+                tokens.push(token);
+                new_beams.push(Hypothesis { tokens, log_prob })
+            }
+        }
+        beams = new_beams;
+        sort_by_score(&mut finished);
+        finished.truncate(beam_size);
+        let best_active = beams
+            .iter()
+            .map(|h| h.score(len + 1, length_penalty))
+            .fold(f64::NEG_INFINITY, f64::max);
+        if beams.is_empty()
+            || finished.len() == beam_size && finished[beam_size - 1].0 >= best_active
+        {
+            break;
+        }
+    }
+    for hypothesis in beams.into_iter() {
+        let score = hypothesis.score(hypothesis.tokens.len(), length_penalty);
+        finished.push((score, hypothesis))
+    }
+    sort_by_score(&mut finished);
+    finished
+        .into_iter()
+        .take(beam_size)
+        .map(|(_, h)| h.tokens)
+        .collect()
+}
@@ -0,0 +1,102 @@
+//! Message passing for graph neural networks.
+//!
+//! A graph with N nodes is described by an `edge_index` tensor of shape
+//! [2, E] where each column contains the source and destination nodes of an
+//! edge. Messages are computed for each edge and aggregated at the
+//! destination nodes, this is the building block of layers such as GCN or
+//! GraphSAGE.
+use crate::{Kind, ScatterReduce, Tensor};
+use failure::Fallible;
+
+/// The aggregation of the messages received by a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    Mean,
+    Max,
+}
+
+/// Gathers node features along the edges of a graph and aggregates them at
+/// the destination nodes.
+///
+/// Nodes that do not receive any message get zeros.
+#[derive(Debug, Copy, Clone)]
+pub struct MessagePassing {
+    pub aggregation: Aggregation,
+    /// When set, an edge from each node to itself is added so that the node
+    /// features are part of their own aggregation.
+    pub add_self_loops: bool,
+}
+
+/// Creates a message passing helper using the given aggregation.
+pub fn message_passing(aggregation: Aggregation, add_self_loops: bool) -> MessagePassing {
+    MessagePassing {
+        aggregation,
+        add_self_loops,
+    }
+}
+
+impl MessagePassing {
+    /// Computes and aggregates the messages for each edge.
+    ///
+    /// `xs` contains the node features with shape [N, ...]. `message` is
+    /// called once with the features of the source and destination nodes of
+    /// all the edges, both with shape [E, ...], and returns the messages with
+    /// shape [E, ...]. The result has shape [N, ...] where the trailing
+    /// dimensions are the ones of the messages.
+    pub fn f_propagate<F>(&self, edge_index: &Tensor, xs: &Tensor, message: F) -> Fallible<Tensor>
+    where
+        F: FnOnce(&Tensor, &Tensor) -> Tensor,
+    {
+        ensure!(
+            edge_index.kind() == Kind::Int64 && edge_index.dim() == 2 && edge_index.size()[0] == 2,
+            "message passing: expected an int64 edge index of shape [2, E], got {:?} {:?}",
+            edge_index.kind(),
+            edge_index.size()
+        );
+        ensure!(
+            xs.dim() > 0,
+            "message passing: node features cannot have zero dimension"
+        );
+        let num_nodes = xs.size()[0];
+        let (mut src, mut dst) = (edge_index.f_get(0)?, edge_index.f_get(1)?);
+        if self.add_self_loops {
+            let nodes = Tensor::f_arange(num_nodes, (Kind::Int64, edge_index.device()))?;
+            src = Tensor::f_cat(&[src, nodes.shallow_clone()], 0)?;
+            dst = Tensor::f_cat(&[dst, nodes], 0)?;
+        }
+        let messages = message(&xs.f_index_select(0, &src)?, &xs.f_index_select(0, &dst)?);
+        let size = messages.size();
+        ensure!(
+            !size.is_empty() && size[0] == src.size()[0],
+            "message passing: expected one message per edge, got shape {:?} for {} edges",
+            size,
+            src.size()[0]
+        );
+        let mut index_shape = vec![1; size.len()];
+        index_shape[0] = size[0];
+        let index = dst.f_view(&index_shape)?.f_expand(&size, false)?;
+        let mut out_size = size.clone();
+        out_size[0] = num_nodes;
+        let reduce = match self.aggregation {
+            Aggregation::Sum => ScatterReduce::Sum,
+            Aggregation::Mean => ScatterReduce::Mean,
+            Aggregation::Max => ScatterReduce::Amax,
+        };
+        Tensor::f_zeros(&out_size, (messages.kind(), messages.device()))?
+            .f_scatter_reduce(0, &index, &messages, reduce, false)
+    }
+
+    /// Computes and aggregates the messages for each edge, see `f_propagate`.
+    pub fn propagate<F>(&self, edge_index: &Tensor, xs: &Tensor, message: F) -> Tensor
+    where
+        F: FnOnce(&Tensor, &Tensor) -> Tensor,
+    {
+        self.f_propagate(edge_index, xs, message).unwrap()
+    }
+
+    /// Aggregates the features of the source nodes at the destination nodes.
+    pub fn aggregate(&self, edge_index: &Tensor, xs: &Tensor) -> Tensor {
+        self.propagate(edge_index, xs, |xs_src, _| xs_src.shallow_clone())
+    }
+}
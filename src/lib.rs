@@ -9,19 +9,36 @@ extern crate zip;
 pub mod data;
 
 mod wrappers;
-pub use wrappers::device::{Cuda, Device};
-pub use wrappers::jit::{CModule, IValue};
+pub use wrappers::device::{f_oom_fallback, oom_fallback, Cuda, CudaStream, Device};
+pub use wrappers::hook::HookHandle;
+pub use wrappers::jit::{load_library, CModule, IValue};
 pub use wrappers::kind::Kind;
-pub use wrappers::manual_seed;
 pub use wrappers::scalar::Scalar;
+pub use wrappers::{
+    get_device_rng_state, get_rng_state, manual_seed, set_device_rng_state, set_rng_state,
+};
 
 mod tensor;
-pub use tensor::{no_grad, no_grad_guard, NoGradGuard, Reduction, Tensor};
+pub use tensor::{
+    get_print_options, no_grad, no_grad_guard, set_print_options, Contraction, GridPaddingMode,
+    GridSampleMode, MemoryFormat, NoGradGuard, PrintOptions, ReduceOp, Reduction, ScatterReduce,
+    SparseCsr, Tensor,
+};
 
+pub mod generate;
+pub mod gnn;
+pub mod metrics;
 pub mod nn;
+pub mod quantization;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 pub mod vision;
 
+pub mod profiler {
+    //! Profiling of the operations run by torch.
+    pub use super::wrappers::profiler::*;
+}
+
 pub mod kind {
-    pub(crate) use super::wrappers::kind::T;
     pub use super::wrappers::kind::*;
 }
@@ -0,0 +1,51 @@
+//! Evaluation metrics for classification models.
+use crate::{Device, Kind, Tensor};
+
+/// Returns the top-1 accuracy for some logits and ground-truth targets.
+///
+/// The logits have shape [N, C] and the targets contain the N class indexes.
+pub fn accuracy(logits: &Tensor, targets: &Tensor) -> f64 {
+    let _no_grad = crate::no_grad_guard();
+    let logits = logits.to_device(Device::Cpu);
+    let targets = targets.to_device(Device::Cpu);
+    logits.accuracy_for_logits(&targets).item::<f64>()
+}
+
+/// Returns the top-k accuracy for some logits and ground-truth targets.
+///
+/// A sample is counted as correct when its target is one of the `k` classes
+/// with the highest logits.
+pub fn topk_accuracy(logits: &Tensor, targets: &Tensor, k: i64) -> f64 {
+    let _no_grad = crate::no_grad_guard();
+    let logits = logits.to_device(Device::Cpu);
+    let targets = targets.to_device(Device::Cpu).to_kind(Kind::Int64);
+    let (_values, indexes) = logits.topk(k, -1, true, true);
+    let correct = indexes.eq1(&targets.unsqueeze(-1)).any1(-1, false);
+    correct.to_kind(Kind::Float).mean().item::<f64>()
+}
+
+/// Returns the confusion matrix for some predictions and ground-truth targets.
+///
+/// The predictions can either be class indexes with the same shape as targets,
+/// or logits with an additional trailing dimension in which case the argmax is
+/// used. The returned tensor has shape [num_classes, num_classes], the entry at
+/// (i, j) counts the samples of class i that were predicted as class j.
+pub fn confusion_matrix(preds: &Tensor, targets: &Tensor, num_classes: i64) -> Tensor {
+    let _no_grad = crate::no_grad_guard();
+    let preds = if preds.dim() == targets.dim() + 1 {
+        preds.argmax(-1, false)
+    } else {
+        preds.shallow_clone()
+    };
+    let preds = preds
+        .to_device(Device::Cpu)
+        .to_kind(Kind::Int64)
+        .view(&[-1]);
+    let targets = targets
+        .to_device(Device::Cpu)
+        .to_kind(Kind::Int64)
+        .view(&[-1]);
+    (targets * num_classes + preds)
+        .bincount::<Tensor>(None, num_classes * num_classes)
+        .view(&[num_classes, num_classes])
+}
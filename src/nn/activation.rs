@@ -0,0 +1,65 @@
+//! Activation functions.
+use super::Module;
+use crate::Tensor;
+
+/// An activation function, this can be used as a module or appended to a
+/// sequential layer with `add_activation`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activation {
+    Relu,
+    /// The exact GELU, see `Tensor::gelu`.
+    Gelu,
+    /// The tanh approximation of GELU, see `Tensor::gelu`.
+    GeluTanh,
+    /// Also known as swish, `x * sigmoid(x)`.
+    Silu,
+    /// `x * tanh(softplus(x))`.
+    Mish,
+    Tanh,
+    Sigmoid,
+    /// A leaky ReLU with the given negative slope.
+    LeakyRelu(f64),
+}
+
+impl Activation {
+    /// Applies the activation function to a tensor.
+    pub fn apply(&self, xs: &Tensor) -> Tensor {
+        match *self {
+            Activation::Relu => xs.relu(),
+            Activation::Gelu => xs.gelu("none"),
+            Activation::GeluTanh => xs.gelu("tanh"),
+            Activation::Silu => xs * xs.sigmoid(),
+            Activation::Mish => xs * xs.softplus().tanh(),
+            Activation::Tanh => xs.tanh(),
+            Activation::Sigmoid => xs.sigmoid(),
+            Activation::LeakyRelu(slope) => xs.relu() - (-xs).relu() * slope,
+        }
+    }
+}
+
+impl Module for Activation {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        self.apply(xs)
+    }
+}
+
+/// Applies a softmax along the given dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Softmax(pub i64);
+
+impl Module for Softmax {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        xs.softmax(self.0)
+    }
+}
+
+/// Applies a log-softmax along the given dimension, this is more stable than
+/// taking the log of a softmax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSoftmax(pub i64);
+
+impl Module for LogSoftmax {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        xs.log_softmax(self.0)
+    }
+}
@@ -6,8 +6,18 @@ use std::borrow::Borrow;
 #[derive(Debug, Clone, Copy)]
 pub struct BatchNormConfig {
     pub cudnn_enabled: bool,
+    /// The value added to the variance for numerical stability, 1e-5 by
+    /// default as in PyTorch. Keras and TensorFlow use 1e-3 so this has to be
+    /// set explicitly when porting models from these frameworks.
     pub eps: f64,
+    /// The weight of the current batch statistics when updating the running
+    /// statistics, 0.1 by default as in PyTorch. This is `1 - momentum` for
+    /// the TensorFlow momentum convention, e.g. a Keras momentum of 0.99
+    /// corresponds to 0.01.
     pub momentum: f64,
+    /// When false, no running statistics are tracked and the batch statistics
+    /// are used for normalization in both training and evaluation mode.
+    pub track_running_stats: bool,
     pub ws_init: super::Init,
     pub bs_init: super::Init,
 }
@@ -18,6 +28,7 @@ impl Default for BatchNormConfig {
             cudnn_enabled: true,
             eps: 1e-5,
             momentum: 0.1,
+            track_running_stats: true,
             ws_init: super::Init::Uniform { lo: 0., up: 1. },
             bs_init: super::Init::Const(0.),
         }
@@ -28,11 +39,17 @@ impl Default for BatchNormConfig {
 #[derive(Debug)]
 pub struct BatchNorm {
     config: BatchNormConfig,
+    /// The running statistics, these are undefined tensors when the layer does
+    /// not track running statistics.
     pub running_mean: Tensor,
     pub running_var: Tensor,
     pub ws: Tensor,
     pub bs: Tensor,
     pub nd: usize,
+    /// When set, the running statistics are used for normalization and are left
+    /// unchanged even in training mode, the affine parameters are still
+    /// trained. This is sometimes known as frozen batch-normalization.
+    pub frozen_stats: bool,
 }
 
 fn batch_norm<'a, T: Borrow<super::Path<'a>>>(
@@ -42,13 +59,22 @@ fn batch_norm<'a, T: Borrow<super::Path<'a>>>(
     config: BatchNormConfig,
 ) -> BatchNorm {
     let vs = vs.borrow();
+    let (running_mean, running_var) = if config.track_running_stats {
+        (
+            vs.zeros_no_train("running_mean", &[out_dim]),
+            vs.ones_no_train("running_var", &[out_dim]),
+        )
+    } else {
+        (Tensor::new(), Tensor::new())
+    };
     BatchNorm {
         config,
-        running_mean: vs.zeros_no_train("running_mean", &[out_dim]),
-        running_var: vs.ones_no_train("running_var", &[out_dim]),
+        running_mean,
+        running_var,
         ws: vs.var("weight", &[out_dim], config.ws_init),
         bs: vs.var("bias", &[out_dim], config.bs_init),
         nd,
+        frozen_stats: false,
     }
 }
 
@@ -97,13 +123,23 @@ impl super::module::ModuleT for BatchNorm {
                 xs.size()
             )
         };
+        let track_running_stats = self.config.track_running_stats;
+        let (running_mean, running_var, use_batch_stats) = if track_running_stats {
+            (
+                Some(&self.running_mean),
+                Some(&self.running_var),
+                train && !self.frozen_stats,
+            )
+        } else {
+            (None, None, true)
+        };
         Tensor::batch_norm(
             xs,
             Some(&self.ws),
             Some(&self.bs),
-            Some(&self.running_mean),
-            Some(&self.running_var),
-            train,
+            running_mean,
+            running_var,
+            use_batch_stats,
             self.config.momentum,
             self.config.eps,
             self.config.cudnn_enabled,
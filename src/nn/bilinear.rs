@@ -0,0 +1,94 @@
+//! A bilinear layer, `y = x1^T W x2 + b`.
+use crate::Tensor;
+use failure::Fallible;
+use std::borrow::Borrow;
+
+/// Configuration for a bilinear layer.
+#[derive(Debug, Clone, Copy)]
+pub struct BilinearConfig {
+    /// The weight initialization, the default is uniform with a bound
+    /// depending on the first input dimension as in PyTorch.
+    pub ws_init: Option<super::Init>,
+    /// The bias initialization, uses the same default as the weight.
+    pub bs_init: Option<super::Init>,
+    /// When not set, the layer has no bias and no `bias` variable is created.
+    pub bias: bool,
+}
+
+impl Default for BilinearConfig {
+    fn default() -> Self {
+        BilinearConfig {
+            ws_init: None,
+            bs_init: None,
+            bias: true,
+        }
+    }
+}
+
+/// A bilinear layer.
+///
+/// The weight has shape [out_dim, in1_dim, in2_dim] and the output for each
+/// element of the batch is `y[k] = x1^T W[k] x2 + b[k]`.
+#[derive(Debug)]
+pub struct Bilinear {
+    pub ws: Tensor,
+    pub bs: Option<Tensor>,
+}
+
+/// Creates a new bilinear layer.
+pub fn bilinear<'a, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    in1_dim: i64,
+    in2_dim: i64,
+    out_dim: i64,
+    c: BilinearConfig,
+) -> Bilinear {
+    let vs = vs.borrow();
+    let bound = 1.0 / (in1_dim as f64).sqrt();
+    let default_init = super::Init::Uniform {
+        lo: -bound,
+        up: bound,
+    };
+    let bs = if c.bias {
+        Some(vs.var("bias", &[out_dim], c.bs_init.unwrap_or(default_init)))
+    } else {
+        None
+    };
+    let ws_init = c.ws_init.unwrap_or(default_init);
+    Bilinear {
+        ws: vs.var("weight", &[out_dim, in1_dim, in2_dim], ws_init),
+        bs,
+    }
+}
+
+impl Bilinear {
+    /// Applies the layer to two inputs of shape [..., in1_dim] and
+    /// [..., in2_dim] sharing the same leading dimensions, the output has
+    /// shape [..., out_dim].
+    pub fn f_forward(&self, xs1: &Tensor, xs2: &Tensor) -> Fallible<Tensor> {
+        let (size1, size2) = (xs1.size(), xs2.size());
+        let (in1_dim, in2_dim) = (self.ws.size()[1], self.ws.size()[2]);
+        ensure!(
+            !size1.is_empty()
+                && size1.len() == size2.len()
+                && size1[..size1.len() - 1] == size2[..size2.len() - 1],
+            "bilinear: incompatible input shapes {:?} and {:?}",
+            size1,
+            size2
+        );
+        ensure!(
+            size1[size1.len() - 1] == in1_dim && size2[size2.len() - 1] == in2_dim,
+            "bilinear: expected inputs with {} and {} features, got shapes {:?} and {:?}",
+            in1_dim,
+            in2_dim,
+            size1,
+            size2
+        );
+        Tensor::f_bilinear(xs1, xs2, &self.ws, self.bs.as_ref())
+    }
+
+    /// Applies the layer to two inputs, panics if their shapes do not match.
+    pub fn forward(&self, xs1: &Tensor, xs2: &Tensor) -> Tensor {
+        self.f_forward(xs1, xs2).unwrap()
+    }
+}
@@ -0,0 +1,125 @@
+//! Keeping the best checkpoints according to a monitored metric.
+use super::VarStore;
+use failure::Fallible;
+use std::path::{Path, PathBuf};
+
+/// Whether lower or higher values of the monitored metric are better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricMode {
+    /// Lower is better, e.g. for a validation loss.
+    Min,
+    /// Higher is better, e.g. for a validation accuracy.
+    Max,
+}
+
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    step: i64,
+    metric: f64,
+    path: PathBuf,
+}
+
+/// Saves checkpoints of a var-store and only keeps the best ones.
+///
+/// The checkpoints are written to `checkpoint-<step>.ot` files in the
+/// checkpoint directory. Files present in the directory before the creation
+/// of the manager are left untouched.
+#[derive(Debug)]
+pub struct CheckpointManager {
+    dir: PathBuf,
+    mode: MetricMode,
+    keep: usize,
+    // The kept checkpoints sorted from best to worst.
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl CheckpointManager {
+    /// Creates a manager keeping the best `keep` checkpoints in `dir`, the
+    /// directory is created if it does not exist.
+    pub fn new<T: AsRef<Path>>(dir: T, mode: MetricMode, keep: usize) -> Fallible<Self> {
+        ensure!(keep > 0, "checkpoint manager has to keep at least one file");
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(CheckpointManager {
+            dir: dir.as_ref().to_path_buf(),
+            mode,
+            keep,
+            checkpoints: vec![],
+        })
+    }
+
+    fn is_better(&self, lhs: f64, rhs: f64) -> bool {
+        match self.mode {
+            MetricMode::Min => lhs < rhs,
+            MetricMode::Max => lhs > rhs,
+        }
+    }
+
+    /// Saves a checkpoint of `vs` if `metric` is among the best ones and
+    /// removes the checkpoint that is no longer in the best `keep`.
+    ///
+    /// On ties, the older checkpoint is considered better. A NaN metric is
+    /// never saved. The file is first written to a temporary file that is
+    /// then renamed so that a crash does not leave a truncated checkpoint.
+    /// Returns the path of the new checkpoint if one was saved.
+    pub fn maybe_save(
+        &mut self,
+        vs: &VarStore,
+        step: i64,
+        metric: f64,
+    ) -> Fallible<Option<PathBuf>> {
+        if metric.is_nan() {
+            return Ok(None);
+        }
+        let path = self.dir.join(format!("checkpoint-{}.ot", step));
+        // Saving the same step twice overwrites the previous file, the existing
+        // entry for this step is ignored when ranking the new checkpoint.
+        let others: Vec<&Checkpoint> = self.checkpoints.iter().filter(|c| c.path != path).collect();
+        let index = others
+            .iter()
+            .position(|c| self.is_better(metric, c.metric))
+            .unwrap_or(others.len());
+        if index >= self.keep {
+            return Ok(None);
+        }
+        let tmp_path = self.dir.join(format!("checkpoint-{}.ot.tmp", step));
+        vs.save(&tmp_path)?;
+        std::fs::rename(&tmp_path, &path)?;
+        self.checkpoints.retain(|c| c.path != path);
+        self.checkpoints.insert(
+            index,
+            Checkpoint {
+                step,
+                metric,
+                path: path.clone(),
+            },
+        );
+        while self.checkpoints.len() > self.keep {
+            let removed = self.checkpoints.pop().unwrap();
+            std::fs::remove_file(&removed.path)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// The path of the best checkpoint, if any.
+    pub fn best_path(&self) -> Option<&Path> {
+        self.checkpoints.first().map(|c| c.path.as_path())
+    }
+
+    /// The step and metric of the best checkpoint, if any.
+    pub fn best(&self) -> Option<(i64, f64)> {
+        self.checkpoints.first().map(|c| (c.step, c.metric))
+    }
+
+    /// The paths of the kept checkpoints, from best to worst.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.checkpoints.iter().map(|c| c.path.as_path()).collect()
+    }
+
+    /// Loads the best checkpoint in `vs`, fails if no checkpoint has been saved.
+    pub fn load_best(&self, vs: &mut VarStore) -> Fallible<()> {
+        match self.best_path() {
+            Some(path) => vs.load(path),
+            None => bail!("no checkpoint has been saved"),
+        }
+    }
+}
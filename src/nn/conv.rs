@@ -1,13 +1,27 @@
 //! N-dimensional convolution layers.
 use super::Path;
 use crate::Tensor;
+use failure::Fallible;
 use std::borrow::Borrow;
 
+/// How the input of a convolution gets padded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding<ND> {
+    /// Zero padding added on both sides of each dimension.
+    Explicit(ND),
+    /// Zero padding such that the output has the same spatial size as the
+    /// input, this requires a stride of 1. As in PyTorch, when the total
+    /// padding for a dimension is odd the extra padding goes on the right.
+    Same,
+    /// No padding.
+    Valid,
+}
+
 /// Generic convolution config.
 #[derive(Debug, Clone, Copy)]
 pub struct ConvConfigND<ND> {
     pub stride: ND,
-    pub padding: ND,
+    pub padding: Padding<ND>,
     pub dilation: ND,
     pub groups: i64,
     pub bias: bool,
@@ -22,7 +36,7 @@ impl Default for ConvConfig {
     fn default() -> Self {
         ConvConfig {
             stride: 1,
-            padding: 0,
+            padding: Padding::Explicit(0),
             dilation: 1,
             groups: 1,
             bias: true,
@@ -36,7 +50,7 @@ impl Default for ConvConfigND<[i64; 2]> {
     fn default() -> Self {
         ConvConfigND::<[i64; 2]> {
             stride: [1, 1],
-            padding: [0, 0],
+            padding: Padding::Explicit([0, 0]),
             dilation: [1, 1],
             groups: 1,
             bias: true,
@@ -61,6 +75,11 @@ pub struct Conv<ND> {
     pub ws: Tensor,
     pub bs: Option<Tensor>,
     config: ConvConfigND<ND>,
+    // The padding applied on both sides of each dimension by the convolution.
+    padding: Vec<i64>,
+    // The extra right padding applied to the input for `Padding::Same` when
+    // the total padding is odd, in the `constant_pad_nd` format.
+    extra_padding: Option<Vec<i64>>,
 }
 
 /// One dimension convolution layer.
@@ -72,15 +91,53 @@ pub type Conv2D = Conv<[i64; 2]>;
 /// Three dimensions convolution layer.
 pub type Conv3D = Conv<[i64; 3]>;
 
+// Returns the symmetric padding to be used by the convolution together with
+// the extra right padding needed when it is not symmetric.
+fn resolve_padding<ND: AsRef<[i64]>>(
+    config: &ConvConfigND<ND>,
+    ksizes: &[i64],
+) -> Fallible<(Vec<i64>, Option<Vec<i64>>)> {
+    match &config.padding {
+        Padding::Explicit(padding) => Ok((padding.as_ref().to_vec(), None)),
+        Padding::Valid => Ok((vec![0; ksizes.len()], None)),
+        Padding::Same => {
+            ensure!(
+                config.stride.as_ref().iter().all(|&s| s == 1),
+                "same padding requires a stride of 1, got {:?}",
+                config.stride.as_ref()
+            );
+            let dilation = config.dilation.as_ref();
+            let total: Vec<i64> = (0..ksizes.len())
+                .map(|i| dilation[i] * (ksizes[i] - 1))
+                .collect();
+            let padding = total.iter().map(|t| t / 2).collect();
+            let extra_padding = if total.iter().all(|t| t % 2 == 0) {
+                None
+            } else {
+                // constant_pad_nd starts with the last dimension.
+                Some(total.iter().rev().flat_map(|t| vec![0, t % 2]).collect())
+            };
+            Ok((padding, extra_padding))
+        }
+    }
+}
+
 /// Creates a new convolution layer for any number of dimensions.
-pub fn conv<'a, ND: std::convert::AsRef<[i64]>, T: Borrow<super::Path<'a>>>(
+///
+/// An error is returned if `Padding::Same` is used with a stride different
+/// from 1.
+pub fn f_conv<'a, ND, T: Borrow<super::Path<'a>>>(
     vs: T,
     in_dim: i64,
     out_dim: i64,
     ksizes: ND,
     config: ConvConfigND<ND>,
-) -> Conv<ND> {
+) -> Fallible<Conv<ND>>
+where
+    ND: AsRef<[i64]>,
+{
     let vs = vs.borrow();
+    let (padding, extra_padding) = resolve_padding(&config, ksizes.as_ref())?;
     let bs = if config.bias {
         Some(vs.var("bias", &[out_dim], config.bs_init))
     } else {
@@ -89,29 +146,55 @@ pub fn conv<'a, ND: std::convert::AsRef<[i64]>, T: Borrow<super::Path<'a>>>(
     let mut weight_size = vec![out_dim, in_dim / config.groups];
     weight_size.extend(ksizes.as_ref().iter());
     let ws = vs.var("weight", weight_size.as_slice(), config.ws_init);
-    Conv { ws, bs, config }
+    Ok(Conv {
+        ws,
+        bs,
+        config,
+        padding,
+        extra_padding,
+    })
+}
+
+/// Creates a new convolution layer for any number of dimensions, panics on
+/// invalid configs, see `f_conv`.
+pub fn conv<'a, ND, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    in_dim: i64,
+    out_dim: i64,
+    ksizes: ND,
+    config: ConvConfigND<ND>,
+) -> Conv<ND>
+where
+    ND: AsRef<[i64]>,
+{
+    f_conv(vs, in_dim, out_dim, ksizes, config).unwrap()
 }
 
-trait Create: std::convert::AsRef<[i64]> + std::marker::Sized {
+trait Create: AsRef<[i64]> + std::marker::Sized {
     fn make_array(i: i64) -> Self;
 
-    fn conv<'a, T: Borrow<super::Path<'a>>>(
+    fn f_conv<'a, T: Borrow<super::Path<'a>>>(
         vs: T,
         in_dim: i64,
         out_dim: i64,
         ksize: i64,
         config: ConvConfig,
-    ) -> Conv<Self> {
+    ) -> Fallible<Conv<Self>> {
+        let padding = match config.padding {
+            Padding::Explicit(padding) => Padding::Explicit(Self::make_array(padding)),
+            Padding::Same => Padding::Same,
+            Padding::Valid => Padding::Valid,
+        };
         let config = ConvConfigND::<Self> {
             stride: Self::make_array(config.stride),
-            padding: Self::make_array(config.padding),
+            padding,
             dilation: Self::make_array(config.dilation),
             groups: config.groups,
             bias: config.bias,
             ws_init: config.ws_init,
             bs_init: config.bs_init,
         };
-        conv(vs, in_dim, out_dim, Self::make_array(ksize), config)
+        f_conv(vs, in_dim, out_dim, Self::make_array(ksize), config)
     }
 }
 
@@ -134,58 +217,133 @@ impl Create for [i64; 3] {
 }
 
 /// Creates a new one dimension convolution layer.
+///
+/// The input has shape [N, C, L] and its length dimension gets zero padded on
+/// both sides by `padding`, so the output length is
+/// `(L + 2 * padding - dilation * (k - 1) - 1) / stride + 1`. For a causal
+/// convolution, where each output only depends on the current and previous
+/// inputs, use no padding and left pad the input by `dilation * (k - 1)`, e.g.
+/// with `xs.constant_pad_nd(&[dilation * (k - 1), 0])`.
+///
+/// An error is returned if `Padding::Same` is used with a stride different
+/// from 1.
+pub fn f_conv1d<'a, T: Borrow<Path<'a>>>(
+    vs: T,
+    i: i64,
+    o: i64,
+    k: i64,
+    c: ConvConfig,
+) -> Fallible<Conv1D> {
+    <[i64; 1]>::f_conv(vs, i, o, k, c)
+}
+
+/// Creates a new one dimension convolution layer, see `f_conv1d`.
 pub fn conv1d<'a, T: Borrow<Path<'a>>>(vs: T, i: i64, o: i64, k: i64, c: ConvConfig) -> Conv1D {
-    <[i64; 1]>::conv(vs, i, o, k, c)
+    f_conv1d(vs, i, o, k, c).unwrap()
+}
+
+/// Creates a new two dimension convolution layer, see `f_conv1d` for the
+/// padding errors.
+pub fn f_conv2d<'a, T: Borrow<Path<'a>>>(
+    vs: T,
+    i: i64,
+    o: i64,
+    k: i64,
+    c: ConvConfig,
+) -> Fallible<Conv2D> {
+    <[i64; 2]>::f_conv(vs, i, o, k, c)
 }
 
 /// Creates a new two dimension convolution layer.
 pub fn conv2d<'a, T: Borrow<Path<'a>>>(vs: T, i: i64, o: i64, k: i64, c: ConvConfig) -> Conv2D {
-    <[i64; 2]>::conv(vs, i, o, k, c)
+    f_conv2d(vs, i, o, k, c).unwrap()
+}
+
+/// Creates a new three dimension convolution layer, see `f_conv1d` for the
+/// padding errors.
+pub fn f_conv3d<'a, T: Borrow<Path<'a>>>(
+    vs: T,
+    i: i64,
+    o: i64,
+    k: i64,
+    c: ConvConfig,
+) -> Fallible<Conv3D> {
+    <[i64; 3]>::f_conv(vs, i, o, k, c)
 }
 
 /// Creates a new three dimension convolution layer.
 pub fn conv3d<'a, T: Borrow<Path<'a>>>(vs: T, i: i64, o: i64, k: i64, c: ConvConfig) -> Conv3D {
-    <[i64; 3]>::conv(vs, i, o, k, c)
+    f_conv3d(vs, i, o, k, c).unwrap()
+}
+
+impl<ND> Conv<ND> {
+    fn pad_input(&self, xs: &Tensor) -> Tensor {
+        match &self.extra_padding {
+            Some(padding) => xs.constant_pad_nd(padding),
+            None => xs.shallow_clone(),
+        }
+    }
+}
+
+// Each output value uses one multiply-accumulate per weight of its filter.
+fn conv_macs(ys: &Tensor, ws: &Tensor) -> u64 {
+    let out_dim = ws.size()[0];
+    (ys.numel() * (ws.numel() / out_dim)) as u64
 }
 
 impl super::module::Module for Conv1D {
     fn forward(&self, xs: &Tensor) -> Tensor {
-        Tensor::conv1d(
+        let xs = self.pad_input(xs);
+        let ys = Tensor::conv1d(
             &xs,
             &self.ws,
             self.bs.as_ref(),
             &self.config.stride,
-            &self.config.padding,
+            &self.padding,
             &self.config.dilation,
             self.config.groups,
-        )
+        );
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_macs(&ys, &self.ws))
+        }
+        ys
     }
 }
 
 impl super::module::Module for Conv2D {
     fn forward(&self, xs: &Tensor) -> Tensor {
-        Tensor::conv2d(
+        let xs = self.pad_input(xs);
+        let ys = Tensor::conv2d(
             &xs,
             &self.ws,
             self.bs.as_ref(),
             &self.config.stride,
-            &self.config.padding,
+            &self.padding,
             &self.config.dilation,
             self.config.groups,
-        )
+        );
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_macs(&ys, &self.ws))
+        }
+        ys
     }
 }
 
 impl super::module::Module for Conv3D {
     fn forward(&self, xs: &Tensor) -> Tensor {
-        Tensor::conv3d(
+        let xs = self.pad_input(xs);
+        let ys = Tensor::conv3d(
             &xs,
             &self.ws,
             self.bs.as_ref(),
             &self.config.stride,
-            &self.config.padding,
+            &self.padding,
             &self.config.dilation,
             self.config.groups,
-        )
+        );
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_macs(&ys, &self.ws))
+        }
+        ys
     }
 }
@@ -145,8 +145,17 @@ pub fn conv_transpose3d<'a, T: Borrow<Path<'a>>>(
     <[i64; 3]>::conv_transpose(vs, i, o, k, c)
 }
 
+// Each input value gets multiplied by the weights of its input channel.
+fn conv_transpose_macs(xs: &Tensor, ws: &Tensor) -> u64 {
+    let in_dim = ws.size()[0];
+    (xs.numel() * (ws.numel() / in_dim)) as u64
+}
+
 impl super::module::Module for ConvTranspose1D {
     fn forward(&self, xs: &Tensor) -> Tensor {
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_transpose_macs(xs, &self.ws))
+        }
         Tensor::conv_transpose1d(
             &xs,
             &self.ws,
@@ -162,6 +171,9 @@ impl super::module::Module for ConvTranspose1D {
 
 impl super::module::Module for ConvTranspose2D {
     fn forward(&self, xs: &Tensor) -> Tensor {
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_transpose_macs(xs, &self.ws))
+        }
         Tensor::conv_transpose2d(
             &xs,
             &self.ws,
@@ -177,6 +189,9 @@ impl super::module::Module for ConvTranspose2D {
 
 impl super::module::Module for ConvTranspose3D {
     fn forward(&self, xs: &Tensor) -> Tensor {
+        if crate::profiler::is_counting_flops() {
+            crate::profiler::add_flops(conv_transpose_macs(xs, &self.ws))
+        }
         Tensor::conv_transpose3d(
             &xs,
             &self.ws,
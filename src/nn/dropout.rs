@@ -0,0 +1,111 @@
+//! Dropout layers.
+use super::ModuleT;
+use crate::Tensor;
+use std::cell::RefCell;
+
+/// A dropout layer, each element is zeroed with probability `p` in training
+/// mode and the remaining ones are scaled by `1 / (1 - p)`.
+///
+/// For testing purposes, the mask can be provided explicitly with
+/// `forward_with_mask`, and the masks drawn in training mode can be recorded
+/// by setting `record_mask`.
+#[derive(Debug)]
+pub struct Dropout {
+    pub p: f64,
+    /// When set, the mask used by the last training mode forward pass is kept
+    /// and can be retrieved with `last_mask`.
+    pub record_mask: bool,
+    last_mask: RefCell<Option<Tensor>>,
+}
+
+/// Creates a new dropout layer with dropout probability `p`.
+pub fn dropout(p: f64) -> Dropout {
+    assert!(
+        (0. ..=1.).contains(&p),
+        "dropout probability {} is not between 0 and 1",
+        p
+    );
+    Dropout {
+        p,
+        record_mask: false,
+        last_mask: RefCell::new(None),
+    }
+}
+
+impl Dropout {
+    /// Applies dropout using `mask` rather than a random mask.
+    ///
+    /// The mask contains ones for the elements that are kept and zeros for
+    /// the dropped ones, it has to be broadcastable to the input shape. The
+    /// kept elements are scaled by `1 / (1 - p)` as in training mode.
+    pub fn forward_with_mask(&self, xs: &Tensor, mask: &Tensor) -> Tensor {
+        let scale = if self.p < 1. { 1. / (1. - self.p) } else { 0. };
+        xs * mask.to_kind(xs.kind()) * scale
+    }
+
+    /// The mask used by the last training mode forward pass when
+    /// `record_mask` is set.
+    pub fn last_mask(&self) -> Option<Tensor> {
+        self.last_mask
+            .borrow()
+            .as_ref()
+            .map(|mask| mask.shallow_clone())
+    }
+}
+
+impl ModuleT for Dropout {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        if !train {
+            xs.shallow_clone()
+        } else if self.record_mask {
+            let mask = crate::no_grad(|| xs.empty_like().bernoulli_1(1. - self.p));
+            let ys = self.forward_with_mask(xs, &mask);
+            *self.last_mask.borrow_mut() = Some(mask);
+            ys
+        } else {
+            xs.dropout(self.p, train)
+        }
+    }
+}
+
+/// Stochastic depth, whole samples of a residual branch are zeroed with
+/// probability `drop_prob` in training mode and the remaining ones are scaled
+/// by `1 / (1 - drop_prob)`.
+///
+/// Contrary to `Dropout`, a single value is drawn per sample, i.e. per entry
+/// of the first dimension, so that the branch is either fully kept or fully
+/// dropped for each sample. The layer is applied to the branch output before
+/// adding the residual connection.
+///
+/// See "Deep Networks with Stochastic Depth", Huang et al.
+/// https://arxiv.org/abs/1603.09382
+#[derive(Debug, Clone, Copy)]
+pub struct DropPath {
+    pub drop_prob: f64,
+}
+
+/// Creates a new stochastic depth layer with drop probability `drop_prob`.
+pub fn drop_path(drop_prob: f64) -> DropPath {
+    assert!(
+        (0. ..=1.).contains(&drop_prob),
+        "drop path probability {} is not between 0 and 1",
+        drop_prob
+    );
+    DropPath { drop_prob }
+}
+
+impl ModuleT for DropPath {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        if !train || self.drop_prob == 0. || xs.dim() == 0 {
+            return xs.shallow_clone();
+        }
+        if self.drop_prob == 1. {
+            return xs.zeros_like();
+        }
+        let mut shape = vec![1; xs.dim()];
+        shape[0] = xs.size()[0];
+        let keep_prob = 1. - self.drop_prob;
+        let mask = crate::no_grad(|| xs.new_empty(&shape).bernoulli_1(keep_prob));
+        xs * mask / keep_prob
+    }
+}
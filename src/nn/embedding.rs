@@ -0,0 +1,69 @@
+//! Embedding layers.
+use crate::Tensor;
+use std::borrow::Borrow;
+
+/// Configuration option for an embedding layer.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddingConfig {
+    /// When set, the gradient with respect to the weight matrix is a sparse
+    /// tensor that only contains the rows used in the forward pass.
+    ///
+    /// Sparse gradients are supported by the SGD optimizer when no weight
+    /// decay is used, the other optimizers require dense gradients.
+    pub sparse: bool,
+    pub scale_grad_by_freq: bool,
+    pub ws_init: super::Init,
+    /// The embedding at this index does not contribute to the gradient,
+    /// a negative value disables this.
+    pub padding_idx: i64,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        EmbeddingConfig {
+            sparse: false,
+            scale_grad_by_freq: false,
+            ws_init: super::Init::Randn {
+                mean: 0.,
+                stdev: 1.,
+            },
+            padding_idx: -1,
+        }
+    }
+}
+
+/// An embedding layer.
+///
+/// This maps integer indexes to dense vectors, the input can have any shape
+/// and the output has an additional trailing dimension of size `embedding_dim`.
+#[derive(Debug)]
+pub struct Embedding {
+    pub ws: Tensor,
+    config: EmbeddingConfig,
+}
+
+/// Creates a new embedding layer.
+pub fn embedding<'a, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    num_embeddings: i64,
+    embedding_dim: i64,
+    config: EmbeddingConfig,
+) -> Embedding {
+    let vs = vs.borrow();
+    Embedding {
+        ws: vs.var("weight", &[num_embeddings, embedding_dim], config.ws_init),
+        config,
+    }
+}
+
+impl super::module::Module for Embedding {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        Tensor::embedding(
+            &self.ws,
+            xs,
+            self.config.padding_idx,
+            self.config.scale_grad_by_freq,
+            self.config.sparse,
+        )
+    }
+}
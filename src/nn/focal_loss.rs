@@ -0,0 +1,78 @@
+//! The focal loss, a cross-entropy variant that down-weights easy examples.
+//!
+//! See "Focal Loss for Dense Object Detection", Lin et al.
+//! https://arxiv.org/abs/1708.02002
+use crate::{Kind, Reduction, Tensor};
+
+/// A focal loss.
+///
+/// For a sample whose ground-truth class has probability `pt` the loss is
+/// `-alpha * (1 - pt) ^ gamma * log(pt)`. Using a `gamma` of 0 and no `alpha`
+/// results in the standard cross-entropy loss.
+#[derive(Debug, Clone, Copy)]
+pub struct FocalLoss {
+    pub gamma: f64,
+    pub alpha: Option<f64>,
+    pub reduction: Reduction,
+}
+
+impl Default for FocalLoss {
+    fn default() -> Self {
+        FocalLoss {
+            gamma: 2.,
+            alpha: None,
+            reduction: Reduction::Mean,
+        }
+    }
+}
+
+impl FocalLoss {
+    fn reduce(&self, losses: Tensor) -> Tensor {
+        match self.reduction {
+            Reduction::None => losses,
+            Reduction::Mean => losses.mean(),
+            Reduction::Sum => losses.sum(),
+        }
+    }
+
+    // Computes the loss from the log-probabilities of the ground-truth classes.
+    fn focal_term(&self, log_pt: &Tensor) -> Tensor {
+        let pt = log_pt.exp();
+        -(1. - pt).pow(self.gamma) * log_pt
+    }
+
+    /// The multi-class focal loss based on a softmax over the logits.
+    ///
+    /// The logits have shape [N, C] and the targets contain the N class indexes.
+    /// When set, `alpha` is used as a constant weight for all the classes.
+    pub fn forward(&self, logits: &Tensor, targets: &Tensor) -> Tensor {
+        let targets = targets.to_kind(Kind::Int64).unsqueeze(-1);
+        let log_pt = logits
+            .log_softmax(-1)
+            .gather(-1, &targets, false)
+            .squeeze1(-1);
+        let losses = self.focal_term(&log_pt);
+        let losses = match self.alpha {
+            None => losses,
+            Some(alpha) => losses * alpha,
+        };
+        self.reduce(losses)
+    }
+
+    /// The binary focal loss based on a sigmoid, as used in RetinaNet.
+    ///
+    /// The logits and targets have the same shape, the targets being 0 or 1.
+    /// When set, `alpha` weights the positive examples and `1 - alpha` the
+    /// negative ones.
+    pub fn binary(&self, logits: &Tensor, targets: &Tensor) -> Tensor {
+        let targets = targets.to_kind(logits.kind());
+        // log(sigmoid(-x)) is used for the negative class to stay in log space.
+        let log_pt = &targets * logits.log_sigmoid() + (1. - &targets) * (-logits).log_sigmoid();
+        let losses = self.focal_term(&log_pt);
+        let losses = match self.alpha {
+            None => losses,
+            Some(alpha) => losses * (&targets * alpha + (1. - &targets) * (1. - alpha)),
+        };
+        self.reduce(losses)
+    }
+}
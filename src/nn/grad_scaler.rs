@@ -0,0 +1,128 @@
+//! Dynamic loss scaling for mixed precision training.
+use super::Optimizer;
+use crate::Tensor;
+
+/// A dynamic loss scaler, similar to PyTorch `torch.cuda.amp.GradScaler`.
+///
+/// The loss is multiplied by a scale factor before the backward pass so that
+/// small half-precision gradients do not underflow. The gradients are then
+/// divided by this factor before the optimizer step. When some gradients
+/// overflow, the step is skipped and the scale is reduced by `backoff_factor`.
+/// After `growth_interval` consecutive steps without overflow, the scale is
+/// multiplied by `growth_factor`.
+///
+/// With gradient accumulation, `scale` is applied to the loss of each
+/// micro-batch whereas `unscale_`, `step` and `update` are called once per
+/// effective batch, after all the micro-batch gradients have been accumulated.
+/// The overflow check then covers the gradients of all the micro-batches.
+/// ```ignore
+/// for (i, (xs, ys)) in batches.enumerate() {
+///     let loss = model.forward(&xs).cross_entropy_for_logits(&ys);
+///     scaler.scale(&(loss / accumulation_steps as f64)).backward();
+///     if (i + 1) % accumulation_steps == 0 {
+///         scaler.step(&opt);
+///         scaler.update();
+///         opt.zero_grad();
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct GradScaler {
+    pub growth_factor: f64,
+    pub backoff_factor: f64,
+    pub growth_interval: i64,
+    scale: f64,
+    growth_tracker: i64,
+    // Whether some non-finite gradients were found when unscaling the current
+    // effective batch, `None` if the gradients have not been unscaled yet.
+    found_inf: Option<bool>,
+}
+
+/// Creates a loss scaler starting with scale `init_scale`.
+pub fn grad_scaler(init_scale: f64) -> GradScaler {
+    GradScaler {
+        growth_factor: 2.,
+        backoff_factor: 0.5,
+        growth_interval: 2000,
+        scale: init_scale,
+        growth_tracker: 0,
+        found_inf: None,
+    }
+}
+
+impl Default for GradScaler {
+    fn default() -> Self {
+        grad_scaler(65536.)
+    }
+}
+
+impl GradScaler {
+    /// The current scale factor.
+    pub fn get_scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Multiplies the loss by the current scale factor.
+    pub fn scale(&self, loss: &Tensor) -> Tensor {
+        loss * self.scale
+    }
+
+    /// Divides the gradients tracked by the optimizer by the scale factor and
+    /// checks them for infinite or NaN values.
+    ///
+    /// This has to be called at most once per effective batch, after all the
+    /// micro-batch gradients have been accumulated, e.g. to clip the unscaled
+    /// gradients before calling `step`. Further calls before `update` have no
+    /// effect. Returns true if some gradients are not finite.
+    pub fn unscale_<T>(&mut self, opt: &Optimizer<T>) -> bool {
+        if let Some(found_inf) = self.found_inf {
+            return found_inf;
+        }
+        let _no_grad = crate::no_grad_guard();
+        let inv_scale = 1. / self.scale;
+        let mut found_inf = false;
+        for tensor in opt.trainable_variables().iter() {
+            let mut grad = tensor.grad();
+            if grad.defined() {
+                let _t = grad.g_mul_1(inv_scale);
+                // A NaN or infinite entry results in a non-finite sum.
+                if !grad.sum().item::<f64>().is_finite() {
+                    found_inf = true
+                }
+            }
+        }
+        self.found_inf = Some(found_inf);
+        found_inf
+    }
+
+    /// Unscales the gradients if `unscale_` has not been called yet and
+    /// performs an optimizer step unless some gradients are not finite.
+    ///
+    /// Returns true if the step has been performed.
+    pub fn step<T>(&mut self, opt: &Optimizer<T>) -> bool {
+        let found_inf = self.unscale_(opt);
+        if !found_inf {
+            opt.step()
+        }
+        !found_inf
+    }
+
+    /// Updates the scale factor at the end of an effective batch, this has to
+    /// be called after `step`.
+    pub fn update(&mut self) {
+        match self.found_inf.take() {
+            Some(true) => {
+                self.scale *= self.backoff_factor;
+                self.growth_tracker = 0
+            }
+            Some(false) => {
+                self.growth_tracker += 1;
+                if self.growth_tracker >= self.growth_interval {
+                    self.scale *= self.growth_factor;
+                    self.growth_tracker = 0
+                }
+            }
+            None => (),
+        }
+    }
+}
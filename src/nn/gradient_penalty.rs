@@ -0,0 +1,34 @@
+//! The gradient penalty used to train Wasserstein GANs.
+//!
+//! See "Improved Training of Wasserstein GANs", Gulrajani et al.
+//! https://arxiv.org/abs/1704.00028
+use crate::{Kind, Tensor};
+
+/// Computes the gradient penalty of a discriminator.
+///
+/// The penalty is evaluated on random interpolations between the `real` and
+/// `fake` samples, it is `lambda` times the mean of `(|grad| - 1)^2` where
+/// `grad` is the gradient of the discriminator output with respect to each
+/// interpolated sample. The gradient is computed with `create_graph` set so
+/// that back-propagating the penalty reaches the discriminator variables.
+pub fn gradient_penalty(
+    disc: &dyn Fn(&Tensor) -> Tensor,
+    real: &Tensor,
+    fake: &Tensor,
+    lambda: f64,
+) -> Tensor {
+    let size = real.size();
+    let mut alpha_size = vec![1; size.len()];
+    alpha_size[0] = size[0];
+    let alpha = Tensor::rand(&alpha_size, (Kind::Float, real.device())).to_kind(real.kind());
+    let interpolated =
+        (&alpha * real.detach() + (1. - &alpha) * fake.detach()).set_requires_grad(true);
+    let scores = disc(&interpolated);
+    let grads = Tensor::run_backward(&[scores.sum()], &[&interpolated], true, true);
+    let grad_norms = grads[0]
+        .view(&[size[0], -1])
+        .pow(2.)
+        .sum2(&[1], false)
+        .sqrt();
+    (grad_norms - 1.).pow(2.).mean() * lambda
+}
@@ -0,0 +1,31 @@
+//! A gradient reversal layer as used for domain adaptation.
+//!
+//! See "Unsupervised Domain Adaptation by Backpropagation", Ganin et al.
+//! https://arxiv.org/abs/1409.7495
+use crate::Tensor;
+
+/// A layer acting as identity in the forward pass and multiplying the
+/// gradient by `-lambda` in the backward pass.
+#[derive(Debug, Clone, Copy)]
+pub struct GradientReversal {
+    pub lambda: f64,
+}
+
+/// Creates a new gradient reversal layer.
+pub fn gradient_reversal(lambda: f64) -> GradientReversal {
+    GradientReversal { lambda }
+}
+
+impl super::module::Module for GradientReversal {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        if !xs.requires_grad() {
+            return xs.shallow_clone();
+        }
+        // The view creates a new node in the graph so that the hook only
+        // applies to the gradient flowing through this layer.
+        let ys = xs.view_as(xs);
+        let lambda = self.lambda;
+        let _handle = ys.register_hook(move |grad| grad * -lambda);
+        ys
+    }
+}
@@ -0,0 +1,107 @@
+//! A sequential layer supporting forward hooks on its named sub-layers.
+//!
+//! This makes it possible to capture intermediate activations, e.g. for
+//! feature extraction or knowledge distillation, without changing the
+//! forward code of the layers.
+use super::ModuleT;
+use crate::Tensor;
+use failure::Fallible;
+
+// A forward hook, called on the output of a sub-layer.
+type HookFn = Box<dyn Fn(&Tensor)>;
+
+/// A sequential layer where each sub-layer has a name and forward hooks.
+///
+/// Layers added via `add` are named after their index, starting from "0".
+pub struct HookedSeq {
+    layers: Vec<(String, Box<dyn ModuleT>)>,
+    hooks: Vec<(usize, HookFn)>,
+}
+
+impl std::fmt::Debug for HookedSeq {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HookedSeq")
+            .field("layers", &self.layers)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+/// Creates a new empty sequential layer with hooks.
+pub fn hooked_seq() -> HookedSeq {
+    HookedSeq {
+        layers: vec![],
+        hooks: vec![],
+    }
+}
+
+impl HookedSeq {
+    /// The number of sub-layers embedded in this layer.
+    pub fn len(&self) -> i64 {
+        self.layers.len() as i64
+    }
+
+    /// Returns true if this layer does not have any sub-layer.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Appends a layer after all the current layers, the layer is named after
+    /// its index.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<M: ModuleT + 'static>(self, layer: M) -> Self {
+        let name = self.layers.len().to_string();
+        self.add_named(&name, layer)
+    }
+
+    /// Appends a named layer after all the current layers.
+    pub fn add_named<M: ModuleT + 'static>(mut self, name: &str, layer: M) -> Self {
+        self.layers.push((name.to_string(), Box::new(layer)));
+        self
+    }
+
+    /// Appends a closure after all the current layers.
+    pub fn add_fn<F>(self, f: F) -> Self
+    where
+        F: 'static,
+        F: Fn(&Tensor) -> Tensor,
+    {
+        self.add(super::func(f))
+    }
+
+    /// Registers a closure called on the output of the layer named `name`
+    /// each time the forward pass runs.
+    ///
+    /// An error is returned if there is no layer with this name.
+    pub fn register_forward_hook<F>(&mut self, name: &str, f: F) -> Fallible<()>
+    where
+        F: 'static,
+        F: Fn(&Tensor),
+    {
+        match self.layers.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.hooks.push((index, Box::new(f)));
+                Ok(())
+            }
+            None => bail!("cannot find layer {} to register a forward hook", name),
+        }
+    }
+
+    /// Removes all the forward hooks.
+    pub fn clear_forward_hooks(&mut self) {
+        self.hooks.clear()
+    }
+}
+
+impl ModuleT for HookedSeq {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        let mut xs = xs.shallow_clone();
+        for (index, (_, layer)) in self.layers.iter().enumerate() {
+            xs = layer.forward_t(&xs, train);
+            for (_, hook) in self.hooks.iter().filter(|(i, _)| *i == index) {
+                hook(&xs)
+            }
+        }
+        xs
+    }
+}
@@ -1,5 +1,7 @@
 //! Variable initialization.
 use crate::{Device, Kind, Tensor};
+use rand::distributions::{Distribution, Normal};
+use rand::{rngs::StdRng, SeedableRng};
 
 /// Variable initializations.
 #[derive(Debug, Copy, Clone)]
@@ -10,6 +12,14 @@ pub enum Init {
     /// Random normal with some mean and standard deviation.
     Randn { mean: f64, stdev: f64 },
 
+    /// Random normal with some mean and standard deviation, using a dedicated
+    /// generator with the specified seed.
+    ///
+    /// The generated values only depend on the seed and on the shape, they
+    /// do not depend on the global seed or on the order in which the other
+    /// variables are initialized.
+    RandnSeeded { mean: f64, stdev: f64, seed: u64 },
+
     /// Uniform initialization between some lower and upper bounds.
     Uniform { lo: f64, up: f64 },
 
@@ -38,6 +48,9 @@ pub fn init(i: Init, dims: &[i64], device: Device) -> Tensor {
                 Tensor::randn(dims, (Kind::Float, device)) * stdev + mean
             }
         }
+        Init::RandnSeeded { mean, stdev, seed } => {
+            randn_seeded(dims, mean, stdev, seed).to_device(device)
+        }
         Init::KaimingUniform => {
             let fan_in: i64 = dims.iter().skip(1).product();
             let bound = (1.0 / fan_in as f64).sqrt();
@@ -46,6 +59,14 @@ pub fn init(i: Init, dims: &[i64], device: Device) -> Tensor {
     }
 }
 
+fn randn_seeded(dims: &[i64], mean: f64, stdev: f64, seed: u64) -> Tensor {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let normal = Normal::new(mean, stdev);
+    let numel: i64 = dims.iter().product();
+    let values: Vec<f32> = (0..numel).map(|_| normal.sample(&mut rng) as f32).collect();
+    Tensor::of_slice(&values).view(dims)
+}
+
 impl Init {
     /// Re-initializes an existing tensor with the specified initialization
     pub fn set(self, tensor: &mut Tensor) {
@@ -64,6 +85,9 @@ impl Init {
             Init::Randn { mean, stdev } => {
                 tensor.copy_(&(tensor.randn_like() * stdev + mean));
             }
+            Init::RandnSeeded { mean, stdev, seed } => {
+                tensor.copy_(&randn_seeded(&tensor.size(), mean, stdev, seed));
+            }
         }
     }
 }
@@ -0,0 +1,33 @@
+//! A learnable per-channel scaling layer.
+//!
+//! See "Going deeper with Image Transformers", Touvron et al.
+//! https://arxiv.org/abs/2103.17239
+use crate::Tensor;
+use std::borrow::Borrow;
+
+/// Multiplies its input by a learnable per-channel factor.
+///
+/// This is typically applied to the output of residual branches so that each
+/// branch initially contributes little to the output, which stabilizes the
+/// training of deep transformers. The channels are along the last dimension,
+/// e.g. (N, L, C) inputs for vision transformers or channels-last ConvNeXt
+/// blocks.
+#[derive(Debug)]
+pub struct LayerScale {
+    pub gamma: Tensor,
+}
+
+/// Creates a new LayerScale layer for `dim` channels, `gamma` is initialized
+/// to `init_value`, usually a small value like 1e-6.
+pub fn layer_scale<'a, T: Borrow<super::Path<'a>>>(vs: T, dim: i64, init_value: f64) -> LayerScale {
+    let gamma = vs
+        .borrow()
+        .var("gamma", &[dim], super::Init::Const(init_value));
+    LayerScale { gamma }
+}
+
+impl super::module::Module for LayerScale {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        xs * &self.gamma
+    }
+}
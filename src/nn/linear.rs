@@ -6,6 +6,8 @@ use std::borrow::Borrow;
 #[derive(Debug, Clone, Copy)]
 pub struct LinearConfig {
     pub ws_init: super::Init,
+    /// The bias initialization, the default is uniform with a bound depending
+    /// on the input dimension.
     pub bs_init: Option<super::Init>,
 }
 
@@ -46,8 +48,42 @@ pub fn linear<'a, T: Borrow<super::Path<'a>>>(
     }
 }
 
+fn add_flops(xs: &Tensor, ws: &Tensor) {
+    if crate::profiler::is_counting_flops() {
+        let in_dim = ws.size()[1];
+        crate::profiler::add_flops((xs.numel() / in_dim * ws.numel()) as u64)
+    }
+}
+
 impl super::module::Module for Linear {
     fn forward(&self, xs: &Tensor) -> Tensor {
+        add_flops(xs, &self.ws);
         xs.matmul(&self.ws.tr()) + &self.bs
     }
 }
+
+/// A linear fully-connected layer without bias.
+#[derive(Debug)]
+pub struct LinearNoBias {
+    pub ws: Tensor,
+}
+
+/// Creates a new linear layer without bias, only the `weight` variable is
+/// created.
+pub fn linear_no_bias<'a, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    in_dim: i64,
+    out_dim: i64,
+    ws_init: super::Init,
+) -> LinearNoBias {
+    LinearNoBias {
+        ws: vs.borrow().var("weight", &[out_dim, in_dim], ws_init),
+    }
+}
+
+impl super::module::Module for LinearNoBias {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        add_flops(xs, &self.ws);
+        xs.matmul(&self.ws.tr())
+    }
+}
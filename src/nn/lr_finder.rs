@@ -0,0 +1,65 @@
+//! A learning rate range test to select the learning rate before training.
+use super::{Optimizer, VarStore};
+use crate::Tensor;
+
+/// Trains for up to `num_iters` batches while increasing the learning rate
+/// geometrically from `start_lr` to `end_lr`, and returns the (lr, loss)
+/// pairs.
+///
+/// The loss returned by `loss_fn` for each batch is recorded before the
+/// corresponding optimization step. The sweep stops early when the loss is
+/// not finite or more than four times the lowest loss seen so far, or when
+/// `batches` is exhausted. A good learning rate is usually a bit below the
+/// one reaching the lowest loss, where the loss decreases the fastest.
+///
+/// The variables of `vs` are restored to their initial values at the end of
+/// the sweep. The optimizer state, e.g. momentum, is not restored so a new
+/// optimizer should be used for the actual training.
+pub fn lr_finder<T, I, F>(
+    vs: &VarStore,
+    opt: &mut Optimizer<T>,
+    batches: I,
+    mut loss_fn: F,
+    start_lr: f64,
+    end_lr: f64,
+    num_iters: usize,
+) -> Vec<(f64, f64)>
+where
+    I: IntoIterator<Item = (Tensor, Tensor)>,
+    F: FnMut(&Tensor, &Tensor) -> Tensor,
+{
+    let snapshot: Vec<(Tensor, Tensor)> = crate::no_grad(|| {
+        vs.variables()
+            .into_values()
+            .map(|var| {
+                let copy = var.copy();
+                (var, copy)
+            })
+            .collect()
+    });
+    let mut history = vec![];
+    let mut best_loss = f64::INFINITY;
+    for (iter, (xs, ys)) in batches.into_iter().take(num_iters).enumerate() {
+        let ratio = if num_iters > 1 {
+            iter as f64 / (num_iters - 1) as f64
+        } else {
+            0.
+        };
+        let lr = start_lr * (end_lr / start_lr).powf(ratio);
+        opt.set_lr(lr);
+        let loss = loss_fn(&xs, &ys);
+        let loss_value = loss.item::<f64>();
+        history.push((lr, loss_value));
+        if !loss_value.is_finite() || loss_value > 4. * best_loss {
+            break;
+        }
+        best_loss = best_loss.min(loss_value);
+        opt.backward_step(&loss);
+    }
+    crate::no_grad(|| {
+        for (mut var, copy) in snapshot.into_iter() {
+            var.copy_(&copy)
+        }
+    });
+    history
+}
@@ -6,14 +6,23 @@ mod init;
 pub use init::{init, Init};
 
 mod var_store;
-pub use var_store::{Path, VarStore};
+pub use var_store::{Path, SaveHeader, VarStore};
 
 mod module;
 pub use module::{Module, ModuleT};
 
+mod activation;
+pub use activation::*;
+
 mod linear;
 pub use linear::*;
 
+mod bilinear;
+pub use bilinear::*;
+
+mod checkpoint;
+pub use checkpoint::*;
+
 mod conv;
 pub use conv::*;
 
@@ -23,18 +32,62 @@ pub use conv_transpose::*;
 mod batch_norm;
 pub use batch_norm::*;
 
+mod dropout;
+pub use dropout::*;
+
+mod embedding;
+pub use embedding::*;
+
 mod rnn;
 pub use rnn::*;
 
 mod func;
 pub use func::*;
 
+mod focal_loss;
+pub use focal_loss::*;
+
+mod gradient_penalty;
+pub use gradient_penalty::*;
+
+mod gradient_reversal;
+pub use gradient_reversal::*;
+
+mod grad_scaler;
+pub use grad_scaler::*;
+
+mod hooked_seq;
+pub use hooked_seq::*;
+
+mod layer_scale;
+pub use layer_scale::*;
+
+mod lr_finder;
+pub use lr_finder::lr_finder;
+
+mod multihead_attention;
+pub use multihead_attention::*;
+
+mod per_sample_grads;
+pub use per_sample_grads::*;
+
+mod pooling;
+pub use pooling::*;
+
+mod positional_encoding;
+pub use positional_encoding::*;
+
 mod sequential;
 pub use sequential::*;
 
 mod optimizer;
 pub use optimizer::{adam, rms_prop, sgd, Adam, Optimizer, OptimizerConfig, RmsProp, Sgd};
 
+mod scheduler;
+pub use scheduler::{exponential_lr, polynomial_lr, ExponentialLR, PolynomialLR};
+
+pub mod utils;
+
 /// An identity layer. This just propagates its tensor input as output.
 #[derive(Debug)]
 pub struct Id();
@@ -44,3 +97,42 @@ impl ModuleT for Id {
         xs.shallow_clone()
     }
 }
+
+/// An identity module without any parameters, the output shares the data of
+/// the input.
+///
+/// Contrary to `Id` this implements `Module` so it can replace any layer, e.g.
+/// a classifier head when extracting features.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl Module for Identity {
+    fn forward(&self, xs: &crate::Tensor) -> crate::Tensor {
+        xs.shallow_clone()
+    }
+}
+
+/// Flattens a contiguous range of dimensions into a single one.
+///
+/// The default flattens all the dimensions except the first one, e.g. to
+/// convert NCHW feature maps to the input of a linear classifier.
+#[derive(Debug, Clone, Copy)]
+pub struct Flatten {
+    pub start_dim: i64,
+    pub end_dim: i64,
+}
+
+impl Default for Flatten {
+    fn default() -> Self {
+        Flatten {
+            start_dim: 1,
+            end_dim: -1,
+        }
+    }
+}
+
+impl Module for Flatten {
+    fn forward(&self, xs: &crate::Tensor) -> crate::Tensor {
+        xs.flatten(self.start_dim, self.end_dim)
+    }
+}
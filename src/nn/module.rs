@@ -28,7 +28,7 @@ pub trait ModuleT: std::fmt::Debug {
                 .forward_t(&xs.to_device(d), false)
                 .accuracy_for_logits(&ys.to_device(d));
             let size = xs.size()[0] as f64;
-            sum_accuracy += f64::from(&acc) * size;
+            sum_accuracy += acc.item::<f64>() * size;
             sample_count += size;
         }
         sum_accuracy / sample_count
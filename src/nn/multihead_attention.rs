@@ -0,0 +1,204 @@
+//! Multi-head attention.
+//!
+//! See "Attention Is All You Need", Vaswani et al.
+//! https://arxiv.org/abs/1706.03762
+use super::Init;
+use crate::Tensor;
+use std::borrow::Borrow;
+
+/// The score used for masked entries. This is used rather than minus infinity
+/// so that fully masked rows result in a uniform attention instead of NaNs.
+const MASKED_SCORE: f64 = -1e9;
+
+/// Configuration for a multi-head attention layer.
+#[derive(Debug, Clone, Copy)]
+pub struct MultiheadAttentionConfig {
+    /// The dropout probability applied to the attention weights.
+    pub dropout: f64,
+    /// When set, the input and output projections have a bias.
+    pub bias: bool,
+}
+
+impl Default for MultiheadAttentionConfig {
+    fn default() -> Self {
+        MultiheadAttentionConfig {
+            dropout: 0.,
+            bias: true,
+        }
+    }
+}
+
+/// A multi-head attention layer.
+///
+/// The variables use the same names and layout as PyTorch `nn.MultiheadAttention`
+/// so weights can be exchanged: `in_proj_weight` packs the query, key and
+/// value projections along its first dimension and the output projection uses
+/// the `weight` and `bias` variables of the `out_proj` sub-path.
+#[derive(Debug)]
+pub struct MultiheadAttention {
+    pub in_proj_weight: Tensor,
+    pub in_proj_bias: Option<Tensor>,
+    pub out_proj_weight: Tensor,
+    pub out_proj_bias: Option<Tensor>,
+    embed_dim: i64,
+    num_heads: i64,
+    config: MultiheadAttentionConfig,
+}
+
+/// Creates a new multi-head attention layer.
+///
+/// `embed_dim` has to be divisible by `num_heads`.
+pub fn multihead_attention<'a, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    embed_dim: i64,
+    num_heads: i64,
+    config: MultiheadAttentionConfig,
+) -> MultiheadAttention {
+    assert!(
+        num_heads > 0 && embed_dim % num_heads == 0,
+        "embed_dim {} is not divisible by num_heads {}",
+        embed_dim,
+        num_heads
+    );
+    let vs = vs.borrow();
+    // Xavier uniform initialization as in PyTorch.
+    let bound = (6.0 / (4 * embed_dim) as f64).sqrt();
+    let in_proj_weight = vs.var(
+        "in_proj_weight",
+        &[3 * embed_dim, embed_dim],
+        Init::Uniform {
+            lo: -bound,
+            up: bound,
+        },
+    );
+    let in_proj_bias = if config.bias {
+        Some(vs.var("in_proj_bias", &[3 * embed_dim], Init::Const(0.)))
+    } else {
+        None
+    };
+    let out_vs = vs / "out_proj";
+    let out_proj_weight = out_vs.var("weight", &[embed_dim, embed_dim], Init::KaimingUniform);
+    let out_proj_bias = if config.bias {
+        Some(out_vs.var("bias", &[embed_dim], Init::Const(0.)))
+    } else {
+        None
+    };
+    MultiheadAttention {
+        in_proj_weight,
+        in_proj_bias,
+        out_proj_weight,
+        out_proj_bias,
+        embed_dim,
+        num_heads,
+        config,
+    }
+}
+
+impl MultiheadAttention {
+    // Applies the i-th of the packed input projections and splits the heads,
+    // the result has shape (batch * num_heads, seq_len, head_dim).
+    fn in_projection(&self, xs: &Tensor, i: i64) -> Tensor {
+        let (seq_len, batch_size) = (xs.size()[0], xs.size()[1]);
+        let ws = self
+            .in_proj_weight
+            .narrow(0, i * self.embed_dim, self.embed_dim);
+        let ys = xs.matmul(&ws.tr());
+        let ys = match &self.in_proj_bias {
+            Some(bs) => ys + bs.narrow(0, i * self.embed_dim, self.embed_dim),
+            None => ys,
+        };
+        ys.contiguous()
+            .view(&[
+                seq_len,
+                batch_size * self.num_heads,
+                self.embed_dim / self.num_heads,
+            ])
+            .transpose(0, 1)
+    }
+
+    /// Computes the attention of `query` over `key` and `value`.
+    ///
+    /// The query has shape (L, N, E) and the key and value have shape (S, N, E)
+    /// where L is the target length, S the source length, N the batch size and
+    /// E the embedding dimension.
+    ///
+    /// `key_padding_mask` has shape (N, S), its non-zero entries mark the keys
+    /// that are ignored. `attn_mask` has shape (L, S), a floating-point mask is
+    /// added to the attention scores whereas the non-zero entries of an integer
+    /// mask are ignored.
+    ///
+    /// Returns the output with shape (L, N, E) and the attention weights with
+    /// shape (N, L, S), averaged over the heads.
+    pub fn forward_t(
+        &self,
+        query: &Tensor,
+        key: &Tensor,
+        value: &Tensor,
+        key_padding_mask: Option<&Tensor>,
+        attn_mask: Option<&Tensor>,
+        train: bool,
+    ) -> (Tensor, Tensor) {
+        let (tgt_len, batch_size, embed_dim) = query.size3().unwrap();
+        assert_eq!(
+            embed_dim,
+            self.embed_dim,
+            "unexpected query shape {:?}",
+            query.size()
+        );
+        let src_len = key.size()[0];
+        assert!(
+            key.size() == value.size() && key.size() == [src_len, batch_size, embed_dim],
+            "key {:?} and value {:?} are not compatible with query {:?}",
+            key.size(),
+            value.size(),
+            query.size()
+        );
+        let head_dim = embed_dim / self.num_heads;
+        let q = self.in_projection(query, 0) * (head_dim as f64).powf(-0.5);
+        let k = self.in_projection(key, 1);
+        let v = self.in_projection(value, 2);
+        let mut scores = q.bmm(&k.transpose(1, 2));
+        if let Some(mask) = attn_mask {
+            scores = if mask.kind().is_floating_point() {
+                scores + mask
+            } else {
+                scores.masked_fill(&mask.ne(0).unsqueeze(0), MASKED_SCORE)
+            }
+        }
+        if let Some(mask) = key_padding_mask {
+            let mask = mask.ne(0).view(&[batch_size, 1, 1, src_len]);
+            scores = scores
+                .view(&[batch_size, self.num_heads, tgt_len, src_len])
+                .masked_fill(&mask, MASKED_SCORE)
+                .view(&[batch_size * self.num_heads, tgt_len, src_len]);
+        }
+        let weights = scores.softmax(-1);
+        let ys = weights
+            .dropout(self.config.dropout, train)
+            .bmm(&v)
+            .transpose(0, 1)
+            .contiguous()
+            .view(&[tgt_len, batch_size, embed_dim])
+            .matmul(&self.out_proj_weight.tr());
+        let ys = match &self.out_proj_bias {
+            Some(bs) => ys + bs,
+            None => ys,
+        };
+        let weights = weights
+            .view(&[batch_size, self.num_heads, tgt_len, src_len])
+            .mean2(&[1], false);
+        (ys, weights)
+    }
+
+    /// Computes the attention in evaluation mode, see `forward_t`.
+    pub fn forward(
+        &self,
+        query: &Tensor,
+        key: &Tensor,
+        value: &Tensor,
+        key_padding_mask: Option<&Tensor>,
+        attn_mask: Option<&Tensor>,
+    ) -> (Tensor, Tensor) {
+        self.forward_t(query, key, value, key_padding_mask, attn_mask, false)
+    }
+}
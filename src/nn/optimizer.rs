@@ -10,6 +10,10 @@ pub struct Optimizer<T> {
     opt: COptimizer,
     trainable_variables: Vec<Tensor>,
     config: T,
+    // The current learning rate and momentum, these are used when the
+    // underlying optimizer has to be rebuilt.
+    lr: f64,
+    momentum: Option<f64>,
 }
 
 /// Optimizer configurations. These configs can be used to build optimizer.
@@ -28,6 +32,8 @@ where
             opt,
             trainable_variables,
             config: self,
+            lr,
+            momentum: None,
         })
     }
 }
@@ -149,11 +155,61 @@ impl<T> Optimizer<T> {
         self.opt.zero_grad().unwrap()
     }
 
+    /// Zeroes the gradient for the tensors tracked by this optimizer, optionally
+    /// resetting them to undefined tensors instead.
+    ///
+    /// When `set_to_none` is true, the gradients are released rather than being
+    /// filled with zeros and the next backward pass allocates fresh gradient
+    /// tensors. This saves some memory and a fill operation. It should be false
+    /// if some code expects the gradients to be defined before the next backward
+    /// pass, e.g. when accumulating gradients manually into them or when reading
+    /// them for parameters that may not get a gradient.
+    pub fn zero_grad_set_to_none(&self, set_to_none: bool) {
+        if set_to_none {
+            for tensor in self.trainable_variables.iter() {
+                tensor.reset_grad()
+            }
+        } else {
+            self.zero_grad()
+        }
+    }
+
+    /// Adds some parameters to the set of tensors tracked by the optimizer.
+    ///
+    /// This can be used for layers created after the optimizer, e.g. adapters
+    /// added during training. The optimizer state for these parameters, e.g. the
+    /// Adam moment estimates, gets initialized on the next step.
+    pub fn add_parameters(&mut self, params: &[Tensor]) {
+        self.opt.add_parameters(params).unwrap();
+        self.trainable_variables
+            .extend(params.iter().map(|p| p.shallow_clone()))
+    }
+
+    /// The tensors tracked by this optimizer.
+    pub(crate) fn trainable_variables(&self) -> &[Tensor] {
+        &self.trainable_variables
+    }
+
     /// Clips gradient value at some specified maximum value.
     pub fn clip_grad_value(&self, max: f64) {
+        super::utils::clip_grad_value(&self.trainable_variables, max)
+    }
+
+    /// Returns the global L2 norm of the gradients of all the trainable variables.
+    ///
+    /// Variables that do not have a gradient yet are ignored. The gradients are
+    /// not modified.
+    pub fn grad_norm(&self) -> f64 {
+        let _no_grad = crate::no_grad_guard();
+        let mut norm2 = 0.;
         for tensor in self.trainable_variables.iter() {
-            let _t = tensor.grad().clamp_(-max, max);
+            let grad = tensor.grad();
+            if grad.defined() {
+                let norm = grad.norm().item::<f64>();
+                norm2 += norm * norm
+            }
         }
+        norm2.sqrt()
     }
 
     /// Performs an optimization step, updating the tracked tensors based on their gradients.
@@ -180,11 +236,43 @@ impl<T> Optimizer<T> {
 
     /// Sets the optimizer learning rate.
     pub fn set_lr(&mut self, lr: f64) {
-        self.opt.set_learning_rate(lr).unwrap()
+        self.opt.set_learning_rate(lr).unwrap();
+        self.lr = lr
     }
 
     /// Sets the optimizer momentum.
     pub fn set_momentum(&mut self, m: f64) {
-        self.opt.set_momentum(m).unwrap()
+        self.opt.set_momentum(m).unwrap();
+        self.momentum = Some(m)
+    }
+}
+
+impl<T: OptimizerConfig> Optimizer<T> {
+    /// Tracks the trainable variables currently in `vs` instead of the
+    /// previously tracked tensors.
+    ///
+    /// Loading a var-store copies the values into the existing variables so
+    /// this is not needed after `VarStore::load`. It is needed when the
+    /// variables have been replaced, e.g. after creating the layers again
+    /// with a new var-store and loading a checkpoint in it, or after freezing
+    /// or unfreezing some of them. The learning rate and momentum are kept but
+    /// the optimizer state, e.g. the Adam moment estimates, is reset. The
+    /// parameters added with `add_parameters` that are not in `vs` are no
+    /// longer tracked.
+    pub fn f_reattach(&mut self, vs: &VarStore) -> Fallible<()> {
+        let mut opt = self.config.build_copt(self.lr)?;
+        if let Some(momentum) = self.momentum {
+            opt.set_momentum(momentum)?
+        }
+        let trainable_variables = vs.trainable_variables();
+        opt.add_parameters(&trainable_variables)?;
+        self.opt = opt;
+        self.trainable_variables = trainable_variables;
+        Ok(())
+    }
+
+    /// Tracks the trainable variables currently in `vs`, see `f_reattach`.
+    pub fn reattach(&mut self, vs: &VarStore) {
+        self.f_reattach(vs).unwrap()
     }
 }
@@ -0,0 +1,71 @@
+//! Per-sample gradients, as used by differentially private SGD.
+use super::VarStore;
+use crate::Tensor;
+use failure::Fallible;
+use std::collections::HashMap;
+
+/// Computes the gradients of the loss for each sample of a batch.
+///
+/// The tensors in `batch`, e.g. the inputs and the targets, share the same
+/// leading batch dimension. `loss_fn` is called on the slices of these tensors
+/// for each sample, each slice keeping a batch dimension of size 1, and returns
+/// the scalar loss for this sample. The result maps the name of each variable
+/// of `vs` requiring gradients to a tensor holding the per-sample gradients
+/// stacked along a leading batch dimension.
+///
+/// The samples are processed one at a time so this requires one forward and
+/// one backward pass per sample. The `grad` of the variables is not modified.
+pub fn per_sample_grads<F>(
+    vs: &VarStore,
+    batch: &[&Tensor],
+    loss_fn: F,
+) -> Fallible<HashMap<String, Tensor>>
+where
+    F: Fn(&[Tensor]) -> Tensor,
+{
+    ensure!(!batch.is_empty(), "per_sample_grads: empty batch");
+    let batch_size = batch[0].size().first().cloned().unwrap_or(0);
+    for xs in batch.iter() {
+        ensure!(
+            xs.size().first() == Some(&batch_size),
+            "per_sample_grads: inconsistent batch sizes {:?} and {:?}",
+            batch[0].size(),
+            xs.size()
+        )
+    }
+    let (names, variables): (Vec<String>, Vec<Tensor>) = vs
+        .variables()
+        .into_iter()
+        .filter(|(_, v)| v.requires_grad())
+        .unzip();
+    let mut grads: Vec<Vec<Tensor>> = variables.iter().map(|_| vec![]).collect();
+    for index in 0..batch_size {
+        let sample: Vec<Tensor> = batch
+            .iter()
+            .map(|xs| xs.f_narrow(0, index, 1))
+            .collect::<Fallible<_>>()?;
+        let loss = loss_fn(&sample);
+        let sample_grads = Tensor::f_run_backward(&[loss], &variables, false, false)?;
+        for ((grads, grad), var) in grads.iter_mut().zip(sample_grads).zip(variables.iter()) {
+            // Variables that do not contribute to the loss get a zero gradient.
+            let grad = if grad.defined() {
+                grad
+            } else {
+                var.zeros_like()
+            };
+            grads.push(grad)
+        }
+    }
+    let mut result = HashMap::new();
+    for ((name, grads), var) in names.into_iter().zip(grads).zip(variables.iter()) {
+        let stacked = if grads.is_empty() {
+            let mut size = vec![0];
+            size.extend(var.size());
+            var.f_new_zeros(&size)?
+        } else {
+            Tensor::f_stack(&grads, 0)?
+        };
+        result.insert(name, stacked);
+    }
+    Ok(result)
+}
@@ -0,0 +1,28 @@
+//! Adaptive pooling layers.
+//!
+//! The kernel size and stride are computed so that the output has a fixed
+//! spatial size whatever the input size. Pooling to `[1, 1]` gives global
+//! average or max pooling.
+use super::Module;
+use crate::Tensor;
+
+/// A 2D adaptive average pooling layer, the output has the given height and
+/// width.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveAvgPool2d(pub [i64; 2]);
+
+impl Module for AdaptiveAvgPool2d {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        xs.adaptive_avg_pool2d(&self.0)
+    }
+}
+
+/// A 2D adaptive max pooling layer, the output has the given height and width.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveMaxPool2d(pub [i64; 2]);
+
+impl Module for AdaptiveMaxPool2d {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        xs.adaptive_max_pool2d(&self.0).0
+    }
+}
@@ -0,0 +1,89 @@
+//! Positional encodings for sequence models.
+use crate::{Kind, Tensor};
+use std::borrow::Borrow;
+
+/// The kind of positional encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionalEncodingConfig {
+    /// The fixed sinusoidal encoding from "Attention Is All You Need",
+    /// Vaswani et al. https://arxiv.org/abs/1706.03762
+    ///
+    /// `PE(pos, 2i) = sin(pos / 10000^(2i / d_model))` and
+    /// `PE(pos, 2i + 1) = cos(pos / 10000^(2i / d_model))`.
+    Sinusoidal { max_len: i64, d_model: i64 },
+    /// A trainable encoding for each position, initialized with a normal
+    /// distribution of standard deviation 0.02.
+    Learned { max_len: i64, d_model: i64 },
+}
+
+/// Adds a positional encoding to a sequence of embeddings.
+///
+/// The input has shape (N, L, D), or more generally any shape ending with
+/// (L, D), where L is the sequence length and D is `d_model`. L can be up to
+/// `max_len` and the encodings of the first L positions are used. Sequence
+/// first inputs with shape (L, N, D) have to be transposed.
+#[derive(Debug)]
+pub struct PositionalEncoding {
+    /// The encodings with shape (max_len, d_model), this is a buffer for the
+    /// sinusoidal encoding and a trainable variable for the learned one.
+    pub encoding: Tensor,
+    max_len: i64,
+    d_model: i64,
+}
+
+fn sinusoidal_encoding(max_len: i64, d_model: i64) -> Tensor {
+    let options = (Kind::Double, crate::Device::Cpu);
+    let positions = Tensor::arange(max_len, options).unsqueeze(1);
+    let div_term = (Tensor::arange2(0, d_model, 2, options) * (-(10000f64.ln()) / d_model as f64))
+        .exp()
+        .unsqueeze(0);
+    let angles = positions * div_term;
+    // Interleaves the sines and cosines, the last cosine is dropped when
+    // d_model is odd.
+    Tensor::stack(&[angles.sin(), angles.cos()], 2)
+        .view(&[max_len, -1])
+        .narrow(1, 0, d_model)
+}
+
+/// Creates a new positional encoding layer.
+pub fn positional_encoding<'a, T: Borrow<super::Path<'a>>>(
+    vs: T,
+    config: PositionalEncodingConfig,
+) -> PositionalEncoding {
+    let vs = vs.borrow();
+    let (encoding, max_len, d_model) = match config {
+        PositionalEncodingConfig::Sinusoidal { max_len, d_model } => {
+            let mut encoding = vs.zeros_no_train("pe", &[max_len, d_model]);
+            crate::no_grad(|| encoding.copy_(&sinusoidal_encoding(max_len, d_model)));
+            (encoding, max_len, d_model)
+        }
+        PositionalEncodingConfig::Learned { max_len, d_model } => {
+            let init = super::Init::Randn {
+                mean: 0.,
+                stdev: 0.02,
+            };
+            let encoding = vs.var("pe", &[max_len, d_model], init);
+            (encoding, max_len, d_model)
+        }
+    };
+    PositionalEncoding {
+        encoding,
+        max_len,
+        d_model,
+    }
+}
+
+impl super::module::Module for PositionalEncoding {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        let size = xs.size();
+        let dim = size.len();
+        assert!(
+            dim >= 2 && size[dim - 1] == self.d_model && size[dim - 2] <= self.max_len,
+            "expected an input with shape (..., L, {}) where L <= {}, got {:?}",
+            self.d_model,
+            self.max_len,
+            size
+        );
+        xs + self.encoding.narrow(0, 0, size[dim - 2])
+    }
+}
@@ -0,0 +1,70 @@
+//! Learning rate schedulers.
+use super::Optimizer;
+
+/// A polynomial decay of the learning rate.
+///
+/// After `t` steps the learning rate is `lr * (1 - t / total_steps) ^ power`.
+/// The learning rate stays at its final value, zero for a positive `power`,
+/// once `total_steps` have been reached. This also applies from the start when
+/// `total_steps` is zero.
+#[derive(Debug, Clone, Copy)]
+pub struct PolynomialLR {
+    pub lr: f64,
+    pub total_steps: i64,
+    pub power: f64,
+    step: i64,
+}
+
+/// Creates a polynomial decay scheduler starting from learning rate `lr`.
+pub fn polynomial_lr(lr: f64, total_steps: i64, power: f64) -> PolynomialLR {
+    PolynomialLR {
+        lr,
+        total_steps,
+        power,
+        step: 0,
+    }
+}
+
+impl PolynomialLR {
+    /// The learning rate for the current step.
+    pub fn get_lr(&self) -> f64 {
+        if self.step >= self.total_steps {
+            return self.lr * 0f64.powf(self.power);
+        }
+        self.lr * (1. - self.step as f64 / self.total_steps as f64).powf(self.power)
+    }
+
+    /// Increments the step counter and sets the optimizer learning rate accordingly.
+    pub fn step<T>(&mut self, opt: &mut Optimizer<T>) {
+        self.step += 1;
+        opt.set_lr(self.get_lr())
+    }
+}
+
+/// An exponential decay of the learning rate.
+///
+/// After `t` steps the learning rate is `lr * gamma ^ t`.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialLR {
+    pub lr: f64,
+    pub gamma: f64,
+    step: i64,
+}
+
+/// Creates an exponential decay scheduler starting from learning rate `lr`.
+pub fn exponential_lr(lr: f64, gamma: f64) -> ExponentialLR {
+    ExponentialLR { lr, gamma, step: 0 }
+}
+
+impl ExponentialLR {
+    /// The learning rate for the current step.
+    pub fn get_lr(&self) -> f64 {
+        self.lr * self.gamma.powf(self.step as f64)
+    }
+
+    /// Increments the step counter and sets the optimizer learning rate accordingly.
+    pub fn step<T>(&mut self, opt: &mut Optimizer<T>) {
+        self.step += 1;
+        opt.set_lr(self.get_lr())
+    }
+}
@@ -1,5 +1,5 @@
 //! A sequential layer used to chain multiple layers and closures.
-use super::{Module, ModuleT};
+use super::{Activation, Module, ModuleT};
 use crate::Tensor;
 
 /// A sequential layer combining multiple other layers.
@@ -56,6 +56,11 @@ impl Sequential {
         self.add(super::func(f))
     }
 
+    /// Appends an activation function after all the current layers.
+    pub fn add_activation(self, activation: Activation) -> Self {
+        self.add(activation)
+    }
+
     /// Applies the forward pass and returns the output for each layer.
     pub fn forward_all(&self, xs: &Tensor, n: Option<usize>) -> Vec<Tensor> {
         if self.layers.is_empty() {
@@ -129,6 +134,11 @@ impl SequentialT {
         self.add(super::func(f))
     }
 
+    /// Appends an activation function after all the current layers.
+    pub fn add_activation(self, activation: Activation) -> Self {
+        self.add(activation)
+    }
+
     /// Appends a closure after all the current layers.
     pub fn add_fn_t<F>(self, f: F) -> Self
     where
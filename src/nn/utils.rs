@@ -0,0 +1,60 @@
+//! Gradient utilities for tensors that are not necessarily part of a var-store.
+use crate::{Kind, Tensor};
+
+/// Clips the gradients of some tensors so that their global norm is at most
+/// `max_norm`.
+///
+/// The norm is computed over all the gradients together as if they were
+/// concatenated into a single vector. `norm_type` is the order of the norm,
+/// e.g. 2 for the L2 norm, and can be `std::f64::INFINITY` for the maximum
+/// absolute value. The gradients are scaled in place by
+/// `max_norm / (norm + 1e-6)` when this is below 1 and tensors without a
+/// gradient are ignored.
+///
+/// Returns the global norm of the gradients before clipping.
+pub fn clip_grad_norm(tensors: &[Tensor], max_norm: f64, norm_type: f64) -> f64 {
+    let _no_grad = crate::no_grad_guard();
+    let grads: Vec<Tensor> = tensors
+        .iter()
+        .map(|tensor| tensor.grad())
+        .filter(|grad| grad.defined())
+        .collect();
+    let total_norm = if norm_type.is_infinite() {
+        grads
+            .iter()
+            .map(|grad| grad.abs().max().item::<f64>())
+            .fold(0., f64::max)
+    } else {
+        grads
+            .iter()
+            .map(|grad| {
+                grad.to_kind(Kind::Double)
+                    .abs()
+                    .pow(norm_type)
+                    .sum()
+                    .item::<f64>()
+            })
+            .sum::<f64>()
+            .powf(1. / norm_type)
+    };
+    let clip_coef = max_norm / (total_norm + 1e-6);
+    if clip_coef < 1. {
+        for mut grad in grads.into_iter() {
+            let _t = grad.g_mul_1(clip_coef);
+        }
+    }
+    total_norm
+}
+
+/// Clamps the gradients of some tensors in place to `[-clip_value, clip_value]`.
+///
+/// Tensors without a gradient are ignored.
+pub fn clip_grad_value(tensors: &[Tensor], clip_value: f64) {
+    let _no_grad = crate::no_grad_guard();
+    for tensor in tensors.iter() {
+        let mut grad = tensor.grad();
+        if grad.defined() {
+            let _t = grad.clamp_(-clip_value, clip_value);
+        }
+    }
+}
@@ -12,10 +12,79 @@ const SEP: char = '|';
 
 // When the variable store is frozen, trainable still is set to tree,
 // however the tensor is not set to require gradients.
+// Shared variables are aliases for variables registered under another name,
+// they are neither trained nor saved separately.
+// Non-persistent variables are buffers that live in the var-store but are
+// not written to or read from checkpoints.
 #[derive(Debug)]
 struct Variable {
     tensor: Tensor,
     trainable: bool,
+    shared: bool,
+    persistent: bool,
+}
+
+impl Variable {
+    // Whether the variable is part of the files written by `save`.
+    fn saved(&self) -> bool {
+        self.persistent && !self.shared
+    }
+}
+
+/// The magic string at the beginning of the files written by `save_versioned`.
+const VERSIONED_MAGIC: &str = "TCH-VARSTORE";
+/// The version of the format used by `save_versioned`.
+const VERSIONED_FORMAT: u32 = 1;
+
+/// The header of a file written by `VarStore::save_versioned`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveHeader {
+    /// The version of the header format.
+    pub format_version: u32,
+    /// The version of the tch crate used to write the file.
+    pub tch_version: String,
+    /// The number of tensors stored in the file.
+    pub tensor_count: usize,
+}
+
+impl SaveHeader {
+    fn read<R: std::io::BufRead>(reader: &mut R) -> Fallible<SaveHeader> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let fields: Vec<&str> = line.trim_end().split(' ').collect();
+        match fields.as_slice() {
+            [magic, format_version, tch_version, tensor_count] if *magic == VERSIONED_MAGIC => {
+                Ok(SaveHeader {
+                    format_version: format_version.parse()?,
+                    tch_version: tch_version.to_string(),
+                    tensor_count: tensor_count.parse()?,
+                })
+            }
+            _ => bail!("not a versioned var-store file"),
+        }
+    }
+}
+
+// A temporary file that gets removed when dropped.
+struct TempFile(std::path::PathBuf);
+
+impl TempFile {
+    fn new() -> TempFile {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "tch-varstore-{}-{}.ot",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+        TempFile(std::env::temp_dir().join(name))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
 }
 
 /// A VarStore is used to store variables used by one or multiple layers.
@@ -62,6 +131,90 @@ impl VarStore {
             .collect()
     }
 
+    /// Returns the variables of this var-store keyed by their names.
+    ///
+    /// Shared variables are skipped as they alias variables registered under
+    /// another name.
+    pub fn variables(&self) -> HashMap<String, Tensor> {
+        let variables = self.variables.lock().unwrap();
+        variables
+            .iter()
+            .filter(|(_, v)| !v.shared)
+            .map(|(name, v)| (name.clone(), v.tensor.shallow_clone()))
+            .collect()
+    }
+
+    // The trainable variables sorted by name, this is the order used by
+    // `flatten` and `unflatten`.
+    fn sorted_trainable_variables(&self) -> Vec<(String, Tensor)> {
+        let variables = self.variables.lock().unwrap();
+        let mut variables: Vec<(String, Tensor)> = variables
+            .iter()
+            .filter(|(_, v)| v.trainable && !v.shared)
+            .map(|(name, v)| (name.clone(), v.tensor.shallow_clone()))
+            .collect();
+        variables.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+        variables
+    }
+
+    /// Returns a one dimension tensor with the values of all the trainable
+    /// variables.
+    ///
+    /// The variables are concatenated in the order of their names so that the
+    /// result can be written back with `unflatten`, e.g. after perturbing it.
+    /// The result uses the kind of the first variable and does not track
+    /// gradients.
+    pub fn flatten(&self) -> Tensor {
+        let _no_grad = crate::no_grad_guard();
+        let variables = self.sorted_trainable_variables();
+        match variables.first() {
+            None => Tensor::zeros(&[0], (Kind::Float, self.device)),
+            Some((_, first)) => {
+                let kind = first.kind();
+                let flat: Vec<Tensor> = variables
+                    .iter()
+                    .map(|(_, v)| v.to_kind(kind).view(&[-1]))
+                    .collect();
+                Tensor::cat(&flat, 0)
+            }
+        }
+    }
+
+    /// Writes the values of a tensor returned by `flatten` back to the
+    /// trainable variables.
+    ///
+    /// An error is returned if the number of elements does not match the total
+    /// size of the trainable variables.
+    pub fn f_unflatten(&mut self, flat: &Tensor) -> Fallible<()> {
+        let _no_grad = crate::no_grad_guard();
+        let mut variables = self.sorted_trainable_variables();
+        let total: i64 = variables.iter().map(|(_, v)| v.numel()).sum();
+        ensure!(
+            flat.size() == [total],
+            "unflatten: expected a tensor of shape [{}], got {:?}",
+            total,
+            flat.size()
+        );
+        let mut start = 0;
+        for (name, var) in variables.iter_mut() {
+            let numel = var.numel();
+            let src = flat
+                .f_narrow(0, start, numel)?
+                .f_view(var.size().as_slice())?
+                .f_to_kind(var.kind())?;
+            var.f_copy_(&src)
+                .map_err(|e| format_err!("{}: {}", name, e))?;
+            start += numel
+        }
+        Ok(())
+    }
+
+    /// Writes the values of a tensor returned by `flatten` back to the
+    /// trainable variables, panics if the sizes do not match.
+    pub fn unflatten(&mut self, flat: &Tensor) {
+        self.f_unflatten(flat).unwrap()
+    }
+
     pub fn root(&self) -> Path {
         Path {
             path: vec![],
@@ -70,33 +223,224 @@ impl VarStore {
     }
 
     /// Saves the var-store variable values to a file.
+    ///
+    /// Non-persistent buffers are not saved.
     pub fn save<T: AsRef<std::path::Path>>(&self, path: T) -> Fallible<()> {
         let variables = self.variables.lock().unwrap();
         let named_tensors = variables
             .iter()
+            .filter(|(_, y)| y.saved())
             .map(|(x, y)| (&x[..], &y.tensor))
             .collect::<Vec<_>>();
         Tensor::save_multi(named_tensors.as_slice(), path)
     }
 
+    /// Saves the var-store variable values to a file with a version header.
+    ///
+    /// The header records the version of the tch crate and the number of
+    /// tensors, `load_versioned` uses it to report incompatibilities between
+    /// versions with a clear error.
+    pub fn save_versioned<T: AsRef<std::path::Path>>(&self, path: T) -> Fallible<()> {
+        use std::io::Write;
+        let tmp = TempFile::new();
+        self.save(&tmp.0)?;
+        let tensor_count = self
+            .variables
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|v| v.saved())
+            .count();
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(
+            file,
+            "{} {} {} {}",
+            VERSIONED_MAGIC,
+            VERSIONED_FORMAT,
+            env!("CARGO_PKG_VERSION"),
+            tensor_count
+        )?;
+        std::io::copy(&mut std::fs::File::open(&tmp.0)?, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads the header of a file written by `save_versioned`.
+    pub fn read_versioned_header<T: AsRef<std::path::Path>>(path: T) -> Fallible<SaveHeader> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path.as_ref())?);
+        SaveHeader::read(&mut reader).map_err(|e| format_err!("{:?}: {}", path.as_ref(), e))
+    }
+
+    /// Loads the var-store variable values from a file written by `save_versioned`.
+    ///
+    /// Files written with a newer format or that cannot be read by libtorch
+    /// result in an error mentioning the tch version used to save them.
+    pub fn load_versioned<T: AsRef<std::path::Path>>(&mut self, path: T) -> Fallible<()> {
+        let path = path.as_ref();
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let header = SaveHeader::read(&mut reader).map_err(|e| format_err!("{:?}: {}", path, e))?;
+        let version_error = |e: &dyn std::fmt::Display| {
+            format_err!(
+                "{:?} was saved with tch {}, loading with tch {}: {}",
+                path,
+                header.tch_version,
+                env!("CARGO_PKG_VERSION"),
+                e
+            )
+        };
+        if header.format_version > VERSIONED_FORMAT {
+            let e = format!("unsupported format version {}", header.format_version);
+            return Err(version_error(&e));
+        }
+        let tmp = TempFile::new();
+        std::io::copy(&mut reader, &mut std::fs::File::create(&tmp.0)?)?;
+        let named_tensors = Tensor::load_multi(&tmp.0).map_err(|e| version_error(&e))?;
+        ensure!(
+            named_tensors.len() == header.tensor_count,
+            "{:?}: the header mentions {} tensors but {} were found",
+            path,
+            header.tensor_count,
+            named_tensors.len()
+        );
+        self.copy_named_tensors(named_tensors, path, &|name| Some(name.to_string()))
+    }
+
     /// Loads the var-store variable values from a file.
+    ///
+    /// The loaded tensors are converted to the kind of the corresponding
+    /// variables, e.g. a checkpoint saved in half-precision can be loaded in a
+    /// single-precision var-store. Their shapes have to match exactly.
     pub fn load<T: AsRef<std::path::Path>>(&mut self, path: T) -> Fallible<()> {
+        self.load_with_mapping(path, &|name| Some(name.to_string()))
+    }
+
+    /// Loads the var-store variable values from a file, renaming the tensors.
+    ///
+    /// The `mapping` closure translates the names used in the file to the
+    /// var-store variable names, the tensors for which it returns `None` are
+    /// skipped. As for `load`, an error is returned if some variables of the
+    /// var-store are not set by the renamed tensors.
+    pub fn load_with_mapping<T: AsRef<std::path::Path>>(
+        &mut self,
+        path: T,
+        mapping: &dyn Fn(&str) -> Option<String>,
+    ) -> Fallible<()> {
         let named_tensors = Tensor::load_multi(&path)?;
-        let named_tensors: HashMap<_, _> = named_tensors.into_iter().collect();
+        self.copy_named_tensors(named_tensors, path.as_ref(), mapping)
+    }
+
+    /// Loads the var-store variable values from a file, permuting the
+    /// dimensions of some tensors before copying them.
+    ///
+    /// `permutations` maps the names of tensors in the file to the permutation
+    /// of their dimensions, e.g. `vec![1, 0]` loads the [in, out] weights of a
+    /// TensorFlow dense layer into a linear layer using [out, in]. Each
+    /// permutation must have the rank of its tensor.
+    pub fn load_with_permutations<T: AsRef<std::path::Path>>(
+        &mut self,
+        path: T,
+        permutations: &HashMap<String, Vec<i64>>,
+    ) -> Fallible<()> {
+        let path = path.as_ref();
+        let mut named_tensors = Tensor::load_multi(path)?;
+        for (name, permutation) in permutations.iter() {
+            let tensor = match named_tensors.iter_mut().find(|(n, _)| n == name) {
+                Some((_, tensor)) => tensor,
+                None => bail!("cannot find {} in {:?} to permute", name, path),
+            };
+            let rank = tensor.dim();
+            let mut sorted = permutation.clone();
+            sorted.sort();
+            ensure!(
+                sorted == (0..rank as i64).collect::<Vec<_>>(),
+                "{}: {:?} is not a permutation of the {} dims of shape {:?}",
+                name,
+                permutation,
+                rank,
+                tensor.size()
+            );
+            *tensor = tensor.f_permute(permutation)?.f_contiguous()?;
+        }
+        self.copy_named_tensors(named_tensors, path, &|name| Some(name.to_string()))
+    }
+
+    fn copy_named_tensors(
+        &mut self,
+        loaded: Vec<(String, Tensor)>,
+        path: &std::path::Path,
+        mapping: &dyn Fn(&str) -> Option<String>,
+    ) -> Fallible<()> {
+        let mut named_tensors = HashMap::new();
+        for (name, tensor) in loaded.into_iter() {
+            if let Some(mapped_name) = mapping(&name) {
+                if named_tensors.insert(mapped_name.clone(), tensor).is_some() {
+                    bail!(
+                        "multiple tensors are mapped to {} in {:?}",
+                        mapped_name,
+                        path
+                    )
+                }
+            }
+        }
         let mut variables = self.variables.lock().unwrap();
-        for (name, var) in variables.iter_mut() {
+        for (name, var) in variables.iter_mut().filter(|(_, v)| v.saved()) {
             match named_tensors.get(name) {
                 Some(src) => crate::no_grad(|| {
+                    ensure!(
+                        src.size() == var.tensor.size(),
+                        "{}: shape {:?} in {:?} differs from {:?} in the var-store",
+                        name,
+                        src.size(),
+                        path,
+                        var.tensor.size()
+                    );
+                    let src = src.f_to_kind(var.tensor.kind())?;
                     var.tensor
-                        .f_copy_(src)
+                        .f_copy_(&src)
                         .map_err(|e| format_err!("{}: {}", name, e))
                 })?,
-                None => return Err(format_err!("cannot find {} in {:?}", name, path.as_ref())),
+                None => return Err(format_err!("cannot find {} in {:?}", name, path)),
             }
         }
         Ok(())
     }
 
+    /// Casts all the floating-point variables of the var-store to the specified kind.
+    ///
+    /// The conversion happens in place so tensors previously returned by the
+    /// var-store, e.g. the ones held by layers, use the new kind too.
+    pub fn set_kind(&mut self, kind: Kind) {
+        let mut variables = self.variables.lock().unwrap();
+        crate::no_grad(|| {
+            for variable in variables.values_mut() {
+                if variable.tensor.kind().is_floating_point() {
+                    let converted = variable.tensor.to_kind(kind);
+                    variable.tensor.set_data(&converted);
+                }
+            }
+        })
+    }
+
+    /// Casts all the floating-point variables to half-precision (16 bits).
+    pub fn half(&mut self) {
+        self.set_kind(Kind::Half)
+    }
+
+    /// Casts all the floating-point variables to bfloat16.
+    pub fn bfloat16(&mut self) {
+        self.set_kind(Kind::BFloat16)
+    }
+
+    /// Casts all the floating-point variables to single-precision (32 bits).
+    pub fn float(&mut self) {
+        self.set_kind(Kind::Float)
+    }
+
+    /// Casts all the floating-point variables to double-precision (64 bits).
+    pub fn double(&mut self) {
+        self.set_kind(Kind::Double)
+    }
+
     pub fn freeze(&mut self) {
         let variables = self.variables.lock().unwrap();
         for variable in variables.values() {
@@ -145,6 +489,17 @@ impl<'a> Path<'a> {
     }
 
     fn add(&self, name: &str, tensor: Tensor, trainable: bool) -> Tensor {
+        self.add_variable(name, tensor, trainable, false, true)
+    }
+
+    fn add_variable(
+        &self,
+        name: &str,
+        tensor: Tensor,
+        trainable: bool,
+        shared: bool,
+        persistent: bool,
+    ) -> Tensor {
         let path = self.path(name);
         let mut variables = self.var_store.variables.lock().unwrap();
         let path = if variables.contains_key(&path) {
@@ -160,6 +515,8 @@ impl<'a> Path<'a> {
         let var = Variable {
             tensor: tensor.shallow_clone(),
             trainable,
+            shared,
+            persistent,
         };
         variables.insert(path, var);
         tensor
@@ -200,6 +557,21 @@ impl<'a> Path<'a> {
         self.var(name, dims, Init::Randn { mean, stdev })
     }
 
+    /// Creates a random normal variable using a dedicated generator seeded with `seed`.
+    ///
+    /// The initial value does not depend on the global seed nor on the order in
+    /// which variables are created.
+    pub fn randn_seeded(
+        &self,
+        name: &str,
+        dims: &[i64],
+        mean: f64,
+        stdev: f64,
+        seed: u64,
+    ) -> Tensor {
+        self.var(name, dims, Init::RandnSeeded { mean, stdev, seed })
+    }
+
     pub fn uniform(&self, name: &str, dims: &[i64], lo: f64, up: f64) -> Tensor {
         self.var(name, dims, Init::Uniform { lo, up })
     }
@@ -208,6 +580,27 @@ impl<'a> Path<'a> {
         self.var(name, dims, Init::KaimingUniform)
     }
 
+    /// Registers an alias for an existing variable, e.g. to tie some weights.
+    ///
+    /// The returned tensor shares its storage and gradient with `existing` so
+    /// updating one of them updates the other. `existing` should be a variable
+    /// of the same var-store: the alias is not returned by `trainable_variables`
+    /// and is not written by `save`, the original variable being used instead.
+    pub fn var_shared(&self, name: &str, existing: &Tensor) -> Tensor {
+        self.add_variable(name, existing.shallow_clone(), false, true, true)
+    }
+
+    /// Registers a non-persistent buffer, e.g. a cached mask, copied to the
+    /// var-store device.
+    ///
+    /// The buffer is not trained and, unlike `zeros_no_train`, it is neither
+    /// written by `save` nor expected by `load`. It is still converted by
+    /// `set_kind` and returned by `variables`.
+    pub fn buffer_non_persistent(&self, name: &str, tensor: &Tensor) -> Tensor {
+        let tensor = tensor.to_device(self.device());
+        self.add_variable(name, tensor, false, false, false)
+    }
+
     pub fn var_copy(&self, name: &str, t: &Tensor) -> Tensor {
         let mut v = self.zeros(name, &t.size());
         crate::no_grad(|| v.copy_(&t));
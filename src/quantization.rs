@@ -0,0 +1,258 @@
+//! Int8 quantization of tensors.
+//!
+//! Per-channel quantization uses a scale and zero point for each slice along
+//! the quantization axis, this preserves more accuracy than per-tensor
+//! quantization for convolution and linear weights where the magnitude varies
+//! a lot between output channels. The calibration helpers compute the
+//! quantization parameters from some sample activations.
+use crate::{Device, Kind, Tensor};
+use failure::Fallible;
+
+// The number of quantized levels for the absolute values of symmetric int8.
+const NUM_QUANTIZED_BINS: usize = 128;
+
+// Returns the histogram of the absolute values, using `num_bins` bins over
+// [0, max_abs].
+fn abs_histogram(activations: &[Tensor], num_bins: usize, max_abs: f64) -> Vec<f64> {
+    let mut histogram = vec![0f64; num_bins];
+    for xs in activations.iter() {
+        let bins = (xs.to_device(Device::Cpu).to_kind(Kind::Double).abs()
+            * (num_bins as f64 / max_abs))
+            .floor()
+            .clamp(0., (num_bins - 1) as f64)
+            .to_kind(Kind::Int64)
+            .view(&[-1]);
+        let counts = Vec::<f64>::from(&bins.bincount::<Tensor>(None, num_bins as i64));
+        for (h, c) in histogram.iter_mut().zip(counts.iter()) {
+            *h += c
+        }
+    }
+    histogram
+}
+
+// Computes KL(p || q) where both distributions are given as unnormalized counts.
+fn kl_divergence(p: &[f64], q: &[f64]) -> f64 {
+    let p_sum: f64 = p.iter().sum();
+    let q_sum: f64 = q.iter().sum();
+    p.iter()
+        .zip(q.iter())
+        .filter(|(&p, _)| p > 0.)
+        .map(|(&p, &q)| {
+            let p = p / p_sum;
+            // Smoothing avoids infinite divergences for empty quantized bins.
+            let q = (q / q_sum).max(1e-12);
+            p * (p / q).ln()
+        })
+        .sum()
+}
+
+// Returns the distribution obtained by quantizing `p` to NUM_QUANTIZED_BINS
+// levels, expanded back to the size of `p`. The counts of each level are
+// spread evenly over the bins that were non-empty in `p`.
+fn quantize_distribution(p: &[f64]) -> Vec<f64> {
+    let len = p.len();
+    let mut q = vec![0f64; len];
+    for level in 0..NUM_QUANTIZED_BINS {
+        let start = level * len / NUM_QUANTIZED_BINS;
+        let end = (level + 1) * len / NUM_QUANTIZED_BINS;
+        let total: f64 = p[start..end].iter().sum();
+        let non_empty = p[start..end].iter().filter(|&&v| v > 0.).count();
+        if non_empty > 0 {
+            for i in start..end {
+                if p[i] > 0. {
+                    q[i] = total / non_empty as f64
+                }
+            }
+        }
+    }
+    q
+}
+
+/// Computes the scale and zero point for symmetric int8 quantization using the
+/// entropy calibration method.
+///
+/// The absolute values of the activations are collected in a histogram with
+/// `num_bins` bins. Each candidate threshold clips the values above it, the
+/// selected threshold is the one minimizing the KL divergence between the
+/// clipped distribution and its quantized version. Compared to using the
+/// maximum absolute value, this discards the outliers that would otherwise
+/// waste most of the quantization range. The returned zero point is always 0.
+///
+/// An error is returned if `num_bins` is smaller than the 128 levels used for
+/// the absolute values of int8.
+pub fn f_kl_calibrate(activations: &[Tensor], num_bins: i64) -> Fallible<(f64, i64)> {
+    let _no_grad = crate::no_grad_guard();
+    ensure!(
+        num_bins >= NUM_QUANTIZED_BINS as i64,
+        "kl_calibrate: expected at least {} bins, got {}",
+        NUM_QUANTIZED_BINS,
+        num_bins
+    );
+    let num_bins = num_bins as usize;
+    let mut max_abs = 0f64;
+    for xs in activations.iter() {
+        max_abs = max_abs.max(xs.f_abs()?.f_max()?.f_item::<f64>()?)
+    }
+    if max_abs == 0. {
+        return Ok((1., 0));
+    }
+    let histogram = abs_histogram(activations, num_bins, max_abs);
+    let bin_width = max_abs / num_bins as f64;
+    let mut best = (f64::INFINITY, num_bins);
+    for threshold_bin in NUM_QUANTIZED_BINS..=num_bins {
+        // The reference distribution gets the clipped outliers in its last bin.
+        let mut p = histogram[..threshold_bin].to_vec();
+        let outliers: f64 = histogram[threshold_bin..].iter().sum();
+        p[threshold_bin - 1] += outliers;
+        let q = quantize_distribution(&histogram[..threshold_bin]);
+        let divergence = kl_divergence(&p, &q);
+        if divergence < best.0 {
+            best = (divergence, threshold_bin)
+        }
+    }
+    let threshold = best.1 as f64 * bin_width;
+    Ok((threshold / 127., 0))
+}
+
+/// Computes the scale and zero point for symmetric int8 quantization using the
+/// entropy calibration method, see `f_kl_calibrate`.
+pub fn kl_calibrate(activations: &[Tensor], num_bins: i64) -> (f64, i64) {
+    f_kl_calibrate(activations, num_bins).unwrap()
+}
+
+/// A tensor quantized with a scale and zero point per channel.
+#[derive(Debug)]
+pub struct PerChannelQuantized {
+    int_repr: Tensor,
+    scales: Vec<f64>,
+    zero_points: Vec<i64>,
+    axis: i64,
+}
+
+fn quantized_range(kind: Kind) -> Fallible<(i64, i64)> {
+    match kind {
+        Kind::Int8 => Ok((-128, 127)),
+        Kind::Uint8 => Ok((0, 255)),
+        kind => bail!("quantize_per_channel: unsupported kind {:?}", kind),
+    }
+}
+
+// Returns a shape that broadcasts a vector of size `size` along `axis`.
+fn channel_shape(rank: usize, axis: i64, size: i64) -> Vec<i64> {
+    let mut shape = vec![1; rank];
+    shape[axis as usize] = size;
+    shape
+}
+
+impl Tensor {
+    /// Quantizes a floating point tensor using a scale and zero point per channel.
+    ///
+    /// The `scales` and `zero_points` slices must have the same length as the
+    /// `axis` dimension. The quantized values are stored using `kind` which can
+    /// either be `Kind::Int8` or `Kind::Uint8`.
+    pub fn f_quantize_per_channel(
+        &self,
+        scales: &[f64],
+        zero_points: &[i64],
+        axis: i64,
+        kind: Kind,
+    ) -> Fallible<PerChannelQuantized> {
+        let (qmin, qmax) = quantized_range(kind)?;
+        let size = self.size();
+        let rank = size.len() as i64;
+        ensure!(
+            -rank <= axis && axis < rank,
+            "quantize_per_channel: axis {} out of range for shape {:?}",
+            axis,
+            size
+        );
+        let axis = (axis + rank) % rank;
+        let channels = size[axis as usize];
+        ensure!(
+            scales.len() as i64 == channels && zero_points.len() as i64 == channels,
+            "quantize_per_channel: {} scales and {} zero points for {} channels",
+            scales.len(),
+            zero_points.len(),
+            channels
+        );
+        // NaN scales are rejected along with the non-positive ones.
+        if let Some(scale) = scales.iter().find(|&&s| s.is_nan() || s <= 0.) {
+            bail!(
+                "quantize_per_channel: scales must be positive, got {}",
+                scale
+            )
+        }
+        if let Some(zp) = zero_points.iter().find(|&&z| z < qmin || z > qmax) {
+            bail!(
+                "quantize_per_channel: zero point {} outside of [{}, {}]",
+                zp,
+                qmin,
+                qmax
+            )
+        }
+        let shape = channel_shape(size.len(), axis, channels);
+        let device = self.device();
+        let scales_t = Tensor::of_slice(scales).to_device(device).view(&shape);
+        let zero_points_t = Tensor::of_slice(zero_points)
+            .to_kind(Kind::Double)
+            .to_device(device)
+            .view(&shape);
+        let int_repr = ((self.f_to_kind(Kind::Double)? / scales_t).round() + zero_points_t)
+            .clamp(qmin as f64, qmax as f64)
+            .to_kind(kind);
+        Ok(PerChannelQuantized {
+            int_repr,
+            scales: scales.to_vec(),
+            zero_points: zero_points.to_vec(),
+            axis,
+        })
+    }
+
+    /// Quantizes a floating point tensor using a scale and zero point per channel.
+    pub fn quantize_per_channel(
+        &self,
+        scales: &[f64],
+        zero_points: &[i64],
+        axis: i64,
+        kind: Kind,
+    ) -> PerChannelQuantized {
+        self.f_quantize_per_channel(scales, zero_points, axis, kind)
+            .unwrap()
+    }
+}
+
+impl PerChannelQuantized {
+    /// The underlying integer values.
+    pub fn int_repr(&self) -> &Tensor {
+        &self.int_repr
+    }
+
+    /// The scale used for each channel.
+    pub fn scales(&self) -> &[f64] {
+        &self.scales
+    }
+
+    /// The zero point used for each channel.
+    pub fn zero_points(&self) -> &[i64] {
+        &self.zero_points
+    }
+
+    /// The dimension along which the scales and zero points apply.
+    pub fn axis(&self) -> i64 {
+        self.axis
+    }
+
+    /// Converts the quantized values back to a float tensor.
+    pub fn dequantize(&self) -> Tensor {
+        let shape = channel_shape(self.int_repr.dim(), self.axis, self.scales.len() as i64);
+        let device = self.int_repr.device();
+        let scales = Tensor::of_slice(&self.scales)
+            .to_device(device)
+            .view(&shape);
+        let zero_points = Tensor::of_slice(&self.zero_points)
+            .to_kind(Kind::Double)
+            .to_device(device)
+            .view(&shape);
+        ((self.int_repr.to_kind(Kind::Double) - zero_points) * scales).to_kind(Kind::Float)
+    }
+}
@@ -0,0 +1,48 @@
+//! Exporting tensors as delimited text, e.g. CSV or TSV.
+use crate::{Device, Kind, Tensor};
+use failure::Fallible;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+impl Tensor {
+    /// Writes a one or two dimension tensor as delimited text, e.g. CSV when
+    /// `delimiter` is ',' or TSV when it is '\t'.
+    ///
+    /// Each row of a two dimension tensor is written on its own line whereas
+    /// the values of a one dimension tensor are written one per line.
+    /// Floating-point values use the precision of the print options, see
+    /// `set_print_options`.
+    pub fn write_csv<T: AsRef<Path>>(&self, path: T, delimiter: char) -> Fallible<()> {
+        let columns = match self.size().as_slice() {
+            &[_] => 1,
+            &[_, columns] => columns,
+            size => bail!(
+                "write_csv only supports one or two dims, got {:?}, use reshape to flatten the leading dims",
+                size
+            ),
+        };
+        let tensor = self.to_device(Device::Cpu);
+        let precision = super::get_print_options().precision;
+        // Integer values are extracted as int64 rather than going through f64
+        // which cannot represent all the large values exactly.
+        let values: Vec<String> = if tensor.kind().is_floating_point() {
+            Vec::<f64>::from(&tensor.f_to_kind(Kind::Double)?)
+                .iter()
+                .map(|v| format!("{:.*}", precision, v))
+                .collect()
+        } else {
+            Vec::<i64>::from(&tensor.f_to_kind(Kind::Int64)?)
+                .iter()
+                .map(|v| v.to_string())
+                .collect()
+        };
+        let mut writer = BufWriter::new(File::create(path.as_ref())?);
+        let delimiter = delimiter.to_string();
+        for row in values.chunks(columns.max(1) as usize) {
+            writeln!(writer, "{}", row.join(&delimiter))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Pretty printing of tensors.
+//!
+//! Large tensors are summarized: only the first and last `edge_items` values
+//! of each dimension are printed and the others are replaced with an ellipsis.
+//! The summarized values are extracted before being copied so the full tensor
+//! is never materialized.
+use crate::{Device, Kind, Tensor};
+use std::sync::Mutex;
+
+/// Options controlling how tensors are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// The number of digits after the decimal point for floating-point values.
+    pub precision: usize,
+    /// The number of elements above which a tensor is summarized.
+    pub threshold: i64,
+    /// The number of items printed at the beginning and end of each dimension
+    /// when summarizing.
+    pub edge_items: i64,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            precision: 4,
+            threshold: 1000,
+            edge_items: 3,
+        }
+    }
+}
+
+lazy_static! {
+    static ref PRINT_OPTIONS: Mutex<PrintOptions> = Mutex::new(PrintOptions::default());
+}
+
+/// Sets the options used when displaying tensors.
+pub fn set_print_options(options: PrintOptions) {
+    *PRINT_OPTIONS.lock().unwrap() = options
+}
+
+/// Returns the options used when displaying tensors.
+pub fn get_print_options() -> PrintOptions {
+    *PRINT_OPTIONS.lock().unwrap()
+}
+
+// The formatted values to display together with their shape. For summarized
+// dimensions the values only contain the edge items.
+struct Values {
+    values: Vec<String>,
+    shape: Vec<i64>,
+    summarized: Vec<bool>,
+}
+
+impl Values {
+    fn new(tensor: &Tensor, options: &PrintOptions) -> Values {
+        let _no_grad = crate::no_grad_guard();
+        let summarize = tensor.numel() > options.threshold;
+        let edge = options.edge_items.max(1);
+        let mut tensor = tensor.shallow_clone();
+        let mut summarized = vec![];
+        for (dim, &size) in tensor.size().iter().enumerate() {
+            let dim = dim as i64;
+            if summarize && size > 2 * edge {
+                tensor = Tensor::cat(
+                    &[
+                        tensor.narrow(dim, 0, edge),
+                        tensor.narrow(dim, size - edge, edge),
+                    ],
+                    dim,
+                );
+                summarized.push(true)
+            } else {
+                summarized.push(false)
+            }
+        }
+        let tensor = tensor.to_device(Device::Cpu);
+        // Integer values go through i64 so that large values are not rounded.
+        let values = if tensor.kind().is_floating_point() {
+            Vec::<f64>::from(&tensor)
+                .iter()
+                .map(|v| format!("{:.*}", options.precision, v))
+                .collect()
+        } else {
+            Vec::<i64>::from(&tensor)
+                .iter()
+                .map(|v| v.to_string())
+                .collect()
+        };
+        Values {
+            values,
+            shape: tensor.size(),
+            summarized,
+        }
+    }
+
+    fn format(&self) -> String {
+        let width = self.values.iter().map(|s| s.len()).max().unwrap_or(0);
+        let mut out = String::new();
+        if self.shape.is_empty() {
+            out.push_str(&self.values[0]);
+        } else {
+            self.format_dim(&self.values, width, 0, 0, &mut out);
+        }
+        out
+    }
+
+    // Formats the values for dimension `dim` starting at `offset`.
+    fn format_dim(
+        &self,
+        formatted: &[String],
+        width: usize,
+        dim: usize,
+        offset: usize,
+        out: &mut String,
+    ) {
+        let size = self.shape[dim] as usize;
+        let stride: usize = self.shape[dim + 1..].iter().product::<i64>() as usize;
+        let is_last = dim + 1 == self.shape.len();
+        let separator = if is_last {
+            ", ".to_string()
+        } else {
+            format!(
+                ",{}{}",
+                "\n".repeat(self.shape.len() - dim - 1),
+                " ".repeat(dim + 1)
+            )
+        };
+        out.push('[');
+        for i in 0..size {
+            if i > 0 {
+                out.push_str(&separator);
+            }
+            if self.summarized[dim] && i == size / 2 {
+                out.push_str("...");
+                out.push_str(&separator);
+            }
+            if is_last {
+                out.push_str(&format!("{:>1$}", formatted[offset + i], width));
+            } else {
+                self.format_dim(formatted, width, dim + 1, offset + i * stride, out);
+            }
+        }
+        out.push(']');
+    }
+}
+
+impl std::fmt::Display for Tensor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if !self.defined() {
+            return write!(f, "Tensor[Undefined]");
+        }
+        let (size, kind, device) = (self.size(), self.kind(), self.device());
+        write!(f, "Tensor[{:?}, {:?}, {:?}]", size, kind, device)?;
+        match kind {
+            Kind::ComplexHalf | Kind::ComplexFloat | Kind::ComplexDouble => Ok(()),
+            _ if self.is_sparse() => Ok(()),
+            _ => {
+                let options = get_print_options();
+                let values = Values::new(self, &options);
+                write!(f, "\n{}", values.format())
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Conversions between tensors and the images of the `image` crate.
+//!
+//! This requires the `image` feature.
+use crate::{Device, Kind, Tensor};
+use failure::Fallible;
+use image::{DynamicImage, GenericImageView, ImageBuffer};
+
+impl Tensor {
+    /// Converts an image to a float tensor with shape (C, H, W) and values
+    /// between 0 and 1.
+    ///
+    /// The number of channels C is 1 for grayscale images, 2 for grayscale
+    /// images with an alpha channel, 3 for RGB images and 4 for RGBA images.
+    /// BGR and BGRA images are converted to RGB and RGBA.
+    pub fn from_image(image: &DynamicImage) -> Tensor {
+        let (width, height) = image.dimensions();
+        let (data, channels) = match image {
+            DynamicImage::ImageLuma8(_) => (image.raw_pixels(), 1),
+            DynamicImage::ImageLumaA8(_) => (image.raw_pixels(), 2),
+            DynamicImage::ImageRgb8(_) => (image.raw_pixels(), 3),
+            DynamicImage::ImageRgba8(_) => (image.raw_pixels(), 4),
+            DynamicImage::ImageBgr8(_) => (image.to_rgb().into_raw(), 3),
+            DynamicImage::ImageBgra8(_) => (image.to_rgba().into_raw(), 4),
+        };
+        Tensor::of_slice(&data)
+            .view(&[height as i64, width as i64, channels])
+            .permute(&[2, 0, 1])
+            .to_kind(Kind::Float)
+            / 255.
+    }
+
+    /// Converts a tensor with shape (C, H, W) and values between 0 and 1 to an
+    /// image, this is the inverse of `from_image`.
+    ///
+    /// The values are clamped to [0, 1] and rounded to the nearest 8-bit
+    /// value, the number of channels determines the image color type.
+    pub fn to_image(&self) -> Fallible<DynamicImage> {
+        let (channels, height, width) = self.size3().map_err(|_| {
+            format_err!(
+                "to_image: expected a CHW tensor, got shape {:?}",
+                self.size()
+            )
+        })?;
+        let data = (self
+            .to_device(Device::Cpu)
+            .to_kind(Kind::Float)
+            .clamp(0., 1.)
+            * 255.)
+            .round()
+            .to_kind(Kind::Uint8)
+            .permute(&[1, 2, 0])
+            .contiguous();
+        let data = Vec::<u8>::from(&data);
+        let (width, height) = (width as u32, height as u32);
+        let image = match channels {
+            1 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+            2 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+            3 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+            4 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+            _ => bail!("to_image: unsupported number of channels {}", channels),
+        };
+        image.ok_or_else(|| format_err!("to_image: inconsistent image buffer size"))
+    }
+}
@@ -0,0 +1,297 @@
+//! Precomputed einsum contraction paths.
+//!
+//! When contracting more than two tensors, the order in which the pairwise
+//! contractions are performed can change the cost by orders of magnitude.
+//! Similarly to opt_einsum, a `Contraction` computes a good order once for
+//! given shapes so that it can be reused when running the same contraction
+//! repeatedly.
+use crate::Tensor;
+use failure::Fallible;
+use std::collections::HashMap;
+
+// Above this number of operands, the greedy strategy is used rather than the
+// exhaustive search.
+const OPTIMAL_MAX_OPERANDS: usize = 6;
+
+// A single einsum applied to some of the current operands. The operands are
+// removed from the list of operands and the result is appended to it.
+#[derive(Debug, Clone)]
+struct Step {
+    operands: Vec<usize>,
+    equation: String,
+}
+
+/// An einsum contraction together with its precomputed contraction path.
+#[derive(Debug, Clone)]
+pub struct Contraction {
+    shapes: Vec<Vec<i64>>,
+    steps: Vec<Step>,
+    cost: f64,
+}
+
+// Returns the subscripts of each input and of the output. When the output is
+// not specified, it contains the subscripts appearing only once in
+// alphabetical order.
+fn parse_equation(equation: &str) -> Fallible<(Vec<Vec<char>>, Vec<char>)> {
+    let equation: String = equation.chars().filter(|c| !c.is_whitespace()).collect();
+    // The libtorch einsum only supports lowercase subscripts.
+    if let Some(c) = equation
+        .chars()
+        .find(|&c| !(c.is_ascii_lowercase() || c == ',' || c == '-' || c == '>'))
+    {
+        bail!(
+            "einsum: unsupported character {:?} in equation {}, subscripts have to be in a-z",
+            c,
+            equation
+        )
+    }
+    let (inputs, output) = match equation.find("->") {
+        Some(index) => (&equation[..index], Some(&equation[index + 2..])),
+        None => (equation.as_str(), None),
+    };
+    let inputs: Vec<Vec<char>> = inputs.split(',').map(|s| s.chars().collect()).collect();
+    let output = match output {
+        Some(output) => output.chars().collect(),
+        None => {
+            let mut output: Vec<char> = inputs
+                .iter()
+                .flatten()
+                .filter(|&c| inputs.iter().flatten().filter(|&d| d == c).count() == 1)
+                .cloned()
+                .collect();
+            output.sort();
+            output
+        }
+    };
+    for (i, &c) in output.iter().enumerate() {
+        ensure!(
+            inputs.iter().any(|input| input.contains(&c)),
+            "einsum: output subscript {} does not appear in the inputs",
+            c
+        );
+        ensure!(
+            !output[..i].contains(&c),
+            "einsum: output subscript {} appears more than once",
+            c
+        );
+    }
+    Ok((inputs, output))
+}
+
+// The subscripts kept when contracting operands i and j, these are the ones
+// used by the output or by another operand.
+fn pair_result(operands: &[Vec<char>], i: usize, j: usize, output: &[char]) -> Vec<char> {
+    if operands.len() == 2 {
+        return output.to_vec();
+    }
+    let mut result = vec![];
+    for &c in operands[i].iter().chain(operands[j].iter()) {
+        let used_elsewhere = operands
+            .iter()
+            .enumerate()
+            .any(|(k, operand)| k != i && k != j && operand.contains(&c));
+        if !result.contains(&c) && (output.contains(&c) || used_elsewhere) {
+            result.push(c)
+        }
+    }
+    result
+}
+
+// The number of multiply-adds for contracting two operands.
+fn pair_cost(lhs: &[char], rhs: &[char], sizes: &HashMap<char, i64>) -> f64 {
+    let mut seen = vec![];
+    let mut cost = 1f64;
+    for &c in lhs.iter().chain(rhs.iter()) {
+        if !seen.contains(&c) {
+            seen.push(c);
+            cost *= sizes[&c] as f64
+        }
+    }
+    cost
+}
+
+// Replaces operands i and j, with i < j, by their contraction.
+fn contract(operands: &[Vec<char>], i: usize, j: usize, result: Vec<char>) -> Vec<Vec<char>> {
+    let mut operands = operands.to_vec();
+    operands.remove(j);
+    operands.remove(i);
+    operands.push(result);
+    operands
+}
+
+// Explores all the pairwise contraction orders and returns the cheapest one.
+fn optimal_path(
+    operands: &[Vec<char>],
+    output: &[char],
+    sizes: &HashMap<char, i64>,
+) -> (f64, Vec<(usize, usize)>) {
+    let mut best = (0., vec![]);
+    let n = operands.len();
+    for i in 0..n {
+        for j in i + 1..n {
+            let cost = pair_cost(&operands[i], &operands[j], sizes);
+            let result = pair_result(operands, i, j, output);
+            let (rest_cost, rest_path) =
+                optimal_path(&contract(operands, i, j, result), output, sizes);
+            if best.1.is_empty() || cost + rest_cost < best.0 {
+                let mut path = vec![(i, j)];
+                path.extend(rest_path);
+                best = (cost + rest_cost, path)
+            }
+        }
+    }
+    best
+}
+
+// Repeatedly contracts the cheapest pair of operands.
+fn greedy_path(
+    operands: &[Vec<char>],
+    output: &[char],
+    sizes: &HashMap<char, i64>,
+) -> (f64, Vec<(usize, usize)>) {
+    let mut operands = operands.to_vec();
+    let (mut total_cost, mut path) = (0., vec![]);
+    while operands.len() > 1 {
+        let mut best = (f64::INFINITY, 0, 1);
+        for i in 0..operands.len() {
+            for j in i + 1..operands.len() {
+                let cost = pair_cost(&operands[i], &operands[j], sizes);
+                if cost < best.0 {
+                    best = (cost, i, j)
+                }
+            }
+        }
+        let (cost, i, j) = best;
+        let result = pair_result(&operands, i, j, output);
+        operands = contract(&operands, i, j, result);
+        total_cost += cost;
+        path.push((i, j))
+    }
+    (total_cost, path)
+}
+
+fn subscripts(operands: &[&[char]], output: &[char]) -> String {
+    let inputs: Vec<String> = operands.iter().map(|s| s.iter().collect()).collect();
+    format!(
+        "{}->{}",
+        inputs.join(","),
+        output.iter().collect::<String>()
+    )
+}
+
+impl Contraction {
+    /// Computes the contraction path for an einsum equation applied to tensors
+    /// with the given shapes.
+    ///
+    /// Up to six operands, all the pairwise contraction orders are explored
+    /// and the one with the fewest multiply-adds is used. Above this a greedy
+    /// strategy contracts the cheapest pair first.
+    pub fn f_compile(equation: &str, shapes: &[&[i64]]) -> Fallible<Contraction> {
+        let (inputs, output) = parse_equation(equation)?;
+        ensure!(
+            inputs.len() == shapes.len(),
+            "einsum: {} operands in equation, got {} shapes",
+            inputs.len(),
+            shapes.len()
+        );
+        let mut sizes = HashMap::new();
+        for (input, shape) in inputs.iter().zip(shapes.iter()) {
+            ensure!(
+                input.len() == shape.len(),
+                "einsum: subscripts {} do not match shape {:?}",
+                input.iter().collect::<String>(),
+                shape
+            );
+            for (&c, &size) in input.iter().zip(shape.iter()) {
+                let previous = *sizes.entry(c).or_insert(size);
+                ensure!(
+                    previous == size,
+                    "einsum: subscript {} has sizes {} and {}",
+                    c,
+                    previous,
+                    size
+                );
+            }
+        }
+        let (cost, steps) = if inputs.len() == 1 {
+            let cost = pair_cost(&inputs[0], &[], &sizes);
+            let equation = subscripts(&[&inputs[0]], &output);
+            (
+                cost,
+                vec![Step {
+                    operands: vec![0],
+                    equation,
+                }],
+            )
+        } else {
+            let (cost, path) = if inputs.len() <= OPTIMAL_MAX_OPERANDS {
+                optimal_path(&inputs, &output, &sizes)
+            } else {
+                greedy_path(&inputs, &output, &sizes)
+            };
+            let mut operands = inputs.clone();
+            let mut steps = vec![];
+            for (i, j) in path.into_iter() {
+                let result = pair_result(&operands, i, j, &output);
+                let equation = subscripts(&[&operands[i], &operands[j]], &result);
+                steps.push(Step {
+                    operands: vec![i, j],
+                    equation,
+                });
+                operands = contract(&operands, i, j, result)
+            }
+            (cost, steps)
+        };
+        Ok(Contraction {
+            shapes: shapes.iter().map(|s| s.to_vec()).collect(),
+            steps,
+            cost,
+        })
+    }
+
+    /// Computes the contraction path for an einsum equation, see `f_compile`.
+    pub fn compile(equation: &str, shapes: &[&[i64]]) -> Contraction {
+        Contraction::f_compile(equation, shapes).unwrap()
+    }
+
+    /// The estimated number of multiply-adds for running the contraction.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// Runs the contraction, the tensors must have the shapes used when
+    /// compiling it.
+    pub fn f_run(&self, tensors: &[&Tensor]) -> Fallible<Tensor> {
+        ensure!(
+            tensors.len() == self.shapes.len(),
+            "einsum: expected {} tensors, got {}",
+            self.shapes.len(),
+            tensors.len()
+        );
+        for (tensor, shape) in tensors.iter().zip(self.shapes.iter()) {
+            ensure!(
+                &tensor.size() == shape,
+                "einsum: expected shape {:?}, got {:?}",
+                shape,
+                tensor.size()
+            );
+        }
+        let mut operands: Vec<Tensor> = tensors.iter().map(|x| x.shallow_clone()).collect();
+        for step in self.steps.iter() {
+            let mut inputs: Vec<Tensor> = step
+                .operands
+                .iter()
+                .rev()
+                .map(|&i| operands.remove(i))
+                .collect();
+            inputs.reverse();
+            operands.push(Tensor::f_einsum(&step.equation, &inputs)?)
+        }
+        Ok(operands.pop().unwrap())
+    }
+
+    /// Runs the contraction, panics if the tensor shapes do not match.
+    pub fn run(&self, tensors: &[&Tensor]) -> Tensor {
+        self.f_run(tensors).unwrap()
+    }
+}
@@ -0,0 +1,88 @@
+//! Chainable in-place activations and clamping.
+//!
+//! These functions modify the storage of the tensor, so all the tensors that
+//! share this storage, e.g. shallow clones and views, see the new values. They
+//! must not be used on tensors whose values are needed to compute gradients,
+//! e.g. the input of a `sigmoid` that is part of a graph which will be
+//! backpropagated through, as this results in an error or in wrong gradients.
+//!
+//! Contrary to the generated `relu_`, `clamp_`, etc. which return a new
+//! handle to the same storage, these return `&mut self` so calls can be
+//! chained.
+use crate::{Scalar, Tensor};
+use failure::Fallible;
+
+macro_rules! impl_in_place {
+    ($f_name:ident, $name:ident, $op:ident, $doc:expr) => {
+        #[doc = $doc]
+        pub fn $f_name(&mut self) -> Fallible<&mut Tensor> {
+            let _ = self.$op()?;
+            Ok(self)
+        }
+
+        #[doc = $doc]
+        pub fn $name(&mut self) -> &mut Tensor {
+            self.$f_name().unwrap()
+        }
+    };
+}
+
+impl Tensor {
+    impl_in_place!(
+        f_relu_inplace,
+        relu_inplace,
+        f_relu_,
+        "Applies relu in place."
+    );
+    impl_in_place!(
+        f_sigmoid_inplace,
+        sigmoid_inplace,
+        f_sigmoid_,
+        "Applies the sigmoid function in place."
+    );
+    impl_in_place!(
+        f_tanh_inplace,
+        tanh_inplace,
+        f_tanh_,
+        "Applies tanh in place."
+    );
+    impl_in_place!(
+        f_abs_inplace,
+        abs_inplace,
+        f_abs_,
+        "Replaces the elements with their absolute values in place."
+    );
+
+    /// Clamps the elements to `[min, max]` in place.
+    pub fn f_clamp_inplace<S: Into<Scalar>>(&mut self, min: S, max: S) -> Fallible<&mut Tensor> {
+        let _ = self.f_clamp_(min, max)?;
+        Ok(self)
+    }
+
+    /// Clamps the elements to `[min, max]` in place.
+    pub fn clamp_inplace<S: Into<Scalar>>(&mut self, min: S, max: S) -> &mut Tensor {
+        self.f_clamp_inplace(min, max).unwrap()
+    }
+
+    /// Clamps the elements to be at least `min` in place.
+    pub fn f_clamp_min_inplace<S: Into<Scalar>>(&mut self, min: S) -> Fallible<&mut Tensor> {
+        let _ = self.f_clamp_min_(min)?;
+        Ok(self)
+    }
+
+    /// Clamps the elements to be at least `min` in place.
+    pub fn clamp_min_inplace<S: Into<Scalar>>(&mut self, min: S) -> &mut Tensor {
+        self.f_clamp_min_inplace(min).unwrap()
+    }
+
+    /// Clamps the elements to be at most `max` in place.
+    pub fn f_clamp_max_inplace<S: Into<Scalar>>(&mut self, max: S) -> Fallible<&mut Tensor> {
+        let _ = self.f_clamp_max_(max)?;
+        Ok(self)
+    }
+
+    /// Clamps the elements to be at most `max` in place.
+    pub fn clamp_max_inplace<S: Into<Scalar>>(&mut self, max: S) -> &mut Tensor {
+        self.f_clamp_max_inplace(max).unwrap()
+    }
+}
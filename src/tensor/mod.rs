@@ -1,12 +1,26 @@
 //! A Torch tensor.
 use crate::{Device, Kind};
 use failure::Fallible;
+use std::convert::TryFrom;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+mod csv;
+mod display;
+#[cfg(feature = "image")]
+mod dynamic_image;
+mod einsum;
+mod in_place;
 mod iter;
 mod npy;
+mod pth;
+mod scatter;
+mod sparse_csr;
 
 pub use super::wrappers::tensor::{no_grad, no_grad_guard, NoGradGuard, Reduction, Tensor};
+pub use display::{get_print_options, set_print_options, PrintOptions};
+pub use einsum::Contraction;
+pub use scatter::ScatterReduce;
+pub use sparse_csr::SparseCsr;
 
 macro_rules! impl_op {
     ($trait:ident, $rhs:ident, $func:ident, $op:ident) => {
@@ -192,18 +206,67 @@ impl Neg for &Tensor {
     }
 }
 
-impl<T: crate::kind::T> From<&[T]> for Tensor {
+impl<T: crate::kind::TensorKind> From<&[T]> for Tensor {
     fn from(v: &[T]) -> Tensor {
         Tensor::of_slice(v)
     }
 }
 
-impl<T: crate::kind::T> From<T> for Tensor {
+impl<T: crate::kind::TensorKind> From<T> for Tensor {
     fn from(v: T) -> Tensor {
         Tensor::of_slice(&[v]).view(&[])
     }
 }
 
+/// The interpolation used by `grid_sample`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridSampleMode {
+    Bilinear,
+    Nearest,
+}
+
+/// The values used by `grid_sample` for grid locations outside of the input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridPaddingMode {
+    /// Use zeros for out-of-bound locations.
+    Zeros,
+    /// Use the closest border values.
+    Border,
+    /// Reflect the input values at the border.
+    Reflection,
+}
+
+/// The memory layout of a tensor, this does not change its logical shape.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryFormat {
+    /// The usual row-major layout.
+    Contiguous,
+    /// For 4D tensors with a logical NCHW shape, the channels are stored last
+    /// as in the NHWC layout. Whether convolutions run faster with this layout
+    /// depends on the kernels available for the device.
+    ChannelsLast,
+}
+
+/// The reduction operations supported by `Tensor::reduce`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReduceOp {
+    Sum,
+    Mean,
+    Max,
+    Min,
+    Prod,
+    /// The unbiased standard deviation.
+    Std,
+    /// The unbiased variance.
+    Var,
+}
+
+// The strides of a tensor with the given size using the channels-last format.
+fn channels_last_strides(size: &[i64]) -> Vec<i64> {
+    let (c, h, w) = (size[1], size[2], size[3]);
+    vec![h * w * c, 1, w * c, c]
+}
+
 impl std::fmt::Debug for Tensor {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "Tensor[{:?}, {:?}]", self.size(), self.kind())
@@ -220,6 +283,46 @@ impl Tensor {
         self.f_totype(kind)
     }
 
+    /// Creates a tensor filled with zeros using the kind and device of this tensor.
+    pub fn f_new_zeros(&self, size: &[i64]) -> Fallible<Tensor> {
+        Tensor::f_zeros(size, (self.kind(), self.device()))
+    }
+
+    /// Creates a tensor filled with zeros using the kind and device of this tensor.
+    pub fn new_zeros(&self, size: &[i64]) -> Tensor {
+        self.f_new_zeros(size).unwrap()
+    }
+
+    /// Creates a tensor filled with ones using the kind and device of this tensor.
+    pub fn f_new_ones(&self, size: &[i64]) -> Fallible<Tensor> {
+        Tensor::f_ones(size, (self.kind(), self.device()))
+    }
+
+    /// Creates a tensor filled with ones using the kind and device of this tensor.
+    pub fn new_ones(&self, size: &[i64]) -> Tensor {
+        self.f_new_ones(size).unwrap()
+    }
+
+    /// Creates an uninitialized tensor using the kind and device of this tensor.
+    pub fn f_new_empty(&self, size: &[i64]) -> Fallible<Tensor> {
+        Tensor::f_empty(size, (self.kind(), self.device()))
+    }
+
+    /// Creates an uninitialized tensor using the kind and device of this tensor.
+    pub fn new_empty(&self, size: &[i64]) -> Tensor {
+        self.f_new_empty(size).unwrap()
+    }
+
+    /// Creates a tensor filled with `value` using the kind and device of this tensor.
+    pub fn f_new_full<S: Into<crate::Scalar>>(&self, size: &[i64], value: S) -> Fallible<Tensor> {
+        Tensor::f_full(size, value, (self.kind(), self.device()))
+    }
+
+    /// Creates a tensor filled with `value` using the kind and device of this tensor.
+    pub fn new_full<S: Into<crate::Scalar>>(&self, size: &[i64], value: S) -> Tensor {
+        self.f_new_full(size, value).unwrap()
+    }
+
     pub fn nll_loss(&self, targets: &Tensor) -> Tensor {
         self.g_nll_loss::<Tensor>(targets, None, 1, -100)
     }
@@ -254,13 +357,11 @@ macro_rules! from_tensor {
             }
         }
 
-        impl From<&Tensor> for $typ {
-            fn from(tensor: &Tensor) -> $typ {
-                let numel = tensor.numel();
-                if numel != 1 {
-                    panic!("expected exactly one element, got {}", numel)
-                }
-                Vec::from(tensor)[0]
+        impl TryFrom<&Tensor> for $typ {
+            type Error = failure::Error;
+
+            fn try_from(tensor: &Tensor) -> Fallible<$typ> {
+                tensor.f_item::<$typ>()
             }
         }
 
@@ -282,9 +383,11 @@ macro_rules! from_tensor {
             }
         }
 
-        impl From<Tensor> for $typ {
-            fn from(tensor: Tensor) -> $typ {
-                $typ::from(&tensor)
+        impl TryFrom<Tensor> for $typ {
+            type Error = failure::Error;
+
+            fn try_from(tensor: Tensor) -> Fallible<$typ> {
+                $typ::try_from(&tensor)
             }
         }
     };
@@ -303,6 +406,50 @@ impl Tensor {
         self.log_softmax(-1).nll_loss(&targets)
     }
 
+    /// Computes the cross-entropy loss with optional class weights, ignoring
+    /// the targets equal to `ignore_index`.
+    ///
+    /// The logits have shape (N, C) and the targets shape (N). The loss of
+    /// each sample is multiplied by the weight of its target class and the
+    /// result is the weighted mean over the samples that are not ignored, i.e.
+    /// the sum of the losses divided by the sum of the corresponding weights.
+    /// This is NaN when all the targets are ignored. As in PyTorch, the
+    /// default ignore index is -100.
+    pub fn f_cross_entropy_for_logits_weighted(
+        &self,
+        targets: &Tensor,
+        weights: Option<&Tensor>,
+        ignore_index: i64,
+    ) -> Fallible<Tensor> {
+        if let Some(weights) = weights {
+            let num_classes = self.size().last().cloned().unwrap_or(0);
+            ensure!(
+                weights.size() == [num_classes],
+                "cross_entropy: weights with shape {:?} for {} classes",
+                weights.size(),
+                num_classes
+            );
+        }
+        self.f_log_softmax(-1)?.f_nll_loss(
+            targets,
+            weights,
+            crate::Reduction::Mean.to_int(),
+            ignore_index,
+        )
+    }
+
+    /// Computes the cross-entropy loss with optional class weights and an
+    /// ignored target index, see `f_cross_entropy_for_logits_weighted`.
+    pub fn cross_entropy_for_logits_weighted(
+        &self,
+        targets: &Tensor,
+        weights: Option<&Tensor>,
+        ignore_index: i64,
+    ) -> Tensor {
+        self.f_cross_entropy_for_logits_weighted(targets, weights, ignore_index)
+            .unwrap()
+    }
+
     /// Returns the average accuracy for some given logits assuming that
     /// targets represent ground-truth.
     pub fn accuracy_for_logits(&self, targets: &Tensor) -> Tensor {
@@ -340,6 +487,10 @@ impl Tensor {
     }
 
     /// Moves a tensor to a specified device.
+    ///
+    /// Copies between two gpus use a direct peer-to-peer transfer when the
+    /// access has been enabled with `Cuda::set_peer_access`, otherwise they go
+    /// through the host memory.
     pub fn to_device(&self, device: Device) -> Tensor {
         self.to(device)
     }
@@ -348,6 +499,16 @@ impl Tensor {
         self.f_to(device)
     }
 
+    /// Moves a tensor to a specified device without waiting for the copy to
+    /// complete.
+    ///
+    /// The copy is only asynchronous from pinned host memory to a cuda
+    /// device, see `pin_memory`. Operations using the result on the same cuda
+    /// stream are run after the copy.
+    pub fn to_device_non_blocking(&self, device: Device) -> Tensor {
+        self.f_to_device_non_blocking(device).unwrap()
+    }
+
     pub fn avg_pool2d_default(&self, ksize: i64) -> Tensor {
         self.avg_pool2d(&[ksize, ksize], &[ksize, ksize], &[0, 0], false, true)
     }
@@ -356,6 +517,1007 @@ impl Tensor {
         self.max_pool2d(&[ksize, ksize], &[ksize, ksize], &[0, 0], &[1, 1], false)
     }
 
+    /// Samples the input tensor at the locations specified by `grid`.
+    ///
+    /// The input has shape (N, C, H, W) and the grid has shape (N, H_out, W_out, 2)
+    /// in the spatial case, or (N, C, D, H, W) and (N, D_out, H_out, W_out, 3) in
+    /// the volumetric case. Grid values are normalized `x, y(, z)` coordinates
+    /// in [-1, 1]. When `align_corners` is true, -1 and 1 refer to the centers
+    /// of the corner pixels, otherwise they refer to the outer edges of these
+    /// pixels, this is the same convention as in PyTorch.
+    pub fn f_grid_sample(
+        &self,
+        grid: &Tensor,
+        mode: GridSampleMode,
+        padding_mode: GridPaddingMode,
+        align_corners: bool,
+    ) -> Fallible<Tensor> {
+        let size = self.size();
+        let grid_size = grid.size();
+        let spatial_dims = match size.len() {
+            4 => 2,
+            5 => 3,
+            _ => bail!("grid_sample: expected a 4d or 5d input, got {:?}", size),
+        };
+        ensure!(
+            grid_size.len() == size.len() && grid_size[grid_size.len() - 1] == spatial_dims,
+            "grid_sample: expected a {}d grid with a last dimension of size {}, got {:?}",
+            size.len(),
+            spatial_dims,
+            grid_size
+        );
+        let mode = match mode {
+            GridSampleMode::Bilinear => 0,
+            GridSampleMode::Nearest => 1,
+        };
+        let padding_mode = match padding_mode {
+            GridPaddingMode::Zeros => 0,
+            GridPaddingMode::Border => 1,
+            GridPaddingMode::Reflection => 2,
+        };
+        if align_corners {
+            return self.f_grid_sampler(grid, mode, padding_mode);
+        }
+        // The underlying op aligns corners, rescale the grid coordinates so that
+        // -1 and 1 point at the pixel edges rather than at the pixel centers. This
+        // cannot be done for spatial dimensions of size 1 as all the coordinates
+        // then map to the single pixel center.
+        let spatial_size = &size[size.len() - spatial_dims as usize..];
+        ensure!(
+            spatial_size.iter().all(|&s| s > 1),
+            "grid_sample: align_corners=false requires spatial dimensions larger than 1, got {:?}",
+            size
+        );
+        let (grid, padding_mode) = if padding_mode == 2 {
+            // Reflections happen at the pixel edges, i.e. at -1 and 1 before the
+            // rescaling. Reflect the coordinates in [-1, 1] here and clip the
+            // rescaled ones to the pixel centers using the border padding.
+            let t = (grid + 1.).f_remainder(4.)?;
+            (1. - (t - 2.).f_abs()?, 1)
+        } else {
+            (grid.shallow_clone(), padding_mode)
+        };
+        let scales: Vec<f64> = spatial_size
+            .iter()
+            .rev()
+            .map(|&s| s as f64 / (s - 1) as f64)
+            .collect();
+        let scales = Tensor::of_slice(&scales)
+            .f_to_kind(grid.kind())?
+            .f_to_device(grid.device())?;
+        self.f_grid_sampler(&(grid * scales), mode, padding_mode)
+    }
+
+    /// Samples the input tensor at the locations specified by `grid`.
+    ///
+    /// See `f_grid_sample` for details, this panics on invalid shapes.
+    pub fn grid_sample(
+        &self,
+        grid: &Tensor,
+        mode: GridSampleMode,
+        padding_mode: GridPaddingMode,
+        align_corners: bool,
+    ) -> Tensor {
+        self.f_grid_sample(grid, mode, padding_mode, align_corners)
+            .unwrap()
+    }
+
+    /// Moves the dimensions in `source` to the positions in `destination`.
+    ///
+    /// The other dimensions keep their relative order. Negative values index
+    /// dimensions from the end, this matches the NumPy `moveaxis` semantics.
+    pub fn f_movedim(&self, source: &[i64], destination: &[i64]) -> Fallible<Tensor> {
+        let rank = self.dim() as i64;
+        ensure!(
+            source.len() == destination.len(),
+            "movedim: source {:?} and destination {:?} have different lengths",
+            source,
+            destination
+        );
+        let normalize = |dims: &[i64]| -> Fallible<Vec<i64>> {
+            let mut normalized = vec![];
+            for &d in dims.iter() {
+                ensure!(
+                    -rank <= d && d < rank,
+                    "movedim: dim {} out of range for {} dims",
+                    d,
+                    rank
+                );
+                let d = (d + rank) % rank;
+                ensure!(!normalized.contains(&d), "movedim: repeated dim {}", d);
+                normalized.push(d);
+            }
+            Ok(normalized)
+        };
+        let source = normalize(source)?;
+        let destination = normalize(destination)?;
+        let mut order: Vec<i64> = (0..rank).filter(|d| !source.contains(d)).collect();
+        let mut moves: Vec<(i64, i64)> = destination.into_iter().zip(source).collect();
+        moves.sort();
+        for (dst, src) in moves.into_iter() {
+            order.insert(dst as usize, src)
+        }
+        self.f_permute(&order)
+    }
+
+    /// Moves the dimensions in `source` to the positions in `destination`.
+    pub fn movedim(&self, source: &[i64], destination: &[i64]) -> Tensor {
+        self.f_movedim(source, destination).unwrap()
+    }
+
+    /// Swaps two dimensions of a tensor, this is an alias for `transpose`.
+    pub fn swapaxes(&self, dim0: i64, dim1: i64) -> Tensor {
+        self.transpose(dim0, dim1)
+    }
+
+    /// Extracts sliding local blocks from a batched 4D input, also known as im2col.
+    ///
+    /// The input has shape [N, C, H, W] and the result has shape
+    /// [N, C * kh * kw, L] where L is the number of blocks. Contrary to
+    /// `unfold` which returns a view with an additional window dimension sharing
+    /// the input storage, the result here is a new tensor.
+    pub fn f_unfold2d(
+        &self,
+        kernel_size: &[i64],
+        dilation: &[i64],
+        padding: &[i64],
+        stride: &[i64],
+    ) -> Fallible<Tensor> {
+        let (n, c, h, w) = self.size4()?;
+        let (kh, kw, dh, dw, ph, pw, sh, sw) = match (kernel_size, dilation, padding, stride) {
+            (&[kh, kw], &[dh, dw], &[ph, pw], &[sh, sw]) => (kh, kw, dh, dw, ph, pw, sh, sw),
+            _ => bail!(
+                "unfold2d: expected two values for kernel size {:?}, dilation {:?}, padding {:?} and stride {:?}",
+                kernel_size,
+                dilation,
+                padding,
+                stride
+            ),
+        };
+        ensure!(
+            kh > 0 && kw > 0 && dh > 0 && dw > 0 && sh > 0 && sw > 0 && ph >= 0 && pw >= 0,
+            "unfold2d: invalid kernel size {:?}, dilation {:?}, padding {:?} or stride {:?}",
+            kernel_size,
+            dilation,
+            padding,
+            stride
+        );
+        let (eh, ew) = (dh * (kh - 1) + 1, dw * (kw - 1) + 1);
+        ensure!(
+            h + 2 * ph >= eh && w + 2 * pw >= ew,
+            "unfold2d: kernel size {:?} with dilation {:?} is too large for input {:?}",
+            kernel_size,
+            dilation,
+            self.size()
+        );
+        let (oh, ow) = ((h + 2 * ph - eh) / sh + 1, (w + 2 * pw - ew) / sw + 1);
+        // The windows have shape [N, C, oh, ow, eh, ew] before applying the dilation.
+        self.f_constant_pad_nd(&[pw, pw, ph, ph])?
+            .f_unfold(2, eh, sh)?
+            .f_unfold(3, ew, sw)?
+            .f_slice(4, 0, eh, dh)?
+            .f_slice(5, 0, ew, dw)?
+            .f_permute(&[0, 1, 4, 5, 2, 3])?
+            .f_reshape(&[n, c * kh * kw, oh * ow])
+    }
+
+    /// Extracts sliding local blocks from a batched 4D input, see `f_unfold2d`.
+    pub fn unfold2d(
+        &self,
+        kernel_size: &[i64],
+        dilation: &[i64],
+        padding: &[i64],
+        stride: &[i64],
+    ) -> Tensor {
+        self.f_unfold2d(kernel_size, dilation, padding, stride)
+            .unwrap()
+    }
+
+    /// Returns a view of the tensor broadcast to the given sizes.
+    ///
+    /// This follows the NumPy broadcasting rules: the trailing dimensions are
+    /// matched and dimensions of size 1 can be expanded. A size of -1 keeps the
+    /// corresponding dimension unchanged. The result shares the input storage.
+    pub fn f_broadcast_to(&self, sizes: &[i64]) -> Fallible<Tensor> {
+        let size = self.size();
+        ensure!(
+            size.len() <= sizes.len(),
+            "broadcast_to: cannot broadcast {:?} to fewer dims {:?}",
+            size,
+            sizes
+        );
+        let offset = sizes.len() - size.len();
+        for (i, &target) in sizes.iter().enumerate() {
+            let compatible = if i < offset {
+                target >= 0
+            } else {
+                let s = size[i - offset];
+                target == -1 || target == s || s == 1 && target >= 0
+            };
+            ensure!(
+                compatible,
+                "broadcast_to: cannot broadcast {:?} to {:?}",
+                size,
+                sizes
+            );
+        }
+        self.f_expand(sizes, false)
+    }
+
+    /// Returns a view of the tensor broadcast to the given sizes.
+    pub fn broadcast_to(&self, sizes: &[i64]) -> Tensor {
+        self.f_broadcast_to(sizes).unwrap()
+    }
+
+    /// Constructs a tensor by repeating this tensor the number of times given
+    /// by `reps` along each dimension.
+    ///
+    /// This follows the NumPy `tile` semantics: when `reps` has fewer entries
+    /// than the tensor has dimensions, it is left padded with ones so e.g. a
+    /// `[2]` repetition only applies to the last dimension. Contrary to
+    /// `repeat`, `reps` can be shorter than the number of dimensions.
+    pub fn f_tile(&self, reps: &[i64]) -> Fallible<Tensor> {
+        let rank = self.dim();
+        let mut padded_reps = vec![1; rank.saturating_sub(reps.len())];
+        padded_reps.extend_from_slice(reps);
+        self.f_repeat(&padded_reps)
+    }
+
+    /// Constructs a tensor by repeating this tensor along each dimension.
+    pub fn tile(&self, reps: &[i64]) -> Tensor {
+        self.f_tile(reps).unwrap()
+    }
+
+    /// Repeats each element of the tensor along dimension `dim`.
+    ///
+    /// Contrary to `tile` and `repeat` which repeat the whole tensor, e.g.
+    /// `[1, 2]` becomes `[1, 2, 1, 2]`, the elements are repeated one after
+    /// the other, `[1, 2]` becomes `[1, 1, 2, 2]`. `repeats` contains either a
+    /// single count used for all the elements or one count per element along
+    /// `dim`. When `dim` is `None`, the tensor is flattened first.
+    pub fn f_repeat_interleave_dim(&self, repeats: &Tensor, dim: Option<i64>) -> Fallible<Tensor> {
+        let (xs, dim) = match dim {
+            Some(dim) => (self.shallow_clone(), dim),
+            None => (self.f_reshape(&[-1])?, 0),
+        };
+        let size = xs.size();
+        let rank = size.len() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "repeat_interleave: dim {} out of range for shape {:?}",
+            dim,
+            size
+        );
+        let dim_size = size[((dim + rank) % rank) as usize];
+        let numel = repeats.numel();
+        ensure!(
+            repeats.dim() <= 1 && (numel == 1 || numel == dim_size),
+            "repeat_interleave: expected 1 or {} repeats, got shape {:?}",
+            dim_size,
+            repeats.size()
+        );
+        let repeats = repeats
+            .f_to_kind(Kind::Int64)?
+            .f_to_device(xs.device())?
+            .f_reshape(&[-1])?;
+        ensure!(
+            numel == 0 || repeats.f_min()?.f_item::<i64>()? >= 0,
+            "repeat_interleave: repeats have to be non-negative"
+        );
+        let repeats = if numel == 1 {
+            repeats.f_expand(&[dim_size], false)?.f_contiguous()?
+        } else {
+            repeats
+        };
+        xs.f_repeat_interleave1(&repeats, dim)
+    }
+
+    /// Repeats each element of the tensor along dimension `dim`, see
+    /// `f_repeat_interleave_dim`.
+    pub fn repeat_interleave_dim(&self, repeats: &Tensor, dim: Option<i64>) -> Tensor {
+        self.f_repeat_interleave_dim(repeats, dim).unwrap()
+    }
+
+    /// Repeats each element of the tensor `repeats` times along dimension
+    /// `dim`, the tensor is flattened first when `dim` is `None`.
+    pub fn f_repeat_interleave_scalar(&self, repeats: i64, dim: Option<i64>) -> Fallible<Tensor> {
+        self.f_repeat_interleave_dim(&Tensor::from(repeats), dim)
+    }
+
+    /// Repeats each element of the tensor `repeats` times along dimension `dim`.
+    pub fn repeat_interleave_scalar(&self, repeats: i64, dim: Option<i64>) -> Tensor {
+        self.f_repeat_interleave_scalar(repeats, dim).unwrap()
+    }
+
+    /// Pads the last dimensions of the tensor with a constant value.
+    ///
+    /// As in PyTorch `nn.functional.pad`, `pad` contains pairs of left and
+    /// right padding sizes starting from the last dimension, e.g. `[1, 2, 0, 3]`
+    /// pads the last dimension with 1 value on the left and 2 on the right and
+    /// the previous dimension with 3 values on the right. Negative sizes remove
+    /// elements from the corresponding edge, so cropping and padding can be
+    /// combined in a single call.
+    pub fn f_pad(&self, pad: &[i64], value: f64) -> Fallible<Tensor> {
+        let size = self.size();
+        ensure!(
+            pad.len() % 2 == 0 && pad.len() / 2 <= size.len(),
+            "pad: {:?} is not compatible with shape {:?}",
+            pad,
+            size
+        );
+        let mut xs = self.shallow_clone();
+        for (i, lr) in pad.chunks(2).enumerate() {
+            let dim = size.len() - 1 - i;
+            let (left, right) = (lr[0].min(0), lr[1].min(0));
+            let new_size = size[dim] + left + right;
+            ensure!(
+                new_size >= 0,
+                "pad: cannot remove {} elements from dim {} of shape {:?}",
+                -left - right,
+                dim,
+                size
+            );
+            if left < 0 || right < 0 {
+                xs = xs.f_narrow(dim as i64, -left, new_size)?;
+            }
+        }
+        let positive_pad: Vec<i64> = pad.iter().map(|&p| p.max(0)).collect();
+        if positive_pad.iter().all(|&p| p == 0) {
+            Ok(xs)
+        } else if value == 0. {
+            xs.f_constant_pad_nd(&positive_pad)
+        } else {
+            let mut padded_size = xs.size();
+            for (i, lr) in positive_pad.chunks(2).enumerate() {
+                padded_size[size.len() - 1 - i] += lr[0] + lr[1]
+            }
+            let padded = xs.f_new_full(&padded_size, value)?;
+            let mut interior = padded.shallow_clone();
+            for (i, lr) in positive_pad.chunks(2).enumerate() {
+                let dim = size.len() - 1 - i;
+                interior = interior.f_narrow(dim as i64, lr[0], xs.size()[dim])?;
+            }
+            interior.f_copy_(&xs)?;
+            Ok(padded)
+        }
+    }
+
+    /// Pads the last dimensions of the tensor with a constant value, see `f_pad`.
+    pub fn pad(&self, pad: &[i64], value: f64) -> Tensor {
+        self.f_pad(pad, value).unwrap()
+    }
+
+    /// Selects the rows, i.e. the entries along the first dimension, for which
+    /// `mask` is non-zero.
+    ///
+    /// Contrary to `masked_select` which returns a flattened tensor, the other
+    /// dimensions are preserved. The mask is a one dimension tensor with as many
+    /// elements as there are rows.
+    pub fn f_filter_rows(&self, mask: &Tensor) -> Fallible<Tensor> {
+        let size = self.size();
+        ensure!(
+            !size.is_empty(),
+            "filter_rows: cannot filter a zero dimension tensor"
+        );
+        ensure!(
+            mask.size() == [size[0]],
+            "filter_rows: mask with shape {:?} for tensor with shape {:?}",
+            mask.size(),
+            size
+        );
+        let index = mask.f_nonzero()?.f_view(&[-1])?;
+        self.f_index_select(0, &index)
+    }
+
+    /// Selects the rows for which `mask` is non-zero, see `f_filter_rows`.
+    pub fn filter_rows(&self, mask: &Tensor) -> Tensor {
+        self.f_filter_rows(mask).unwrap()
+    }
+
+    /// Returns the indexes of the non-zero elements, one tensor per dimension.
+    ///
+    /// This is similar to NumPy `nonzero`: the i-th tensor contains the
+    /// indexes along dimension i, whereas `nonzero` returns a single tensor
+    /// of shape [N, ndim]. A zero dimension tensor is treated as a one
+    /// dimension tensor with a single element.
+    pub fn f_nonzero_numpy(&self) -> Fallible<Vec<Tensor>> {
+        let xs = if self.dim() == 0 {
+            self.f_view(&[1])?
+        } else {
+            self.shallow_clone()
+        };
+        xs.f_nonzero()?.f_unbind(1)
+    }
+
+    /// Returns the indexes of the non-zero elements, one tensor per dimension.
+    pub fn nonzero_numpy(&self) -> Vec<Tensor> {
+        self.f_nonzero_numpy().unwrap()
+    }
+
+    /// Returns a tensor with the same values using the specified memory format.
+    ///
+    /// The channels-last format is only supported for 4D tensors, the
+    /// resulting tensor keeps the NCHW shape but its strides are the ones of
+    /// a NHWC contiguous tensor. The input is returned as is when it already
+    /// uses the target format.
+    pub fn f_to_memory_format(&self, format: MemoryFormat) -> Fallible<Tensor> {
+        match format {
+            MemoryFormat::Contiguous => self.f_contiguous(),
+            MemoryFormat::ChannelsLast => {
+                ensure!(
+                    self.dim() == 4,
+                    "channels-last format requires a 4D tensor, got shape {:?}",
+                    self.size()
+                );
+                if self.is_contiguous_in(MemoryFormat::ChannelsLast) {
+                    Ok(self.shallow_clone())
+                } else {
+                    self.f_permute(&[0, 2, 3, 1])?
+                        .f_contiguous()?
+                        .f_permute(&[0, 3, 1, 2])
+                }
+            }
+        }
+    }
+
+    /// Returns a tensor with the same values using the specified memory format.
+    pub fn to_memory_format(&self, format: MemoryFormat) -> Tensor {
+        self.f_to_memory_format(format).unwrap()
+    }
+
+    /// Returns true if the tensor is contiguous in the specified memory format.
+    ///
+    /// The strides of dimensions of size 1 are ignored.
+    pub fn is_contiguous_in(&self, format: MemoryFormat) -> bool {
+        match format {
+            MemoryFormat::Contiguous => self.is_contiguous(),
+            MemoryFormat::ChannelsLast => {
+                let size = self.size();
+                size.len() == 4
+                    && channels_last_strides(&size)
+                        .iter()
+                        .zip(self.stride().iter())
+                        .zip(size.iter())
+                        .all(|((expected, stride), &size)| size == 1 || expected == stride)
+            }
+        }
+    }
+
+    // Checks that `dim` is a valid dimension and that the computation happens
+    // on floating-point values, returning the normalized dimension.
+    fn check_reduction_dim(&self, op: &str, dim: i64, kind: Option<Kind>) -> Fallible<i64> {
+        let rank = self.dim() as i64;
+        ensure!(
+            -rank <= dim && dim < rank || rank == 0 && (dim == 0 || dim == -1),
+            "{}: dim {} out of range for shape {:?}",
+            op,
+            dim,
+            self.size()
+        );
+        let kind = kind.unwrap_or_else(|| self.kind());
+        ensure!(
+            kind.is_floating_point(),
+            "{}: expected a floating-point kind, got {:?}",
+            op,
+            kind
+        );
+        Ok(if rank == 0 { 0 } else { (dim + rank) % rank })
+    }
+
+    /// Applies the GELU activation function.
+    ///
+    /// `approximate` is either `"none"` for the exact `x * Phi(x)`, where
+    /// `Phi` is the standard normal cumulative distribution function, or
+    /// `"tanh"` for the tanh based approximation. Both are used by pretrained
+    /// models: the original BERT release and GPT-2 use the tanh approximation
+    /// whereas most PyTorch models, e.g. ViT or the PyTorch ports of BERT, use
+    /// the exact formula.
+    pub fn f_gelu(&self, approximate: &str) -> Fallible<Tensor> {
+        match approximate {
+            "none" => Ok(self * 0.5 * (1. + (self / 2f64.sqrt()).f_erf()?)),
+            "tanh" => {
+                let inner =
+                    (self + self.f_pow(3.)? * 0.044_715) * (2. / std::f64::consts::PI).sqrt();
+                Ok(self * 0.5 * (1. + inner.f_tanh()?))
+            }
+            _ => bail!(
+                "gelu: unknown approximation {}, expected none or tanh",
+                approximate
+            ),
+        }
+    }
+
+    /// Applies the GELU activation function, see `f_gelu`.
+    pub fn gelu(&self, approximate: &str) -> Tensor {
+        self.f_gelu(approximate).unwrap()
+    }
+
+    /// Computes the log of the sum of the exponentials along dimension `dim`.
+    ///
+    /// The maximum value is subtracted before taking the exponentials so that
+    /// the result is stable for large inputs.
+    pub fn f_logsumexp_dim(&self, dim: i64, keepdim: bool) -> Fallible<Tensor> {
+        let dim = self.check_reduction_dim("logsumexp", dim, None)?;
+        self.f_logsumexp(&[dim], keepdim)
+    }
+
+    /// Computes the log of the sum of the exponentials along dimension `dim`.
+    pub fn logsumexp_dim(&self, dim: i64, keepdim: bool) -> Tensor {
+        self.f_logsumexp_dim(dim, keepdim).unwrap()
+    }
+
+    /// Computes the softmax along dimension `dim`.
+    ///
+    /// When `kind` is specified, the input is converted to this kind before the
+    /// computation which can be used to avoid overflows with half-precision
+    /// inputs.
+    pub fn f_softmax_dim(&self, dim: i64, kind: Option<Kind>) -> Fallible<Tensor> {
+        let dim = self.check_reduction_dim("softmax", dim, kind)?;
+        match kind {
+            None => self.f_softmax(dim),
+            Some(kind) => self.f_softmax1(dim, kind),
+        }
+    }
+
+    /// Computes the softmax along dimension `dim`, see `f_softmax_dim`.
+    pub fn softmax_dim(&self, dim: i64, kind: Option<Kind>) -> Tensor {
+        self.f_softmax_dim(dim, kind).unwrap()
+    }
+
+    /// Computes the log of the softmax along dimension `dim`.
+    ///
+    /// This is more stable than taking the log of the softmax output. As for
+    /// `f_softmax_dim`, `kind` optionally specifies the kind used for the
+    /// computation.
+    pub fn f_log_softmax_dim(&self, dim: i64, kind: Option<Kind>) -> Fallible<Tensor> {
+        let dim = self.check_reduction_dim("log_softmax", dim, kind)?;
+        match kind {
+            None => self.f_log_softmax(dim),
+            Some(kind) => self.f_log_softmax1(dim, kind),
+        }
+    }
+
+    /// Computes the log of the softmax along dimension `dim`, see `f_log_softmax_dim`.
+    pub fn log_softmax_dim(&self, dim: i64, kind: Option<Kind>) -> Tensor {
+        self.f_log_softmax_dim(dim, kind).unwrap()
+    }
+
+    /// Computes the scaled dot product attention of a query over some keys
+    /// and values, i.e. `softmax(q k^T / sqrt(E)) v`.
+    ///
+    /// The query has shape (..., L, E), the key (..., S, E) and the value
+    /// (..., S, Ev) where the leading dimensions are broadcast. The result has
+    /// shape (..., L, Ev).
+    ///
+    /// A floating-point `attn_mask` is added to the attention scores whereas
+    /// only the non-zero entries of an integer mask take part in the
+    /// attention. When `is_causal` is set, each query only attends to the keys
+    /// at the same or earlier positions, `attn_mask` must be `None` in this
+    /// case. Dropout with probability `dropout_p` is applied to the attention
+    /// weights.
+    ///
+    /// libtorch does not provide a fused kernel for this operation so the
+    /// attention weights are materialized.
+    pub fn f_scaled_dot_product_attention(
+        &self,
+        key: &Tensor,
+        value: &Tensor,
+        attn_mask: Option<&Tensor>,
+        dropout_p: f64,
+        is_causal: bool,
+    ) -> Fallible<Tensor> {
+        let (q_size, k_size, v_size) = (self.size(), key.size(), value.size());
+        ensure!(
+            q_size.len() >= 2 && k_size.len() >= 2 && v_size.len() >= 2,
+            "scaled_dot_product_attention: expected at least 2 dimensions, got {:?} {:?} {:?}",
+            q_size,
+            k_size,
+            v_size
+        );
+        let (tgt_len, embed_dim) = (q_size[q_size.len() - 2], q_size[q_size.len() - 1]);
+        let src_len = k_size[k_size.len() - 2];
+        ensure!(
+            k_size[k_size.len() - 1] == embed_dim && v_size[v_size.len() - 2] == src_len,
+            "scaled_dot_product_attention: incompatible shapes {:?} {:?} {:?}",
+            q_size,
+            k_size,
+            v_size
+        );
+        ensure!(
+            !(is_causal && attn_mask.is_some()),
+            "scaled_dot_product_attention: attn_mask cannot be set with is_causal"
+        );
+        let scores = (self * (embed_dim as f64).powf(-0.5)).f_matmul(&key.f_transpose(-2, -1)?)?;
+        let scores = match attn_mask {
+            Some(mask) if mask.kind().is_floating_point() => scores.f_add(mask)?,
+            Some(mask) => scores.f_masked_fill(&mask.f_eq(0)?, f64::NEG_INFINITY)?,
+            None if is_causal => {
+                let mask = Tensor::f_ones(&[tgt_len, src_len], (Kind::Float, self.device()))?
+                    .f_triu(1)?
+                    .f_ne(0)?;
+                scores.f_masked_fill(&mask, f64::NEG_INFINITY)?
+            }
+            None => scores,
+        };
+        let weights = scores.f_softmax(-1)?;
+        let weights = if dropout_p > 0. {
+            weights.f_dropout(dropout_p, true)?
+        } else {
+            weights
+        };
+        weights.f_matmul(value)
+    }
+
+    /// Computes the scaled dot product attention, see
+    /// `f_scaled_dot_product_attention`.
+    pub fn scaled_dot_product_attention(
+        &self,
+        key: &Tensor,
+        value: &Tensor,
+        attn_mask: Option<&Tensor>,
+        dropout_p: f64,
+        is_causal: bool,
+    ) -> Tensor {
+        self.f_scaled_dot_product_attention(key, value, attn_mask, dropout_p, is_causal)
+            .unwrap()
+    }
+
+    // Reduces the last dimension of a tensor where the `dims` dimensions have been
+    // moved to the end and flattened, an empty `dims` reduces all the dimensions.
+    fn f_reduce_dims<F>(&self, op: &str, dims: &[i64], keepdim: bool, f: F) -> Fallible<Tensor>
+    where
+        F: Fn(&Tensor) -> Fallible<Tensor>,
+    {
+        let size = self.size();
+        let rank = size.len() as i64;
+        let mut reduced = vec![false; size.len()];
+        for &dim in dims.iter() {
+            ensure!(
+                -rank <= dim && dim < rank,
+                "{}: dim {} out of range for shape {:?}",
+                op,
+                dim,
+                size
+            );
+            let dim = ((dim + rank) % rank) as usize;
+            ensure!(!reduced[dim], "{}: dim {} appears multiple times", op, dim);
+            reduced[dim] = true;
+        }
+        if dims.is_empty() {
+            reduced = vec![true; size.len()]
+        }
+        let kept: Vec<i64> = (0..rank).filter(|&d| !reduced[d as usize]).collect();
+        let mut perm = kept.clone();
+        perm.extend((0..rank).filter(|&d| reduced[d as usize]));
+        let mut flat_shape: Vec<i64> = kept.iter().map(|&d| size[d as usize]).collect();
+        let out_shape: Vec<i64> = if keepdim {
+            size.iter()
+                .zip(reduced.iter())
+                .map(|(&s, &r)| if r { 1 } else { s })
+                .collect()
+        } else {
+            flat_shape.clone()
+        };
+        flat_shape.push(-1);
+        let result = f(&self.f_permute(&perm)?.f_reshape(&flat_shape)?)?;
+        result.f_view(&out_shape)
+    }
+
+    /// Returns the maximum value over dimensions `dims`.
+    ///
+    /// All the dimensions are reduced in a single pass, an empty `dims` slice
+    /// reduces over all the dimensions.
+    pub fn f_amax(&self, dims: &[i64], keepdim: bool) -> Fallible<Tensor> {
+        self.f_reduce_dims("amax", dims, keepdim, |xs| Ok(xs.f_max2(-1, false)?.0))
+    }
+
+    /// Returns the maximum value over dimensions `dims`.
+    pub fn amax(&self, dims: &[i64], keepdim: bool) -> Tensor {
+        self.f_amax(dims, keepdim).unwrap()
+    }
+
+    /// Returns the minimum value over dimensions `dims`.
+    ///
+    /// All the dimensions are reduced in a single pass, an empty `dims` slice
+    /// reduces over all the dimensions.
+    pub fn f_amin(&self, dims: &[i64], keepdim: bool) -> Fallible<Tensor> {
+        self.f_reduce_dims("amin", dims, keepdim, |xs| Ok(xs.f_min2(-1, false)?.0))
+    }
+
+    /// Returns the minimum value over dimensions `dims`.
+    pub fn amin(&self, dims: &[i64], keepdim: bool) -> Tensor {
+        self.f_amin(dims, keepdim).unwrap()
+    }
+
+    /// Reduces the tensor over dimensions `dims` using `op`.
+    ///
+    /// An empty `dims` slice reduces over all the dimensions.
+    pub fn f_reduce(&self, op: ReduceOp, dims: &[i64], keepdim: bool) -> Fallible<Tensor> {
+        self.f_reduce_dims("reduce", dims, keepdim, |xs| match op {
+            ReduceOp::Sum => xs.f_sum2(&[-1], false),
+            ReduceOp::Mean => xs.f_mean2(&[-1], false),
+            ReduceOp::Max => Ok(xs.f_max2(-1, false)?.0),
+            ReduceOp::Min => Ok(xs.f_min2(-1, false)?.0),
+            ReduceOp::Prod => xs.f_prod2(-1, false),
+            ReduceOp::Std => xs.f_std1(&[-1], true, false),
+            ReduceOp::Var => xs.f_var1(&[-1], true, false),
+        })
+    }
+
+    /// Reduces the tensor over dimensions `dims` using `op`.
+    pub fn reduce(&self, op: ReduceOp, dims: &[i64], keepdim: bool) -> Tensor {
+        self.f_reduce(op, dims, keepdim).unwrap()
+    }
+
+    /// Counts the non-zero elements over dimensions `dims`, or over all the
+    /// elements when `dims` is `None`. The result has kind `Int64`.
+    pub fn f_count_nonzero(&self, dims: Option<&[i64]>) -> Fallible<Tensor> {
+        let nonzero = self.f_ne(0)?;
+        match dims {
+            None => nonzero.f_sum1(Kind::Int64),
+            Some(dims) => nonzero.f_sum4(dims, false, Kind::Int64),
+        }
+    }
+
+    /// Counts the non-zero elements over dimensions `dims`, or over all the
+    /// elements when `dims` is `None`.
+    pub fn count_nonzero(&self, dims: Option<&[i64]>) -> Tensor {
+        self.f_count_nonzero(dims).unwrap()
+    }
+
+    /// Returns a mask indicating whether all the elements along dimension
+    /// `dim` are non-zero.
+    ///
+    /// Contrary to `all1`, this supports tensors of any kind. NaN values are
+    /// considered to be non-zero.
+    pub fn f_all_dim(&self, dim: i64, keepdim: bool) -> Fallible<Tensor> {
+        self.f_ne(0)?.f_all1(dim, keepdim)
+    }
+
+    /// Returns a mask indicating whether all the elements along dimension
+    /// `dim` are non-zero.
+    pub fn all_dim(&self, dim: i64, keepdim: bool) -> Tensor {
+        self.f_all_dim(dim, keepdim).unwrap()
+    }
+
+    /// Returns a mask indicating whether any element along dimension `dim` is
+    /// non-zero.
+    ///
+    /// Contrary to `any1`, this supports tensors of any kind. NaN values are
+    /// considered to be non-zero.
+    pub fn f_any_dim(&self, dim: i64, keepdim: bool) -> Fallible<Tensor> {
+        self.f_ne(0)?.f_any1(dim, keepdim)
+    }
+
+    /// Returns a mask indicating whether any element along dimension `dim` is
+    /// non-zero.
+    pub fn any_dim(&self, dim: i64, keepdim: bool) -> Tensor {
+        self.f_any_dim(dim, keepdim).unwrap()
+    }
+
+    /// Returns true if all the elements are non-zero, this is the case for
+    /// empty tensors.
+    ///
+    /// `all` only supports `Uint8` tensors and returns a tensor. This can be
+    /// used for checks such as `!xs.isnan().any_nonzero()`.
+    pub fn all_nonzero(&self) -> bool {
+        self.numel() == 0 || self.ne(0).all().item::<i64>() != 0
+    }
+
+    /// Returns true if any element is non-zero.
+    pub fn any_nonzero(&self) -> bool {
+        self.numel() > 0 && self.ne(0).any().item::<i64>() != 0
+    }
+
+    /// Returns the indexes of the maximum values along dimension `dim`.
+    ///
+    /// When `dim` is `None` the index is computed in the flattened tensor.
+    /// Which index is returned when the maximum appears multiple times is not
+    /// guaranteed and may depend on the device, use `argmax_first` or
+    /// `argmax_last` when this matters.
+    pub fn f_argmax_dim(&self, dim: Option<i64>, keepdim: bool) -> Fallible<Tensor> {
+        match dim {
+            Some(dim) => self.f_argmax(dim, keepdim),
+            None => self.f_reduce_dims("argmax", &[], keepdim, |xs| xs.f_argmax(-1, false)),
+        }
+    }
+
+    /// Returns the indexes of the maximum values along dimension `dim`.
+    pub fn argmax_dim(&self, dim: Option<i64>, keepdim: bool) -> Tensor {
+        self.f_argmax_dim(dim, keepdim).unwrap()
+    }
+
+    /// Returns the indexes of the minimum values along dimension `dim`.
+    ///
+    /// When `dim` is `None` the index is computed in the flattened tensor.
+    pub fn f_argmin_dim(&self, dim: Option<i64>, keepdim: bool) -> Fallible<Tensor> {
+        match dim {
+            Some(dim) => self.f_argmin(dim, keepdim),
+            None => self.f_reduce_dims("argmin", &[], keepdim, |xs| xs.f_argmin(-1, false)),
+        }
+    }
+
+    /// Returns the indexes of the minimum values along dimension `dim`.
+    pub fn argmin_dim(&self, dim: Option<i64>, keepdim: bool) -> Tensor {
+        self.f_argmin_dim(dim, keepdim).unwrap()
+    }
+
+    // Returns the first or last index of the maximum values along `dim`.
+    fn f_argmax_tie_break(&self, dim: i64, keepdim: bool, last: bool) -> Fallible<Tensor> {
+        let size = self.size();
+        let rank = size.len() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "argmax: dim {} out of range for shape {:?}",
+            dim,
+            size
+        );
+        let dim = (dim + rank) % rank;
+        let dim_size = size[dim as usize];
+        let mut index_shape = vec![1; size.len()];
+        index_shape[dim as usize] = dim_size;
+        let indexes =
+            Tensor::f_arange(dim_size, (Kind::Int64, self.device()))?.f_view(&index_shape)?;
+        let is_max = self
+            .f_eq1(&self.f_max2(dim, true)?.0)?
+            .f_to_kind(Kind::Int64)?;
+        // As for `argmax`, NaN values are considered to be the maximum, only
+        // the NaN entries are candidates when there is any.
+        let is_nan = self.f_ne1(self)?.f_to_kind(Kind::Int64)?;
+        let has_nan = is_nan.f_max2(dim, true)?.0;
+        let is_max = &is_nan + is_max * (1 - has_nan);
+        if last {
+            // The non-maximal entries are replaced with -1.
+            let candidates = is_max * (indexes + 1) - 1;
+            Ok(candidates.f_max2(dim, keepdim)?.0)
+        } else {
+            // The non-maximal entries are replaced with the dimension size.
+            let candidates = indexes + (1 - is_max) * dim_size;
+            Ok(candidates.f_min2(dim, keepdim)?.0)
+        }
+    }
+
+    /// Returns the indexes of the maximum values along dimension `dim`, using
+    /// the first occurrence when the maximum appears multiple times.
+    pub fn f_argmax_first(&self, dim: i64, keepdim: bool) -> Fallible<Tensor> {
+        self.f_argmax_tie_break(dim, keepdim, false)
+    }
+
+    /// Returns the indexes of the first maximum values along dimension `dim`.
+    pub fn argmax_first(&self, dim: i64, keepdim: bool) -> Tensor {
+        self.f_argmax_first(dim, keepdim).unwrap()
+    }
+
+    /// Returns the indexes of the maximum values along dimension `dim`, using
+    /// the last occurrence when the maximum appears multiple times.
+    pub fn f_argmax_last(&self, dim: i64, keepdim: bool) -> Fallible<Tensor> {
+        self.f_argmax_tie_break(dim, keepdim, true)
+    }
+
+    /// Returns the indexes of the last maximum values along dimension `dim`.
+    pub fn argmax_last(&self, dim: i64, keepdim: bool) -> Tensor {
+        self.f_argmax_last(dim, keepdim).unwrap()
+    }
+
+    /// Selects the values at `indices` along dimension `dim`.
+    ///
+    /// This is similar to NumPy `take_along_axis`: the indices have the same
+    /// number of dimensions as the tensor and, contrary to `gather`, both are
+    /// broadcast on all the dimensions but `dim`.
+    pub fn f_take_along_dim(&self, indices: &Tensor, dim: i64) -> Fallible<Tensor> {
+        let size = self.size();
+        let index_size = indices.size();
+        let rank = size.len() as i64;
+        ensure!(
+            indices.kind() == Kind::Int64,
+            "take_along_dim: expected Int64 indices, got {:?}",
+            indices.kind()
+        );
+        ensure!(
+            index_size.len() == size.len(),
+            "take_along_dim: indices {:?} and tensor {:?} have different numbers of dims",
+            index_size,
+            size
+        );
+        ensure!(
+            -rank <= dim && dim < rank,
+            "take_along_dim: dim {} out of range for shape {:?}",
+            dim,
+            size
+        );
+        let dim = ((dim + rank) % rank) as usize;
+        let mut expanded_size = size.clone();
+        let mut expanded_index_size = index_size.clone();
+        for d in (0..size.len()).filter(|&d| d != dim) {
+            let (s, i) = (size[d], index_size[d]);
+            ensure!(
+                s == i || s == 1 || i == 1,
+                "take_along_dim: indices {:?} cannot be broadcast with tensor {:?}",
+                index_size,
+                size
+            );
+            let s = if s == 1 { i } else { s };
+            expanded_size[d] = s;
+            expanded_index_size[d] = s;
+        }
+        let xs = self.f_expand(&expanded_size, false)?;
+        xs.f_gather(
+            dim as i64,
+            &indices.f_expand(&expanded_index_size, false)?,
+            false,
+        )
+    }
+
+    /// Selects the values at `indices` along dimension `dim`.
+    pub fn take_along_dim(&self, indices: &Tensor, dim: i64) -> Tensor {
+        self.f_take_along_dim(indices, dim).unwrap()
+    }
+
+    /// Splits a tensor along dimension `dim` at the given indices.
+    ///
+    /// This follows the NumPy `array_split` semantics: the i-th chunk contains
+    /// the entries from `indices[i-1]` to `indices[i]`. Indices are clamped to
+    /// the dimension size and negative indices count from the end, resulting in
+    /// empty chunks when not increasing.
+    pub fn f_tensor_split_indices(&self, indices: &[i64], dim: i64) -> Fallible<Vec<Tensor>> {
+        let rank = self.dim() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "tensor_split: dim {} out of range for shape {:?}",
+            dim,
+            self.size()
+        );
+        let size = self.size()[((dim + rank) % rank) as usize];
+        let mut start = 0;
+        let mut chunks = vec![];
+        for &index in indices.iter().chain(std::iter::once(&size)) {
+            let index = if index < 0 { index + size } else { index };
+            let end = std::cmp::min(std::cmp::max(index, 0), size);
+            let length = std::cmp::max(0, end - start);
+            chunks.push(self.f_narrow(dim, start, length)?);
+            start = end;
+        }
+        Ok(chunks)
+    }
+
+    /// Splits a tensor along dimension `dim` at the given indices.
+    pub fn tensor_split_indices(&self, indices: &[i64], dim: i64) -> Vec<Tensor> {
+        self.f_tensor_split_indices(indices, dim).unwrap()
+    }
+
+    /// Splits a tensor along dimension `dim` in `sections` chunks of sizes as
+    /// equal as possible.
+    ///
+    /// Contrary to `chunk` and `split`, the sizes of the chunks differ by at
+    /// most one: when the dimension size is not divisible by `sections`, the
+    /// first chunks get one more entry.
+    pub fn f_tensor_split(&self, sections: i64, dim: i64) -> Fallible<Vec<Tensor>> {
+        ensure!(
+            sections > 0,
+            "tensor_split: the number of sections has to be positive, got {}",
+            sections
+        );
+        let rank = self.dim() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "tensor_split: dim {} out of range for shape {:?}",
+            dim,
+            self.size()
+        );
+        let size = self.size()[((dim + rank) % rank) as usize];
+        let (chunk_size, remainder) = (size / sections, size % sections);
+        let indices: Vec<i64> = (1..sections)
+            .map(|i| i * chunk_size + std::cmp::min(i, remainder))
+            .collect();
+        self.f_tensor_split_indices(&indices, dim)
+    }
+
+    /// Splits a tensor along dimension `dim` in `sections` chunks of sizes as
+    /// equal as possible.
+    pub fn tensor_split(&self, sections: i64, dim: i64) -> Vec<Tensor> {
+        self.f_tensor_split(sections, dim).unwrap()
+    }
+
     /// Flattens a tensor.
     ///
     /// This returns a flattened version of the given tensor. The first dimension
@@ -382,7 +1544,158 @@ impl Tensor {
         )
     }
 
+    /// Clamps the tensor elements using some lower and upper bound tensors.
+    ///
+    /// The bounds are broadcast to the shape of the input tensor. An error is
+    /// returned if neither `min` nor `max` is specified.
+    pub fn f_clamp_tensor(&self, min: Option<&Tensor>, max: Option<&Tensor>) -> Fallible<Tensor> {
+        match (min, max) {
+            (None, None) => bail!("clamp_tensor: at least one of min or max has to be specified"),
+            (Some(min), None) => self.f_max1(min),
+            (None, Some(max)) => self.f_min1(max),
+            (Some(min), Some(max)) => self.f_max1(min)?.f_min1(max),
+        }
+    }
+
+    /// Clamps the tensor elements using some lower and upper bound tensors.
+    ///
+    /// The bounds are broadcast to the shape of the input tensor. Panics if
+    /// neither `min` nor `max` is specified.
+    pub fn clamp_tensor(&self, min: Option<&Tensor>, max: Option<&Tensor>) -> Tensor {
+        self.f_clamp_tensor(min, max).unwrap()
+    }
+
+    /// Creates a sparse tensor in COO format.
+    ///
+    /// `indices` has shape [sparse_dims, nnz] and contains the coordinates of the
+    /// non-zero elements, their values are given by `values` which first dimension
+    /// has size nnz. The resulting tensor uses the kind and device of `values`.
+    pub fn f_sparse_coo(indices: &Tensor, values: &Tensor, size: &[i64]) -> Fallible<Tensor> {
+        let (sparse_dims, nnz) = indices.size2()?;
+        ensure!(
+            values.dim() >= 1 && values.size()[0] == nnz,
+            "sparse_coo: {} indices but values have shape {:?}",
+            nnz,
+            values.size()
+        );
+        ensure!(
+            sparse_dims as usize + values.dim() - 1 == size.len(),
+            "sparse_coo: incompatible size {:?} for {} sparse dims and values {:?}",
+            size,
+            sparse_dims,
+            values.size()
+        );
+        Tensor::f_sparse_coo_tensor2(
+            &indices.to_kind(Kind::Int64),
+            values,
+            size,
+            (values.kind(), values.device()),
+        )
+    }
+
+    /// Creates a sparse tensor in COO format, see `f_sparse_coo`.
+    pub fn sparse_coo(indices: &Tensor, values: &Tensor, size: &[i64]) -> Tensor {
+        Tensor::f_sparse_coo(indices, values, size).unwrap()
+    }
+
+    /// Selects the entries along dimension `dim` using a one dimension index tensor.
+    ///
+    /// Contrary to `f_index_select`, the index values are checked to be within
+    /// bounds before running the operation and an error is returned otherwise.
+    pub fn f_index_select_checked(&self, dim: i64, index: &Tensor) -> Fallible<Tensor> {
+        let size = self.size();
+        let rank = size.len() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "index_select: dim {} out of range for shape {:?}",
+            dim,
+            size
+        );
+        ensure!(
+            index.dim() == 1,
+            "index_select: expected a one dimension index, got shape {:?}",
+            index.size()
+        );
+        let dim_size = size[((dim + rank) % rank) as usize];
+        check_index_bounds("index_select", index, 0, dim_size)?;
+        self.f_index_select(dim, index)
+    }
+
+    /// Selects the elements at the given indexes, the input tensor is treated
+    /// as if it was viewed as a one dimension tensor.
+    ///
+    /// Negative indexes count from the end. The index values are checked to be
+    /// within bounds before running the operation and an error is returned otherwise.
+    pub fn f_take_checked(&self, index: &Tensor) -> Fallible<Tensor> {
+        let numel = self.numel();
+        check_index_bounds("take", index, -numel, numel)?;
+        self.f_take(index)
+    }
+
+    /// Returns a view on `length` entries along dimension `dim` starting at `start`.
+    ///
+    /// A negative `start` counts from the end of the dimension. Contrary to
+    /// `f_narrow`, the range is checked to be within the dimension before
+    /// running the operation and an error is returned otherwise.
+    pub fn f_narrow_checked(&self, dim: i64, start: i64, length: i64) -> Fallible<Tensor> {
+        let size = self.size();
+        let rank = size.len() as i64;
+        ensure!(
+            -rank <= dim && dim < rank,
+            "narrow: dim {} out of range for shape {:?}",
+            dim,
+            size
+        );
+        let dim_size = size[((dim + rank) % rank) as usize];
+        let first = if start < 0 { start + dim_size } else { start };
+        ensure!(
+            0 <= first && 0 <= length && first + length <= dim_size,
+            "narrow: start {} and length {} out of range for dim {} of shape {:?}",
+            start,
+            length,
+            dim,
+            size
+        );
+        self.f_narrow(dim, first, length)
+    }
+
+    /// Performs a batched matrix multiplication.
+    ///
+    /// Both tensors have to be 3D with the same batch size, [B, N, M] and
+    /// [B, M, P] resulting in [B, N, P]. The shapes are checked before running
+    /// the operation and an error describing the mismatch is returned otherwise.
+    pub fn f_bmm_checked(&self, other: &Tensor) -> Fallible<Tensor> {
+        check_bmm_shapes("bmm", self, other)?;
+        self.f_bmm(other)
+    }
+
+    /// Computes `beta * self + alpha * batch1.bmm(batch2)`.
+    ///
+    /// The batches are checked as for `f_bmm_checked`, `self` has to broadcast
+    /// to the shape of the batched product.
+    pub fn f_baddbmm_checked(
+        &self,
+        batch1: &Tensor,
+        batch2: &Tensor,
+        beta: f64,
+        alpha: f64,
+    ) -> Fallible<Tensor> {
+        let (b, n, _, p) = check_bmm_shapes("baddbmm", batch1, batch2)?;
+        let product = batch1.f_bmm(batch2)?;
+        let input = self.f_broadcast_to(&[b, n, p]).map_err(|_| {
+            format_err!(
+                "baddbmm: input with shape {:?} does not broadcast to {:?}",
+                self.size(),
+                [b, n, p]
+            )
+        })?;
+        Ok(input * beta + product * alpha)
+    }
+
     /// Copies a tensor to a newly allocated tensor using the same shape and device.
+    ///
+    /// Contrary to `shallow_clone`, the result does not share its storage with
+    /// the input so in-place operations on one of them do not affect the other.
     pub fn copy(&self) -> Tensor {
         let mut result = self.zeros_like();
         result.copy_(&self);
@@ -390,6 +1703,56 @@ impl Tensor {
     }
 }
 
+// Checks the shapes of the operands of a batched matrix multiplication and
+// returns the batch size and the matrix dimensions.
+fn check_bmm_shapes(op: &str, lhs: &Tensor, rhs: &Tensor) -> Fallible<(i64, i64, i64, i64)> {
+    let (b1, n, m1) = lhs
+        .size3()
+        .map_err(|_| format_err!("{}: expected a 3D tensor, got shape {:?}", op, lhs.size()))?;
+    let (b2, m2, p) = rhs
+        .size3()
+        .map_err(|_| format_err!("{}: expected a 3D tensor, got shape {:?}", op, rhs.size()))?;
+    ensure!(
+        b1 == b2,
+        "{}: batch sizes differ, {:?} and {:?}",
+        op,
+        lhs.size(),
+        rhs.size()
+    );
+    ensure!(
+        m1 == m2,
+        "{}: inner dimensions differ, {:?} and {:?}",
+        op,
+        lhs.size(),
+        rhs.size()
+    );
+    Ok((b1, n, m1, p))
+}
+
+// Checks that all the values in index are in [lo, up).
+fn check_index_bounds(op: &str, index: &Tensor, lo: i64, up: i64) -> Fallible<()> {
+    ensure!(
+        index.kind() == Kind::Int64,
+        "{}: expected an int64 index, got {:?}",
+        op,
+        index.kind()
+    );
+    if index.numel() > 0 {
+        let min = index.min().f_item::<i64>()?;
+        let max = index.max().f_item::<i64>()?;
+        ensure!(
+            lo <= min && max < up,
+            "{}: index values {}..={} are out of range {}..{}",
+            op,
+            min,
+            max,
+            lo,
+            up
+        );
+    }
+    Ok(())
+}
+
 impl std::iter::Sum for Tensor {
     fn sum<I: Iterator<Item = Tensor>>(mut iter: I) -> Tensor {
         match iter.next() {
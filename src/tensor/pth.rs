@@ -0,0 +1,583 @@
+//! Reading and writing of the PyTorch serialization format.
+//!
+//! Files produced by `torch.save` are zip archives containing a pickle file,
+//! data.pkl, and the raw content of each tensor storage. Only the subset of
+//! pickle used to represent a dictionary of tensors is supported.
+//! https://github.com/pytorch/pytorch/blob/master/torch/serialization.py
+use crate::{Device, Kind, Tensor};
+use failure::Fallible;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+const ARCHIVE_NAME: &str = "archive";
+const PICKLE_FILE: &str = "data.pkl";
+
+fn storage_name(kind: Kind) -> Fallible<&'static str> {
+    let name = match kind {
+        Kind::Uint8 => "ByteStorage",
+        Kind::Int8 => "CharStorage",
+        Kind::Int16 => "ShortStorage",
+        Kind::Int => "IntStorage",
+        Kind::Int64 => "LongStorage",
+        Kind::Half => "HalfStorage",
+        Kind::Float => "FloatStorage",
+        Kind::Double => "DoubleStorage",
+        Kind::BFloat16 => "BFloat16Storage",
+        kind => bail!("unsupported kind {:?}", kind),
+    };
+    Ok(name)
+}
+
+fn storage_kind(name: &str) -> Fallible<Kind> {
+    for &kind in [
+        Kind::Uint8,
+        Kind::Int8,
+        Kind::Int16,
+        Kind::Int,
+        Kind::Int64,
+        Kind::Half,
+        Kind::Float,
+        Kind::Double,
+        Kind::BFloat16,
+    ]
+    .iter()
+    {
+        if storage_name(kind)? == name {
+            return Ok(kind);
+        }
+    }
+    bail!("unsupported storage type {}", name)
+}
+
+// The pickle opcodes, protocol 2.
+mod op {
+    pub const PROTO: u8 = 0x80;
+    pub const STOP: u8 = b'.';
+    pub const MARK: u8 = b'(';
+    pub const EMPTY_DICT: u8 = b'}';
+    pub const EMPTY_LIST: u8 = b']';
+    pub const EMPTY_TUPLE: u8 = b')';
+    pub const TUPLE: u8 = b't';
+    pub const TUPLE1: u8 = 0x85;
+    pub const TUPLE2: u8 = 0x86;
+    pub const TUPLE3: u8 = 0x87;
+    pub const APPEND: u8 = b'a';
+    pub const APPENDS: u8 = b'e';
+    pub const SETITEM: u8 = b's';
+    pub const SETITEMS: u8 = b'u';
+    pub const BINUNICODE: u8 = b'X';
+    pub const GLOBAL: u8 = b'c';
+    pub const BININT: u8 = b'J';
+    pub const BININT1: u8 = b'K';
+    pub const BININT2: u8 = b'M';
+    pub const LONG1: u8 = 0x8a;
+    pub const NONE: u8 = b'N';
+    pub const NEWTRUE: u8 = 0x88;
+    pub const NEWFALSE: u8 = 0x89;
+    pub const BINPUT: u8 = b'q';
+    pub const LONG_BINPUT: u8 = b'r';
+    pub const BINGET: u8 = b'h';
+    pub const LONG_BINGET: u8 = b'j';
+    pub const BINPERSID: u8 = b'Q';
+    pub const REDUCE: u8 = b'R';
+    pub const BUILD: u8 = b'b';
+}
+
+struct Pickler {
+    buf: Vec<u8>,
+}
+
+impl Pickler {
+    fn op(&mut self, op: u8) {
+        self.buf.push(op)
+    }
+
+    fn int(&mut self, v: i64) {
+        if (0..1 << 8).contains(&v) {
+            self.buf.extend_from_slice(&[op::BININT1, v as u8])
+        } else if (0..1 << 16).contains(&v) {
+            self.buf.push(op::BININT2);
+            self.buf.extend_from_slice(&(v as u16).to_le_bytes())
+        } else if i64::from(v as i32) == v {
+            self.buf.push(op::BININT);
+            self.buf.extend_from_slice(&(v as i32).to_le_bytes())
+        } else {
+            self.buf.extend_from_slice(&[op::LONG1, 8]);
+            self.buf.extend_from_slice(&v.to_le_bytes())
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.buf.push(op::BINUNICODE);
+        self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(s.as_bytes())
+    }
+
+    fn global(&mut self, module: &str, name: &str) {
+        self.buf.push(op::GLOBAL);
+        self.buf
+            .extend_from_slice(format!("{}\n{}\n", module, name).as_bytes())
+    }
+
+    fn int_tuple(&mut self, vs: &[i64]) {
+        self.op(op::MARK);
+        for &v in vs.iter() {
+            self.int(v)
+        }
+        self.op(op::TUPLE)
+    }
+
+    // Pickles a call to torch._utils._rebuild_tensor_v2 for a contiguous
+    // tensor using the storage `key`.
+    fn tensor(&mut self, key: &str, kind: Kind, size: &[i64]) -> Fallible<()> {
+        let mut stride = vec![1; size.len()];
+        for i in (0..size.len().saturating_sub(1)).rev() {
+            stride[i] = stride[i + 1] * size[i + 1]
+        }
+        self.global("torch._utils", "_rebuild_tensor_v2");
+        self.op(op::MARK);
+        self.op(op::MARK);
+        self.str("storage");
+        self.global("torch", storage_name(kind)?);
+        self.str(key);
+        self.str("cpu");
+        self.int(size.iter().product());
+        self.op(op::TUPLE);
+        self.op(op::BINPERSID);
+        self.int(0);
+        self.int_tuple(size);
+        self.int_tuple(&stride);
+        self.op(op::NEWFALSE);
+        self.global("collections", "OrderedDict");
+        self.op(op::EMPTY_TUPLE);
+        self.op(op::REDUCE);
+        self.op(op::TUPLE);
+        self.op(op::REDUCE);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Mark,
+    None,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Global(String, String),
+    Tuple(Vec<Value>),
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+    Storage {
+        key: String,
+        kind: Kind,
+    },
+    Tensor {
+        key: String,
+        kind: Kind,
+        offset: i64,
+        size: Vec<i64>,
+        stride: Vec<i64>,
+        requires_grad: bool,
+    },
+}
+
+impl Value {
+    fn int(&self) -> Fallible<i64> {
+        match self {
+            Value::Int(v) => Ok(*v),
+            v => bail!("expected an int, got {:?}", v),
+        }
+    }
+
+    fn ints(&self) -> Fallible<Vec<i64>> {
+        match self {
+            Value::Tuple(vs) => vs.iter().map(|v| v.int()).collect(),
+            v => bail!("expected a tuple, got {:?}", v),
+        }
+    }
+}
+
+struct Unpickler<'a> {
+    data: &'a [u8],
+    pos: usize,
+    stack: Vec<Value>,
+    memo: HashMap<u32, Value>,
+}
+
+impl<'a> Unpickler<'a> {
+    fn read(&mut self, len: usize) -> Fallible<&'a [u8]> {
+        ensure!(
+            self.pos + len <= self.data.len(),
+            "unexpected end of pickle"
+        );
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Fallible<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Fallible<u32> {
+        let b = self.read(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_line(&mut self) -> Fallible<String> {
+        let len = match self.data[self.pos..].iter().position(|&c| c == b'\n') {
+            Some(len) => len,
+            None => bail!("unexpected end of pickle"),
+        };
+        let line = String::from_utf8(self.read(len)?.to_vec())?;
+        self.pos += 1;
+        Ok(line)
+    }
+
+    fn pop(&mut self) -> Fallible<Value> {
+        match self.stack.pop() {
+            Some(v) => Ok(v),
+            None => bail!("empty pickle stack"),
+        }
+    }
+
+    fn pop_to_mark(&mut self) -> Fallible<Vec<Value>> {
+        let mut values = vec![];
+        loop {
+            match self.pop()? {
+                Value::Mark => break,
+                v => values.push(v),
+            }
+        }
+        values.reverse();
+        Ok(values)
+    }
+
+    fn top(&mut self) -> Fallible<&mut Value> {
+        match self.stack.last_mut() {
+            Some(v) => Ok(v),
+            None => bail!("empty pickle stack"),
+        }
+    }
+
+    fn tuple_n(&mut self, len: usize) -> Fallible<()> {
+        ensure!(self.stack.len() >= len, "empty pickle stack");
+        let values = self.stack.split_off(self.stack.len() - len);
+        self.stack.push(Value::Tuple(values));
+        Ok(())
+    }
+
+    fn put(&mut self, idx: u32) -> Fallible<()> {
+        let value = self.top()?.clone();
+        self.memo.insert(idx, value);
+        Ok(())
+    }
+
+    fn get(&mut self, idx: u32) -> Fallible<()> {
+        match self.memo.get(&idx) {
+            Some(value) => {
+                let value = value.clone();
+                self.stack.push(value);
+                Ok(())
+            }
+            None => bail!("missing memo entry {}", idx),
+        }
+    }
+
+    fn set_items(&mut self, items: Vec<Value>) -> Fallible<()> {
+        ensure!(items.len() % 2 == 0, "odd number of dict items");
+        match self.top()? {
+            Value::Dict(d) => {
+                let mut items = items.into_iter();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    d.push((k, v))
+                }
+                Ok(())
+            }
+            v => bail!("expected a dict, got {:?}", v),
+        }
+    }
+
+    fn append(&mut self, items: Vec<Value>) -> Fallible<()> {
+        match self.top()? {
+            Value::List(l) => {
+                l.extend(items);
+                Ok(())
+            }
+            v => bail!("expected a list, got {:?}", v),
+        }
+    }
+
+    fn persistent_load(pid: Value) -> Fallible<Value> {
+        match pid {
+            Value::Tuple(ref vs) if vs.len() >= 3 => match (&vs[0], &vs[1], &vs[2]) {
+                (Value::Str(s), Value::Global(module, name), Value::Str(key))
+                    if s == "storage" && module == "torch" =>
+                {
+                    Ok(Value::Storage {
+                        key: key.to_string(),
+                        kind: storage_kind(name)?,
+                    })
+                }
+                _ => bail!("unsupported persistent id {:?}", pid),
+            },
+            pid => bail!("unsupported persistent id {:?}", pid),
+        }
+    }
+
+    fn reduce(callable: Value, args: Value) -> Fallible<Value> {
+        let args = match args {
+            Value::Tuple(args) => args,
+            args => bail!("expected a tuple of arguments, got {:?}", args),
+        };
+        match callable {
+            Value::Global(ref module, ref name)
+                if module == "torch._utils" && name == "_rebuild_tensor_v2" =>
+            {
+                ensure!(args.len() >= 4, "unexpected _rebuild_tensor_v2 arguments");
+                let (key, kind) = match &args[0] {
+                    Value::Storage { key, kind } => (key.to_string(), *kind),
+                    v => bail!("expected a storage, got {:?}", v),
+                };
+                let requires_grad = match args.get(4) {
+                    Some(Value::Bool(b)) => *b,
+                    _ => false,
+                };
+                Ok(Value::Tensor {
+                    key,
+                    kind,
+                    offset: args[1].int()?,
+                    size: args[2].ints()?,
+                    stride: args[3].ints()?,
+                    requires_grad,
+                })
+            }
+            Value::Global(ref module, ref name)
+                if module == "collections" && name == "OrderedDict" =>
+            {
+                Ok(Value::Dict(vec![]))
+            }
+            callable => bail!("unsupported callable {:?}", callable),
+        }
+    }
+
+    fn load(data: &'a [u8]) -> Fallible<Value> {
+        let mut u = Unpickler {
+            data,
+            pos: 0,
+            stack: vec![],
+            memo: HashMap::new(),
+        };
+        loop {
+            match u.read_u8()? {
+                op::PROTO => {
+                    let _version = u.read_u8()?;
+                }
+                op::STOP => return u.pop(),
+                op::MARK => u.stack.push(Value::Mark),
+                op::EMPTY_DICT => u.stack.push(Value::Dict(vec![])),
+                op::EMPTY_LIST => u.stack.push(Value::List(vec![])),
+                op::EMPTY_TUPLE => u.stack.push(Value::Tuple(vec![])),
+                op::TUPLE => {
+                    let values = u.pop_to_mark()?;
+                    u.stack.push(Value::Tuple(values))
+                }
+                op::TUPLE1 => u.tuple_n(1)?,
+                op::TUPLE2 => u.tuple_n(2)?,
+                op::TUPLE3 => u.tuple_n(3)?,
+                op::APPEND => {
+                    let value = u.pop()?;
+                    u.append(vec![value])?
+                }
+                op::APPENDS => {
+                    let values = u.pop_to_mark()?;
+                    u.append(values)?
+                }
+                op::SETITEM => {
+                    let value = u.pop()?;
+                    let key = u.pop()?;
+                    u.set_items(vec![key, value])?
+                }
+                op::SETITEMS => {
+                    let values = u.pop_to_mark()?;
+                    u.set_items(values)?
+                }
+                op::BINUNICODE => {
+                    let len = u.read_u32()? as usize;
+                    let s = String::from_utf8(u.read(len)?.to_vec())?;
+                    u.stack.push(Value::Str(s))
+                }
+                op::GLOBAL => {
+                    let module = u.read_line()?;
+                    let name = u.read_line()?;
+                    u.stack.push(Value::Global(module, name))
+                }
+                op::BININT => {
+                    let v = u.read_u32()? as i32;
+                    u.stack.push(Value::Int(i64::from(v)))
+                }
+                op::BININT1 => {
+                    let v = u.read_u8()?;
+                    u.stack.push(Value::Int(i64::from(v)))
+                }
+                op::BININT2 => {
+                    let b = u.read(2)?;
+                    u.stack
+                        .push(Value::Int(i64::from(u16::from_le_bytes([b[0], b[1]]))))
+                }
+                op::LONG1 => {
+                    let len = u.read_u8()? as usize;
+                    ensure!(len <= 8, "unsupported long of {} bytes", len);
+                    let bytes = u.read(len)?;
+                    let v = bytes
+                        .iter()
+                        .rev()
+                        .fold(0i64, |acc, &b| (acc << 8) | i64::from(b));
+                    // Sign extend from the most significant byte.
+                    let shift = 64 - 8 * len as u32;
+                    let v = if len == 0 { 0 } else { (v << shift) >> shift };
+                    u.stack.push(Value::Int(v))
+                }
+                op::NONE => u.stack.push(Value::None),
+                op::NEWTRUE => u.stack.push(Value::Bool(true)),
+                op::NEWFALSE => u.stack.push(Value::Bool(false)),
+                op::BINPUT => {
+                    let idx = u32::from(u.read_u8()?);
+                    u.put(idx)?
+                }
+                op::LONG_BINPUT => {
+                    let idx = u.read_u32()?;
+                    u.put(idx)?
+                }
+                op::BINGET => {
+                    let idx = u32::from(u.read_u8()?);
+                    u.get(idx)?
+                }
+                op::LONG_BINGET => {
+                    let idx = u.read_u32()?;
+                    u.get(idx)?
+                }
+                op::BINPERSID => {
+                    let pid = u.pop()?;
+                    u.stack.push(Unpickler::persistent_load(pid)?)
+                }
+                op::REDUCE => {
+                    let args = u.pop()?;
+                    let callable = u.pop()?;
+                    u.stack.push(Unpickler::reduce(callable, args)?)
+                }
+                op::BUILD => {
+                    // The state, e.g. the metadata of a state dict, is ignored.
+                    let _state = u.pop()?;
+                }
+                op => bail!("unsupported pickle opcode {:#x}", op),
+            }
+        }
+    }
+}
+
+impl Tensor {
+    /// Writes some named tensors to a file that can be read by `torch.load`.
+    ///
+    /// The tensors are loaded in Python as a dictionary mapping the names to
+    /// the tensors. Complex tensors are not supported.
+    pub fn save_torch<S: AsRef<str>, T: AsRef<Tensor>, P: AsRef<Path>>(
+        named_tensors: &[(S, T)],
+        path: P,
+    ) -> Fallible<()> {
+        let mut pickler = Pickler {
+            buf: vec![op::PROTO, 2],
+        };
+        pickler.op(op::EMPTY_DICT);
+        pickler.op(op::MARK);
+        let mut storages = vec![];
+        for (key, (name, tensor)) in named_tensors.iter().enumerate() {
+            let tensor = tensor.as_ref().f_to_device(Device::Cpu)?.f_contiguous()?;
+            let storage_key = key.to_string();
+            pickler.str(name.as_ref());
+            pickler.tensor(&storage_key, tensor.kind(), &tensor.size())?;
+            storages.push((storage_key, tensor));
+        }
+        pickler.op(op::SETITEMS);
+        pickler.op(op::STOP);
+
+        let mut zip = zip::ZipWriter::new(File::create(path.as_ref())?);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file(format!("{}/{}", ARCHIVE_NAME, PICKLE_FILE), options)?;
+        zip.write_all(&pickler.buf)?;
+        for (key, tensor) in storages.iter() {
+            zip.start_file(format!("{}/data/{}", ARCHIVE_NAME, key), options)?;
+            let numel = tensor.numel();
+            let elt_size_in_bytes = i64::from(tensor.kind().elt_size_in_bytes());
+            let mut content = vec![0u8; (numel * elt_size_in_bytes) as usize];
+            tensor.f_copy_data(&mut content, numel)?;
+            zip.write_all(&content)?;
+        }
+        zip.start_file(format!("{}/version", ARCHIVE_NAME), options)?;
+        zip.write_all(b"3\n")?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Reads a file written by `torch.save` containing a dictionary of tensors.
+    ///
+    /// Only the zip based format used by default since PyTorch 1.6 is
+    /// supported. The tensors are returned in the order of the dictionary.
+    pub fn load_torch<P: AsRef<Path>>(path: P) -> Fallible<Vec<(String, Tensor)>> {
+        let mut zip = zip::ZipArchive::new(BufReader::new(File::open(path.as_ref())?))?;
+        let pickle_name = (0..zip.len())
+            .map(|i| zip.by_index(i).map(|f| f.name().to_string()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .find(|name| name.ends_with(&format!("/{}", PICKLE_FILE)));
+        let pickle_name = match pickle_name {
+            Some(name) => name,
+            None => bail!("no {} in {:?}", PICKLE_FILE, path.as_ref()),
+        };
+        let prefix = &pickle_name[..pickle_name.len() - PICKLE_FILE.len()];
+        let mut pickle = vec![];
+        zip.by_name(&pickle_name)?.read_to_end(&mut pickle)?;
+        let items = match Unpickler::load(&pickle)? {
+            Value::Dict(items) => items,
+            v => bail!("expected a dict of tensors, got {:?}", v),
+        };
+        let mut storages: HashMap<String, Tensor> = HashMap::new();
+        let mut result = vec![];
+        for (name, value) in items.into_iter() {
+            let name = match name {
+                Value::Str(name) => name,
+                v => bail!("expected a string key, got {:?}", v),
+            };
+            let (key, kind, offset, size, stride, requires_grad) = match value {
+                Value::Tensor {
+                    key,
+                    kind,
+                    offset,
+                    size,
+                    stride,
+                    requires_grad,
+                } => (key, kind, offset, size, stride, requires_grad),
+                v => bail!("expected a tensor for {}, got {:?}", name, v),
+            };
+            if !storages.contains_key(&key) {
+                let mut data = vec![];
+                zip.by_name(&format!("{}data/{}", prefix, key))?
+                    .read_to_end(&mut data)?;
+                let numel = data.len() as i64 / i64::from(kind.elt_size_in_bytes());
+                storages.insert(key.clone(), Tensor::f_of_data_size(&data, &[numel], kind)?);
+            }
+            let tensor = storages[&key].f_as_strided(&size, &stride, offset)?;
+            let tensor = if requires_grad {
+                tensor.set_requires_grad(true)
+            } else {
+                tensor
+            };
+            result.push((name, tensor))
+        }
+        Ok(result)
+    }
+}
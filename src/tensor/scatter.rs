@@ -0,0 +1,171 @@
+//! Scatter operations with reductions.
+use super::check_index_bounds;
+use crate::{Kind, Tensor};
+use failure::Fallible;
+
+/// The reductions supported by `Tensor::scatter_reduce`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScatterReduce {
+    Sum,
+    Prod,
+    Mean,
+    Amax,
+    Amin,
+}
+
+impl ScatterReduce {
+    fn combine(self, lhs: &Tensor, rhs: &Tensor) -> Fallible<Tensor> {
+        match self {
+            ScatterReduce::Sum | ScatterReduce::Mean => lhs.f_add(rhs),
+            ScatterReduce::Prod => lhs.f_mul(rhs),
+            ScatterReduce::Amax => lhs.f_max1(rhs),
+            ScatterReduce::Amin => lhs.f_min1(rhs),
+        }
+    }
+}
+
+impl Tensor {
+    /// Reduces the values of `src` into a copy of this tensor at the indexes
+    /// given by `index` along dimension `dim`.
+    ///
+    /// This follows PyTorch `scatter_reduce`: for a 2D tensor and `dim = 0`,
+    /// `src[i][j]` is reduced into the output at `[index[i][j]][j]`. `index`
+    /// has the same number of dimensions as `src` and the output, its size
+    /// along each dimension must not exceed the one of `src` and, except along
+    /// `dim`, the one of the output. Only the part of `src` covered by `index`
+    /// is used.
+    ///
+    /// When `include_self` is true, the values of this tensor are part of the
+    /// reduction, otherwise they are only kept for the positions where no
+    /// value is scattered. As in PyTorch, the mean of integer tensors is
+    /// rounded towards minus infinity.
+    pub fn f_scatter_reduce(
+        &self,
+        dim: i64,
+        index: &Tensor,
+        src: &Tensor,
+        reduce: ScatterReduce,
+        include_self: bool,
+    ) -> Fallible<Tensor> {
+        let size = self.size();
+        let ndim = size.len();
+        ensure!(
+            ndim > 0,
+            "scatter_reduce: cannot scatter into a zero dimension tensor"
+        );
+        let dim = if dim < 0 { dim + ndim as i64 } else { dim };
+        ensure!(
+            0 <= dim && dim < ndim as i64,
+            "scatter_reduce: dim {} is out of range for shape {:?}",
+            dim,
+            size
+        );
+        let dim = dim as usize;
+        ensure!(
+            src.kind() == self.kind(),
+            "scatter_reduce: src has kind {:?}, expected {:?}",
+            src.kind(),
+            self.kind()
+        );
+        let (index_size, src_size) = (index.size(), src.size());
+        let compatible = index_size.len() == ndim
+            && src_size.len() == ndim
+            && (0..ndim)
+                .all(|d| index_size[d] <= src_size[d] && (d == dim || index_size[d] <= size[d]));
+        ensure!(
+            compatible,
+            "scatter_reduce: index with shape {:?} is not compatible with src {:?} and self {:?}",
+            index_size,
+            src_size,
+            size
+        );
+        check_index_bounds("scatter_reduce", index, 0, size[dim])?;
+        if index.numel() == 0 {
+            return Ok(self.copy());
+        }
+
+        // The linear indexes of the destinations in the flattened output.
+        let mut strides = vec![1; ndim];
+        for d in (0..ndim - 1).rev() {
+            strides[d] = strides[d + 1] * size[d + 1]
+        }
+        let mut dests = index * strides[dim];
+        for d in (0..ndim).filter(|&d| d != dim) {
+            let mut shape = vec![1; ndim];
+            shape[d] = index_size[d];
+            let positions = Tensor::f_arange(index_size[d], (Kind::Int64, self.device()))?;
+            dests += positions.f_view(&shape)? * strides[d];
+        }
+        let mut values = src.shallow_clone();
+        for (d, &len) in index_size.iter().enumerate() {
+            values = values.f_narrow(d as i64, 0, len)?;
+        }
+
+        // Sorts the values by destination and runs a segmented inclusive scan
+        // so that the last value of each group holds the reduction of the
+        // whole group.
+        let order = dests.f_reshape(&[-1])?.f_argsort(0, false)?;
+        let dests = dests.f_reshape(&[-1])?.f_index_select(0, &order)?;
+        let mut values = values.f_reshape(&[-1])?.f_index_select(0, &order)?;
+        let n = dests.size()[0];
+        let mut shift = 1;
+        while shift < n {
+            let same_group =
+                dests
+                    .f_narrow(0, shift, n - shift)?
+                    .f_eq1(&dests.f_narrow(0, 0, n - shift)?)?;
+            let tail = values.f_narrow(0, shift, n - shift)?;
+            let combined = reduce.combine(&tail, &values.f_narrow(0, 0, n - shift)?)?;
+            let tail = combined.f_where_(&same_group, &tail)?;
+            values = Tensor::f_cat(&[values.f_narrow(0, 0, shift)?, tail], 0)?;
+            shift *= 2
+        }
+        let is_last = Tensor::f_cat(
+            &[
+                dests
+                    .f_narrow(0, 1, n - 1)?
+                    .f_ne1(&dests.f_narrow(0, 0, n - 1)?)?,
+                Tensor::f_ones(&[1], (Kind::Uint8, self.device()))?,
+            ],
+            0,
+        )?;
+        let last = is_last.f_nonzero()?.f_view(&[-1])?;
+        let group_dests = dests.f_index_select(0, &last)?;
+        let mut reduced = values.f_index_select(0, &last)?;
+
+        let flat = self.f_reshape(&[-1])?;
+        if include_self {
+            reduced = reduce.combine(&reduced, &flat.f_index_select(0, &group_dests)?)?
+        }
+        if reduce == ScatterReduce::Mean {
+            let previous_last = Tensor::f_cat(
+                &[
+                    Tensor::f_full(&[1], -1, (Kind::Int64, self.device()))?,
+                    last.f_narrow(0, 0, last.size()[0] - 1)?,
+                ],
+                0,
+            )?;
+            let counts = (&last - previous_last + include_self as i64).f_to_kind(self.kind())?;
+            reduced = if self.kind().is_floating_point() {
+                reduced.f_div(&counts)?
+            } else {
+                (&reduced - reduced.f_remainder1(&counts)?).f_div(&counts)?
+            }
+        }
+        flat.f_index_copy(0, &group_dests, &reduced)?.f_view(&size)
+    }
+
+    /// Reduces the values of `src` into a copy of this tensor, see
+    /// `f_scatter_reduce`.
+    pub fn scatter_reduce(
+        &self,
+        dim: i64,
+        index: &Tensor,
+        src: &Tensor,
+        reduce: ScatterReduce,
+        include_self: bool,
+    ) -> Tensor {
+        self.f_scatter_reduce(dim, index, src, reduce, include_self)
+            .unwrap()
+    }
+}
@@ -0,0 +1,229 @@
+//! Sparse matrices in compressed sparse row (CSR) format.
+//!
+//! libtorch only provides the COO sparse layout so the CSR representation is
+//! stored on the Rust side. Products with dense matrices only touch the
+//! non-zero entries and support backpropagation to the values and to the
+//! dense operand.
+use super::check_index_bounds;
+use crate::{Kind, Tensor};
+use failure::Fallible;
+
+/// A two dimension sparse matrix in compressed sparse row format.
+///
+/// The column indices and values of row `i` are stored in `col_indices` and
+/// `values` between positions `crow_indices[i]` and `crow_indices[i + 1]`.
+#[derive(Debug)]
+pub struct SparseCsr {
+    crow_indices: Tensor,
+    col_indices: Tensor,
+    values: Tensor,
+    size: [i64; 2],
+}
+
+impl SparseCsr {
+    /// Creates a sparse CSR matrix.
+    ///
+    /// `crow_indices` has nrows + 1 non-decreasing entries starting from 0 and
+    /// ending with the number of non-zero elements nnz. `col_indices` and
+    /// `values` are one dimension tensors with nnz elements. The indices are
+    /// converted to int64 and moved to the device of `values`.
+    pub fn f_new(
+        crow_indices: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        size: &[i64],
+    ) -> Fallible<SparseCsr> {
+        let (nrows, ncols) = match size {
+            [nrows, ncols] if *nrows >= 0 && *ncols >= 0 => (*nrows, *ncols),
+            _ => bail!("sparse_csr: expected a 2D size, got {:?}", size),
+        };
+        let device = values.device();
+        let crow_indices = crow_indices.f_to_kind(Kind::Int64)?.f_to_device(device)?;
+        let col_indices = col_indices.f_to_kind(Kind::Int64)?.f_to_device(device)?;
+        ensure!(
+            crow_indices.size() == [nrows + 1],
+            "sparse_csr: expected {} crow indices, got shape {:?}",
+            nrows + 1,
+            crow_indices.size()
+        );
+        let nnz = values
+            .size1()
+            .map_err(|_| format_err!("sparse_csr: expected 1D values, got {:?}", values.size()))?;
+        ensure!(
+            col_indices.size() == [nnz],
+            "sparse_csr: {} values but col indices have shape {:?}",
+            nnz,
+            col_indices.size()
+        );
+        let crow: Vec<i64> = Vec::from(&crow_indices.f_to_device(crate::Device::Cpu)?);
+        ensure!(
+            crow[0] == 0 && crow[nrows as usize] == nnz && crow.windows(2).all(|w| w[0] <= w[1]),
+            "sparse_csr: crow indices {:?} are not non-decreasing from 0 to {}",
+            crow,
+            nnz
+        );
+        check_index_bounds("sparse_csr", &col_indices, 0, ncols)?;
+        Ok(SparseCsr {
+            crow_indices,
+            col_indices,
+            values: values.shallow_clone(),
+            size: [nrows, ncols],
+        })
+    }
+
+    /// Creates a sparse CSR matrix, see `f_new`.
+    pub fn new(
+        crow_indices: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        size: &[i64],
+    ) -> SparseCsr {
+        SparseCsr::f_new(crow_indices, col_indices, values, size).unwrap()
+    }
+
+    /// The compressed row indices, with nrows + 1 elements.
+    pub fn crow_indices(&self) -> &Tensor {
+        &self.crow_indices
+    }
+
+    /// The column index of each non-zero element.
+    pub fn col_indices(&self) -> &Tensor {
+        &self.col_indices
+    }
+
+    /// The value of each non-zero element.
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    /// The number of rows and columns of the matrix.
+    pub fn size(&self) -> [i64; 2] {
+        self.size
+    }
+
+    /// The number of non-zero elements.
+    pub fn nnz(&self) -> i64 {
+        self.values.size()[0]
+    }
+
+    // The row index of each non-zero element.
+    fn row_indices(&self) -> Fallible<Tensor> {
+        let nrows = self.size[0];
+        let counts =
+            self.crow_indices.f_narrow(0, 1, nrows)? - self.crow_indices.f_narrow(0, 0, nrows)?;
+        Tensor::f_repeat_interleave(&counts)
+    }
+
+    /// Converts the matrix to a sparse tensor in COO format.
+    pub fn f_to_sparse_coo(&self) -> Fallible<Tensor> {
+        let indices = Tensor::f_stack(&[self.row_indices()?, self.col_indices.shallow_clone()], 0)?;
+        Tensor::f_sparse_coo(&indices, &self.values, &self.size)
+    }
+
+    /// Converts the matrix to a sparse tensor in COO format.
+    pub fn to_sparse_coo(&self) -> Tensor {
+        self.f_to_sparse_coo().unwrap()
+    }
+
+    /// Converts the matrix to a dense tensor.
+    pub fn f_to_dense(&self) -> Fallible<Tensor> {
+        let flat_indices = self.row_indices()? * self.size[1] + &self.col_indices;
+        let dense = Tensor::f_zeros(
+            &[self.size[0] * self.size[1]],
+            (self.values.kind(), self.values.device()),
+        )?;
+        dense
+            .f_index_add(0, &flat_indices, &self.values)?
+            .f_view(&self.size)
+    }
+
+    /// Converts the matrix to a dense tensor.
+    pub fn to_dense(&self) -> Tensor {
+        self.f_to_dense().unwrap()
+    }
+
+    /// Multiplies the matrix by a dense matrix with shape (ncols, k), the
+    /// result is a dense matrix with shape (nrows, k).
+    pub fn f_mm(&self, rhs: &Tensor) -> Fallible<Tensor> {
+        let (rows, k) = rhs.size2()?;
+        ensure!(
+            rows == self.size[1],
+            "sparse_csr: cannot multiply a {:?} matrix by {:?}",
+            self.size,
+            rhs.size()
+        );
+        let products = rhs.f_index_select(0, &self.col_indices)? * self.values.f_unsqueeze(1)?;
+        let result = Tensor::f_zeros(&[self.size[0], k], (products.kind(), products.device()))?;
+        result.f_index_add(0, &self.row_indices()?, &products)
+    }
+
+    /// Multiplies the matrix by a dense matrix, see `f_mm`.
+    pub fn mm(&self, rhs: &Tensor) -> Tensor {
+        self.f_mm(rhs).unwrap()
+    }
+
+    /// Multiplies the matrix by a dense vector with ncols elements or by a
+    /// dense matrix with shape (ncols, k).
+    pub fn f_matmul(&self, rhs: &Tensor) -> Fallible<Tensor> {
+        match rhs.dim() {
+            1 => self.f_mm(&rhs.f_unsqueeze(1)?)?.f_squeeze1(1),
+            2 => self.f_mm(rhs),
+            _ => bail!(
+                "sparse_csr: expected a 1D or 2D right-hand side, got {:?}",
+                rhs.size()
+            ),
+        }
+    }
+
+    /// Multiplies the matrix by a dense vector or matrix, see `f_matmul`.
+    pub fn matmul(&self, rhs: &Tensor) -> Tensor {
+        self.f_matmul(rhs).unwrap()
+    }
+}
+
+impl Tensor {
+    /// Creates a sparse matrix in CSR format, see `SparseCsr::f_new`.
+    pub fn f_sparse_csr(
+        crow_indices: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        size: &[i64],
+    ) -> Fallible<SparseCsr> {
+        SparseCsr::f_new(crow_indices, col_indices, values, size)
+    }
+
+    /// Creates a sparse matrix in CSR format, see `SparseCsr::f_new`.
+    pub fn sparse_csr(
+        crow_indices: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        size: &[i64],
+    ) -> SparseCsr {
+        SparseCsr::new(crow_indices, col_indices, values, size)
+    }
+
+    /// Converts a two dimension dense tensor to a sparse CSR matrix keeping
+    /// its non-zero elements.
+    pub fn f_to_sparse_csr(&self) -> Fallible<SparseCsr> {
+        let (nrows, ncols) = self.size2()?;
+        let indices = self.f_nonzero()?;
+        let (rows, cols) = (indices.f_select(1, 0)?, indices.f_select(1, 1)?);
+        let values = self
+            .f_reshape(&[-1])?
+            .f_index_select(0, &(&rows * ncols + &cols))?;
+        let counts = rows.f_bincount::<Tensor>(None, nrows)?;
+        let crow_indices = Tensor::f_cat(
+            &[
+                Tensor::f_zeros(&[1], (Kind::Int64, self.device()))?,
+                counts.f_cumsum(0)?,
+            ],
+            0,
+        )?;
+        SparseCsr::f_new(&crow_indices, &cols, &values, &[nrows, ncols])
+    }
+
+    /// Converts a two dimension dense tensor to a sparse CSR matrix.
+    pub fn to_sparse_csr(&self) -> SparseCsr {
+        self.f_to_sparse_csr().unwrap()
+    }
+}
@@ -0,0 +1,75 @@
+//! Helpers for writing tests involving tensors.
+//!
+//! This module is only available when the `test-utils` feature is enabled.
+use crate::{Device, Kind, Tensor};
+
+/// Checks that two tensors have the same shape and that their values are
+/// close, i.e. `|a - b| <= atol + rtol * |b|` for all elements.
+///
+/// On failure, the returned error describes both shapes and the maximum
+/// absolute difference. The comparison is done in double precision on the
+/// CPU so tensors of different kinds and devices can be compared. NaN values
+/// are only considered equal to NaN values.
+pub fn check_tensor_eq(a: &Tensor, b: &Tensor, rtol: f64, atol: f64) -> Result<(), String> {
+    if a.size() != b.size() {
+        return Err(format!("shape mismatch: {:?} vs {:?}", a.size(), b.size()));
+    }
+    let _no_grad = crate::no_grad_guard();
+    let to_vec = |xs: &Tensor| Vec::<f64>::from(&xs.to_device(Device::Cpu).to_kind(Kind::Double));
+    let (a_values, b_values) = (to_vec(a), to_vec(b));
+    let mut max_diff = 0f64;
+    let mut mismatches = 0;
+    for (&a, &b) in a_values.iter().zip(b_values.iter()) {
+        if a.is_nan() || b.is_nan() {
+            if a.is_nan() != b.is_nan() {
+                mismatches += 1;
+                max_diff = f64::NAN
+            }
+            continue;
+        }
+        let diff = (a - b).abs();
+        if diff.is_nan() || diff > atol + rtol * b.abs() {
+            mismatches += 1
+        }
+        max_diff = max_diff.max(diff)
+    }
+    if mismatches == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} of {} values differ with shape {:?}, max absolute difference {} (rtol {}, atol {})",
+            mismatches,
+            a_values.len(),
+            a.size(),
+            max_diff,
+            rtol,
+            atol
+        ))
+    }
+}
+
+/// Asserts that two tensors have the same shape and close values.
+///
+/// The relative and absolute tolerances default to 1e-5 and 1e-8, see
+/// `test_utils::check_tensor_eq`.
+///
+/// ```ignore
+/// assert_tensor_eq!(xs, ys);
+/// assert_tensor_eq!(xs, ys, 1e-3, 1e-5);
+/// ```
+#[macro_export]
+macro_rules! assert_tensor_eq {
+    ($a:expr, $b:expr) => {
+        $crate::assert_tensor_eq!($a, $b, 1e-5, 1e-8)
+    };
+    ($a:expr, $b:expr, $rtol:expr, $atol:expr) => {
+        if let Err(err) = $crate::test_utils::check_tensor_eq(&$a, &$b, $rtol, $atol) {
+            panic!(
+                "assertion failed: `{} == {}`\n{}",
+                stringify!($a),
+                stringify!($b),
+                err
+            )
+        }
+    };
+}
@@ -4,7 +4,7 @@ use crate::{nn, nn::ModuleT, Tensor};
 fn conv_bn(p: nn::Path, c_in: i64, c_out: i64, ksize: i64, pad: i64, stride: i64) -> impl ModuleT {
     let conv2d_cfg = nn::ConvConfig {
         stride,
-        padding: pad,
+        padding: nn::Padding::Explicit(pad),
         bias: false,
         ..Default::default()
     };
@@ -20,7 +20,7 @@ fn conv_bn(p: nn::Path, c_in: i64, c_out: i64, ksize: i64, pad: i64, stride: i64
 
 fn conv_bn2(p: nn::Path, c_in: i64, c_out: i64, ksize: [i64; 2], pad: [i64; 2]) -> impl ModuleT {
     let conv2d_cfg = nn::ConvConfigND::<[i64; 2]> {
-        padding: pad,
+        padding: nn::Padding::Explicit(pad),
         bias: false,
         ..Default::default()
     };
@@ -6,7 +6,7 @@ use crate::nn::{self, ModuleT};
 fn cbr(p: nn::Path, c_in: i64, c_out: i64, ks: i64, stride: i64, g: i64) -> impl ModuleT {
     let conv2d = nn::ConvConfig {
         stride,
-        padding: (ks - 1) / 2,
+        padding: nn::Padding::Explicit((ks - 1) / 2),
         groups: g,
         ..Default::default()
     };
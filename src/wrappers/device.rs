@@ -1,4 +1,7 @@
 //! Devices on which tensor computations are run.
+use super::tensor::Tensor;
+use failure::Fallible;
+use libc::c_int;
 
 /// A torch device.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -37,6 +40,165 @@ impl Cuda {
     pub fn cudnn_set_benchmark(b: bool) {
         unsafe_torch!({ torch_sys::atc_set_benchmark_cudnn(if b { 1 } else { 0 }) })
     }
+
+    /// Releases the unused memory held by the cuda caching allocator so that
+    /// it can be used by other applications.
+    ///
+    /// This has no effect when tch has been compiled without cuda support.
+    pub fn empty_cache() {
+        unsafe_torch!(torch_sys::atc_cuda_empty_cache())
+    }
+
+    /// Returns true if the `from` gpu can directly access the memory of the
+    /// `to` gpu.
+    ///
+    /// This returns false when tch has been compiled without cuda support.
+    pub fn can_access_peer(from: usize, to: usize) -> bool {
+        unsafe_torch!(torch_sys::atc_cuda_can_access_peer(
+            from as c_int,
+            to as c_int
+        )) != 0
+    }
+
+    /// Enables or disables the direct access from the `from` gpu to the memory
+    /// of the `to` gpu.
+    ///
+    /// Once enabled, copies between the two devices, e.g. via `to_device`, do
+    /// not go through the host memory. Copies still work without peer access,
+    /// they are just slower. Enabling the access twice is not an error.
+    pub fn f_set_peer_access(from: usize, to: usize, enabled: bool) -> Fallible<()> {
+        unsafe_torch_err!(torch_sys::atc_cuda_set_peer_access(
+            from as c_int,
+            to as c_int,
+            if enabled { 1 } else { 0 }
+        ));
+        Ok(())
+    }
+
+    /// Enables or disables the peer access between two gpus, see
+    /// `f_set_peer_access`.
+    pub fn set_peer_access(from: usize, to: usize, enabled: bool) {
+        Cuda::f_set_peer_access(from, to, enabled).unwrap()
+    }
+}
+
+// Returns true for the errors raised by the cuda allocator when running out
+// of memory.
+fn is_cuda_oom(err: &failure::Error) -> bool {
+    err.to_string().contains("CUDA out of memory")
+}
+
+/// Runs `f` on `inputs`, falling back to the CPU if the GPU runs out of memory.
+///
+/// When `f` fails with a CUDA out of memory error, the cuda cache is emptied
+/// and `f` is run again on CPU copies of the inputs. The result is then moved
+/// back to the device of the first input. Other errors are returned unchanged.
+pub fn f_oom_fallback<F>(inputs: &[&Tensor], mut f: F) -> Fallible<Tensor>
+where
+    F: FnMut(&[Tensor]) -> Fallible<Tensor>,
+{
+    let xs: Vec<Tensor> = inputs.iter().map(|x| x.shallow_clone()).collect();
+    match f(&xs) {
+        Err(err) if is_cuda_oom(&err) => {
+            drop(xs);
+            Cuda::empty_cache();
+            let device = inputs.first().map_or(Device::Cpu, |x| x.device());
+            let xs = inputs
+                .iter()
+                .map(|x| x.f_to_device(Device::Cpu))
+                .collect::<Fallible<Vec<_>>>()?;
+            f(&xs)?.f_to_device(device)
+        }
+        res => res,
+    }
+}
+
+/// Runs `f` on `inputs`, falling back to the CPU if the GPU runs out of memory.
+///
+/// See `f_oom_fallback`, this panics if `f` fails with another error.
+pub fn oom_fallback<F>(inputs: &[&Tensor], f: F) -> Tensor
+where
+    F: FnMut(&[Tensor]) -> Fallible<Tensor>,
+{
+    f_oom_fallback(inputs, f).unwrap()
+}
+
+/// A cuda stream, the operations on a device are enqueued on its current stream.
+///
+/// Using multiple streams makes it possible to overlap computations and data
+/// transfers. This requires tch to be compiled with cuda support, i.e. with
+/// the `TORCH_CUDA_VERSION` environment variable set.
+#[derive(Debug)]
+pub struct CudaStream {
+    c_stream: *mut torch_sys::C_cuda_stream,
+}
+
+// Restores the previously current stream when dropped, including when the
+// closure passed to `with_stream` panics.
+struct StreamGuard {
+    previous: CudaStream,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        let c_stream = unsafe { torch_sys::atcs_exchange(self.previous.c_stream) };
+        if super::utils::read_and_clean_error().is_ok() {
+            drop(CudaStream { c_stream })
+        }
+    }
+}
+
+impl CudaStream {
+    fn device_index(device: Device) -> Fallible<libc::c_int> {
+        match device {
+            Device::Cpu => bail!("cuda streams are not available on cpu"),
+            Device::Cuda(index) => Ok(index as libc::c_int),
+        }
+    }
+
+    /// Returns a new stream from the pool of streams for `device`.
+    pub fn new(device: Device) -> Fallible<CudaStream> {
+        let c_stream = unsafe_torch_err!({ torch_sys::atcs_new(Self::device_index(device)?) });
+        Ok(CudaStream { c_stream })
+    }
+
+    /// Returns the current stream for `device`.
+    pub fn current(device: Device) -> Fallible<CudaStream> {
+        let c_stream = unsafe_torch_err!({ torch_sys::atcs_current(Self::device_index(device)?) });
+        Ok(CudaStream { c_stream })
+    }
+
+    /// The device on which this stream runs.
+    pub fn device(&self) -> Device {
+        let index = unsafe_torch!({ torch_sys::atcs_device(self.c_stream) });
+        Device::Cuda(index as usize)
+    }
+
+    /// Runs a closure with this stream set as the current stream for its device.
+    ///
+    /// The operations run by the closure on this device are enqueued on this
+    /// stream, the previously current stream is restored afterwards.
+    pub fn with_stream<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let c_stream = unsafe_torch!({ torch_sys::atcs_exchange(self.c_stream) });
+        let _guard = StreamGuard {
+            previous: CudaStream { c_stream },
+        };
+        f()
+    }
+
+    /// Blocks until all the operations enqueued on this stream have completed.
+    pub fn synchronize(&self) {
+        unsafe_torch!({ torch_sys::atcs_synchronize(self.c_stream) })
+    }
+}
+
+impl Drop for CudaStream {
+    fn drop(&mut self) {
+        unsafe_torch!({ torch_sys::atcs_free(self.c_stream) })
+    }
 }
 
 impl Device {
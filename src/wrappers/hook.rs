@@ -0,0 +1,73 @@
+//! Hooks called on gradients during the backward pass.
+use super::tensor::Tensor;
+use failure::Fallible;
+use libc::c_void;
+use torch_sys::*;
+
+type HookFn = Box<dyn Fn(&Tensor) -> Tensor + Send>;
+
+extern "C" fn call_hook(data: *mut c_void, grad: *mut C_tensor) -> *mut C_tensor {
+    let f: &HookFn = unsafe { &*(data as *mut HookFn) };
+    // The gradient is owned by the C++ side so it must not be freed here.
+    let grad = std::mem::ManuallyDrop::new(Tensor { c_tensor: grad });
+    // Unwinding across the C++ frames is undefined behavior, a panic is
+    // reported with a null pointer and turned into an error of the backward pass.
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(&grad))) {
+        Ok(new_grad) => std::mem::ManuallyDrop::new(new_grad).c_tensor,
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+extern "C" fn free_hook(data: *mut c_void) {
+    unsafe { drop(Box::from_raw(data as *mut HookFn)) }
+}
+
+/// A handle on a hook registered with `Tensor::register_hook`.
+///
+/// Dropping the handle keeps the hook registered, use `remove` to disable it.
+#[derive(Debug)]
+pub struct HookHandle {
+    c_handle: *mut C_hook_handle,
+}
+
+impl HookHandle {
+    /// Disables the hook, it will not be called in later backward passes.
+    pub fn remove(self) {
+        unsafe_torch!(atho_remove(self.c_handle))
+    }
+}
+
+impl Drop for HookHandle {
+    fn drop(&mut self) {
+        unsafe { atho_free(self.c_handle) }
+    }
+}
+
+impl Tensor {
+    /// Registers a hook called on the gradient of this tensor during backward.
+    ///
+    /// The tensor returned by `f` replaces the gradient, both when propagated
+    /// further in the graph and when accumulated in the `grad` of a leaf
+    /// tensor. The tensor has to require gradients. If `f` panics, the
+    /// backward pass fails and `f_backward` returns an error.
+    pub fn f_register_hook<F>(&self, f: F) -> Fallible<HookHandle>
+    where
+        F: Fn(&Tensor) -> Tensor + Send + 'static,
+    {
+        let data = Box::into_raw(Box::new(Box::new(f) as HookFn)) as *mut c_void;
+        let c_handle = unsafe { at_register_hook(self.c_tensor, data, call_hook, free_hook) };
+        if let Err(err) = super::utils::read_and_clean_error() {
+            free_hook(data);
+            return Err(err.into());
+        }
+        Ok(HookHandle { c_handle })
+    }
+
+    /// Registers a hook called on the gradient of this tensor during backward.
+    pub fn register_hook<F>(&self, f: F) -> HookHandle
+    where
+        F: Fn(&Tensor) -> Tensor + Send + 'static,
+    {
+        self.f_register_hook(f).unwrap()
+    }
+}
@@ -81,6 +81,46 @@ impl CModule {
         Ok(CModule { c_module })
     }
 
+    /// Creates a module from some TorchScript source code.
+    ///
+    /// Each function defined in `source` becomes a method of the module, the
+    /// one named `forward` is used by the forward pass.
+    pub fn compile(source: &str) -> Fallible<CModule> {
+        let source = std::ffi::CString::new(source)?;
+        let c_module = unsafe_torch_err!({ atm_compile(source.as_ptr()) });
+        Ok(CModule { c_module })
+    }
+
+    /// Freezes the module for inference.
+    ///
+    /// The module is switched to evaluation mode and its graphs get simplified
+    /// by propagating the constants and eliminating the dead code, this removes
+    /// the training only operations such as dropout. The outputs are the same
+    /// as the ones of the original module in evaluation mode. Contrary to the
+    /// PyTorch version, the parameters are not inlined in the graphs as this
+    /// is not supported by libtorch 1.1.
+    pub fn f_freeze(&mut self) -> Fallible<()> {
+        unsafe_torch_err!({ atm_freeze(self.c_module) });
+        Ok(())
+    }
+
+    /// Freezes the module for inference, see `f_freeze`.
+    pub fn freeze(&mut self) {
+        self.f_freeze().unwrap()
+    }
+
+    /// Freezes the module and runs the peephole optimizations on its graphs,
+    /// e.g. to remove the no-op operations, see `f_freeze`.
+    pub fn f_optimize_for_inference(&mut self) -> Fallible<()> {
+        unsafe_torch_err!({ atm_optimize_for_inference(self.c_module) });
+        Ok(())
+    }
+
+    /// Optimizes the module for inference, see `f_optimize_for_inference`.
+    pub fn optimize_for_inference(&mut self) {
+        self.f_optimize_for_inference().unwrap()
+    }
+
     /// Performs the forward pass for a model on some specified tensor input.
     pub fn forward<T: Borrow<Tensor>>(&self, ts: &[T]) -> Fallible<Tensor> {
         let ts: Vec<_> = ts.iter().map(|x| x.borrow().c_tensor).collect();
@@ -101,6 +141,26 @@ impl CModule {
     }
 }
 
+/// Loads a shared library registering some custom operators.
+///
+/// The library should be built against libtorch using the custom operator api,
+/// the operators get registered when it is loaded and can then be used by the
+/// TorchScript modules run via `CModule`. The library stays loaded until the
+/// process exits.
+pub fn load_library<T: AsRef<std::path::Path>>(path: T) -> Fallible<()> {
+    let c_path = path_to_cstring(&path)?;
+    let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL) };
+    if handle.is_null() {
+        let err = unsafe { super::utils::ptr_to_string_no_free(libc::dlerror()) };
+        bail!(
+            "cannot load library {:?}: {}",
+            path.as_ref(),
+            err.unwrap_or_else(|| "unknown error".to_string())
+        )
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::IValue;
@@ -110,4 +170,19 @@ mod tests {
         let ivalue2 = IValue::of_c(ivalue.to_c().unwrap()).unwrap();
         assert_eq!(format!("{:?}", ivalue), format!("{:?}", ivalue2));
     }
+
+    #[test]
+    fn load_library() {
+        let err = super::load_library("/nonexistent/libcustom_ops.so").unwrap_err();
+        assert!(format!("{}", err).contains("libcustom_ops.so"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn load_library_libm() {
+        // libm is always available on linux and gets located via the default
+        // dynamic linker search path.
+        super::load_library("libm.so.6").unwrap();
+        super::load_library("libm.so.6").unwrap();
+    }
 }
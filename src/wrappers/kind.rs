@@ -14,6 +14,10 @@ pub enum Kind {
     ComplexHalf,
     ComplexFloat,
     ComplexDouble,
+    /// Brain floating-point format, this has the same exponent range as `Float`
+    /// with a reduced precision. Some operations may not be implemented for this
+    /// kind, especially on CPU.
+    BFloat16,
 }
 
 impl Kind {
@@ -30,6 +34,7 @@ impl Kind {
             Kind::ComplexHalf => 8,
             Kind::ComplexFloat => 9,
             Kind::ComplexDouble => 10,
+            Kind::BFloat16 => 15,
         }
     }
 
@@ -46,6 +51,7 @@ impl Kind {
             8 => Kind::ComplexHalf,
             9 => Kind::ComplexFloat,
             10 => Kind::ComplexDouble,
+            15 => Kind::BFloat16,
             _ => panic!("unexpected kind {}", v),
         }
     }
@@ -63,8 +69,17 @@ impl Kind {
             Kind::ComplexHalf => 4,
             Kind::ComplexFloat => 8,
             Kind::ComplexDouble => 16,
+            Kind::BFloat16 => 2,
         }
     }
+
+    /// Returns true for the real floating-point kinds.
+    pub fn is_floating_point(self) -> bool {
+        matches!(
+            self,
+            Kind::Half | Kind::Float | Kind::Double | Kind::BFloat16
+        )
+    }
 }
 
 pub const FLOAT_CPU: (Kind, crate::Device) = (Kind::Float, crate::Device::Cpu);
@@ -75,34 +90,60 @@ pub const FLOAT_CUDA: (Kind, crate::Device) = (Kind::Float, crate::Device::Cuda(
 pub const DOUBLE_CUDA: (Kind, crate::Device) = (Kind::Double, crate::Device::Cuda(0));
 pub const INT64_CUDA: (Kind, crate::Device) = (Kind::Int64, crate::Device::Cuda(0));
 
-pub trait T {
+mod private {
+    pub trait Sealed {}
+}
+
+/// The Rust types that can be used as tensor elements, each type is linked to
+/// the corresponding `Kind`.
+///
+/// This can be used to write code that is generic over the element type, e.g.
+/// `Tensor::of_slice` or `Tensor::item`. The trait is sealed: the element
+/// data is copied directly between Rust and libtorch so it is only
+/// implemented for types that have the same memory layout as their kind.
+/// There is no boolean kind, masks use `u8` elements.
+pub trait TensorKind: private::Sealed {
     const KIND: Kind;
 }
 
-impl T for u8 {
+impl private::Sealed for u8 {}
+
+impl TensorKind for u8 {
     const KIND: Kind = Kind::Uint8;
 }
 
-impl T for i8 {
+impl private::Sealed for i8 {}
+
+impl TensorKind for i8 {
     const KIND: Kind = Kind::Int8;
 }
 
-impl T for i16 {
+impl private::Sealed for i16 {}
+
+impl TensorKind for i16 {
     const KIND: Kind = Kind::Int16;
 }
 
-impl T for i32 {
+impl private::Sealed for i32 {}
+
+impl TensorKind for i32 {
     const KIND: Kind = Kind::Int;
 }
 
-impl T for i64 {
+impl private::Sealed for i64 {}
+
+impl TensorKind for i64 {
     const KIND: Kind = Kind::Int64;
 }
 
-impl T for f32 {
+impl private::Sealed for f32 {}
+
+impl TensorKind for f32 {
     const KIND: Kind = Kind::Float;
 }
 
-impl T for f64 {
+impl private::Sealed for f64 {}
+
+impl TensorKind for f64 {
     const KIND: Kind = Kind::Double;
 }
@@ -1,12 +1,16 @@
 #[macro_use]
 mod utils;
-pub use utils::manual_seed;
+pub use utils::{
+    get_device_rng_state, get_rng_state, manual_seed, set_device_rng_state, set_rng_state,
+};
 
 pub(crate) mod device;
+pub(crate) mod hook;
 pub(crate) mod image;
 pub(crate) mod jit;
 pub(crate) mod kind;
 pub(crate) mod optimizer;
+pub(crate) mod profiler;
 pub(crate) mod scalar;
 pub(crate) mod tensor;
 pub(crate) mod tensor_fallible_generated;
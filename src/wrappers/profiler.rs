@@ -0,0 +1,376 @@
+//! Profiling of the operations run by torch.
+use failure::Fallible;
+use libc::{c_char, c_void};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// A single operation recorded by the profiler.
+#[derive(Debug, Clone)]
+pub struct ProfileEvent {
+    pub name: String,
+    /// The start time in microseconds, relative to the first recorded operation.
+    pub start_us: f64,
+    pub cpu_us: f64,
+    pub cuda_us: f64,
+    pub thread_id: i64,
+}
+
+/// Aggregated statistics for all the operations sharing the same name.
+#[derive(Debug, Clone)]
+pub struct OpStats {
+    pub name: String,
+    pub count: usize,
+    pub cpu_us: f64,
+    pub cuda_us: f64,
+}
+
+/// The operations recorded during a call to `profile`.
+#[derive(Debug, Clone)]
+pub struct ProfileResult {
+    events: Vec<ProfileEvent>,
+}
+
+extern "C" fn add_event_callback(
+    data: *mut c_void,
+    name: *const c_char,
+    start_us: f64,
+    cpu_us: f64,
+    cuda_us: f64,
+    thread_id: i64,
+) {
+    let name = unsafe { std::ffi::CStr::from_ptr(name).to_string_lossy() };
+    let v: &mut Vec<ProfileEvent> = unsafe { &mut *(data as *mut Vec<ProfileEvent>) };
+    v.push(ProfileEvent {
+        name: name.into_owned(),
+        start_us,
+        cpu_us,
+        cuda_us,
+        thread_id,
+    })
+}
+
+extern "C" fn ignore_event_callback(
+    _data: *mut c_void,
+    _name: *const c_char,
+    _start_us: f64,
+    _cpu_us: f64,
+    _cuda_us: f64,
+    _thread_id: i64,
+) {
+}
+
+// Disables the profiler when dropped, this ensures that the profiler does
+// not stay enabled if the profiled closure panics.
+struct ProfilerGuard {
+    enabled: bool,
+}
+
+impl ProfilerGuard {
+    fn disable(&mut self) -> Vec<ProfileEvent> {
+        let mut events: Vec<ProfileEvent> = vec![];
+        self.enabled = false;
+        unsafe_torch!({
+            torch_sys::atp_disable_profiler(
+                &mut events as *mut _ as *mut c_void,
+                add_event_callback,
+            )
+        });
+        events
+    }
+}
+
+impl Drop for ProfilerGuard {
+    fn drop(&mut self) {
+        if self.enabled {
+            unsafe { torch_sys::atp_disable_profiler(std::ptr::null_mut(), ignore_event_callback) };
+            let _err = super::utils::read_and_clean_error();
+        }
+    }
+}
+
+/// Runs a closure while recording the torch operations that it triggers.
+///
+/// The profiler is only enabled for the duration of the closure. When `use_cuda`
+/// is set, the time spent on the GPU is recorded too.
+pub fn profile<T, F>(use_cuda: bool, f: F) -> (T, ProfileResult)
+where
+    F: FnOnce() -> T,
+{
+    unsafe_torch!(torch_sys::atp_enable_profiler(if use_cuda { 1 } else { 0 }));
+    let mut guard = ProfilerGuard { enabled: true };
+    let result = f();
+    let events = guard.disable();
+    (result, ProfileResult { events })
+}
+
+impl ProfileResult {
+    /// The recorded operations, in the order in which they completed on each thread.
+    pub fn events(&self) -> &[ProfileEvent] {
+        &self.events
+    }
+
+    /// Returns the statistics for each operation name, sorted by decreasing cpu time.
+    pub fn op_stats(&self) -> Vec<OpStats> {
+        let mut stats: HashMap<&str, OpStats> = HashMap::new();
+        for event in self.events.iter() {
+            let stat = stats.entry(&event.name).or_insert_with(|| OpStats {
+                name: event.name.clone(),
+                count: 0,
+                cpu_us: 0.,
+                cuda_us: 0.,
+            });
+            stat.count += 1;
+            stat.cpu_us += event.cpu_us;
+            stat.cuda_us += event.cuda_us;
+        }
+        let mut stats: Vec<OpStats> = stats.into_values().collect();
+        stats.sort_by(|s1, s2| s2.cpu_us.partial_cmp(&s1.cpu_us).unwrap());
+        stats
+    }
+
+    /// Writes the recorded operations to a file using the chrome trace format.
+    ///
+    /// The resulting file can be visualized using chrome://tracing.
+    pub fn export_chrome_trace<T: AsRef<std::path::Path>>(&self, path: T) -> Fallible<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "[")?;
+        for (i, event) in self.events.iter().enumerate() {
+            let sep = if i + 1 == self.events.len() { "" } else { "," };
+            writeln!(
+                file,
+                r#"{{"name": {:?}, "ph": "X", "ts": {}, "dur": {}, "tid": {}, "pid": "CPU Functions", "args": {{}}}}{}"#,
+                event.name, event.start_us, event.cpu_us, event.thread_id, sep
+            )?;
+        }
+        writeln!(file, "]")?;
+        Ok(())
+    }
+}
+
+/// A CPU memory allocation or deallocation recorded by `profile_memory`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryEvent {
+    /// The time in microseconds since the start of the profiling.
+    pub time_us: f64,
+    /// The number of bytes allocated, this is negative for deallocations.
+    pub bytes: i64,
+}
+
+/// The memory events recorded during a call to `profile_memory`.
+#[derive(Debug, Clone)]
+pub struct MemoryProfile {
+    events: Vec<MemoryEvent>,
+    duration_us: f64,
+}
+
+extern "C" fn add_memory_event_callback(data: *mut c_void, time_us: f64, bytes: i64) {
+    let v: &mut Vec<MemoryEvent> = unsafe { &mut *(data as *mut Vec<MemoryEvent>) };
+    v.push(MemoryEvent { time_us, bytes })
+}
+
+extern "C" fn ignore_memory_event_callback(_data: *mut c_void, _time_us: f64, _bytes: i64) {}
+
+// Disables the memory profiler when dropped, see `ProfilerGuard`.
+struct MemoryProfilerGuard {
+    enabled: bool,
+}
+
+impl MemoryProfilerGuard {
+    fn disable(&mut self) -> Vec<MemoryEvent> {
+        let mut events: Vec<MemoryEvent> = vec![];
+        self.enabled = false;
+        unsafe_torch!(torch_sys::atp_disable_memory_profiler(
+            &mut events as *mut _ as *mut c_void,
+            add_memory_event_callback,
+        ));
+        events
+    }
+}
+
+impl Drop for MemoryProfilerGuard {
+    fn drop(&mut self) {
+        if self.enabled {
+            unsafe {
+                torch_sys::atp_disable_memory_profiler(
+                    std::ptr::null_mut(),
+                    ignore_memory_event_callback,
+                )
+            };
+            let _err = super::utils::read_and_clean_error();
+        }
+    }
+}
+
+/// Runs a closure while recording the CPU memory allocations and deallocations.
+///
+/// Only the memory allocated while the closure runs is tracked, freeing
+/// tensors that were created before does not result in events. The allocations
+/// made by other threads during this time are recorded too, whereas the memory
+/// used by tensors on cuda devices is not. Only a single memory profiling can
+/// be in progress at a time, this returns an error otherwise.
+pub fn profile_memory<T, F>(f: F) -> Fallible<(T, MemoryProfile)>
+where
+    F: FnOnce() -> T,
+{
+    unsafe_torch_err!(torch_sys::atp_enable_memory_profiler());
+    let mut guard = MemoryProfilerGuard { enabled: true };
+    let start = std::time::Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let events = guard.disable();
+    let duration_us = elapsed.as_secs() as f64 * 1e6 + f64::from(elapsed.subsec_nanos()) / 1e3;
+    let duration_us = events
+        .last()
+        .map_or(duration_us, |e| e.time_us.max(duration_us));
+    Ok((
+        result,
+        MemoryProfile {
+            events,
+            duration_us,
+        },
+    ))
+}
+
+impl MemoryProfile {
+    /// The recorded allocations and deallocations, in chronological order.
+    pub fn events(&self) -> &[MemoryEvent] {
+        &self.events
+    }
+
+    /// The memory in use after each event, as pairs of a time in microseconds
+    /// and a number of bytes.
+    pub fn timeline(&self) -> Vec<(f64, i64)> {
+        let mut in_use = 0;
+        self.events
+            .iter()
+            .map(|event| {
+                in_use += event.bytes;
+                (event.time_us, in_use)
+            })
+            .collect()
+    }
+
+    /// The maximum number of bytes in use at any point.
+    pub fn peak_bytes(&self) -> i64 {
+        self.timeline().iter().map(|&(_, b)| b).fold(0, i64::max)
+    }
+
+    /// The number of bytes in use averaged over the profiling duration.
+    pub fn average_bytes(&self) -> f64 {
+        if self.duration_us <= 0. {
+            return 0.;
+        }
+        let timeline = self.timeline();
+        let mut total = 0.;
+        for (i, &(time_us, bytes)) in timeline.iter().enumerate() {
+            let end_us = timeline.get(i + 1).map_or(self.duration_us, |e| e.0);
+            total += bytes as f64 * (end_us - time_us)
+        }
+        total / self.duration_us
+    }
+
+    /// Writes the memory timeline to a file using the chrome trace format.
+    ///
+    /// The resulting file can be visualized using chrome://tracing where it
+    /// appears as a counter.
+    pub fn export_chrome_trace<T: AsRef<std::path::Path>>(&self, path: T) -> Fallible<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "[")?;
+        let timeline = self.timeline();
+        for (i, (time_us, bytes)) in timeline.iter().enumerate() {
+            let sep = if i + 1 == timeline.len() { "" } else { "," };
+            writeln!(
+                file,
+                r#"{{"name": "CPU Memory", "ph": "C", "ts": {}, "pid": "CPU Memory", "args": {{"bytes": {}}}}}{}"#,
+                time_us, bytes, sep
+            )?;
+        }
+        writeln!(file, "]")?;
+        Ok(())
+    }
+}
+
+thread_local! {
+    static FLOP_COUNTER: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Adds some multiply-accumulate operations to the count of the enclosing
+/// `count_flops` call if any.
+///
+/// This is used by the `nn` layers and can be used by custom layers to report
+/// their own operations.
+pub fn add_flops(macs: u64) {
+    FLOP_COUNTER.with(|c| {
+        if let Some(count) = c.get() {
+            c.set(Some(count + macs))
+        }
+    })
+}
+
+/// Adds the multiply-accumulate operations of `lhs.matmul(rhs)` to the count
+/// of the enclosing `count_flops` call if any.
+///
+/// This also covers `mm` and `bmm` which are special cases of `matmul`, the
+/// batch dimensions are broadcast as in `matmul`.
+pub fn add_matmul_flops(lhs: &crate::Tensor, rhs: &crate::Tensor) {
+    if !is_counting_flops() {
+        return;
+    }
+    let (lhs, rhs) = (lhs.size(), rhs.size());
+    let (inner, lhs_rows, lhs_batch) = match lhs.as_slice() {
+        [] => return,
+        [k] => (*k, 1, &lhs[..0]),
+        [batch @ .., n, k] => (*k, *n, batch),
+    };
+    let (rhs_cols, rhs_batch) = match rhs.as_slice() {
+        [] => return,
+        [_] => (1, &rhs[..0]),
+        [batch @ .., _, p] => (*p, batch),
+    };
+    let batch_len = std::cmp::max(lhs_batch.len(), rhs_batch.len());
+    let batch_dim = |batch: &[i64], i: usize| {
+        (i + batch.len())
+            .checked_sub(batch_len)
+            .map_or(1, |j| batch[j])
+    };
+    let batch: i64 = (0..batch_len)
+        .map(|i| std::cmp::max(batch_dim(lhs_batch, i), batch_dim(rhs_batch, i)))
+        .product();
+    add_flops((batch * lhs_rows * inner * rhs_cols) as u64)
+}
+
+/// Returns true when a `count_flops` call is in progress on this thread.
+pub fn is_counting_flops() -> bool {
+    FLOP_COUNTER.with(|c| c.get().is_some())
+}
+
+// Restores the previous counter when dropped so that nested calls and panics
+// are handled properly.
+struct FlopCounterGuard {
+    previous: Option<u64>,
+}
+
+impl Drop for FlopCounterGuard {
+    fn drop(&mut self) {
+        FLOP_COUNTER.with(|c| c.set(self.previous))
+    }
+}
+
+/// Returns the approximate number of multiply-accumulate operations used by a
+/// forward pass of `module` on `xs`, in evaluation mode.
+///
+/// The conv and linear layers of `nn` report their operations when they are
+/// run, the count is based on the operand shapes. The libtorch profiler does
+/// not record the shapes of the operations that it intercepts, so the matmul
+/// operations, including `mm` and `bmm`, are not counted automatically: the
+/// modules or closures using them have to call `add_matmul_flops`, and other
+/// custom operations can be reported via `add_flops`.
+pub fn count_flops<M: crate::nn::ModuleT>(module: &M, xs: &crate::Tensor) -> u64 {
+    let _no_grad = crate::no_grad_guard();
+    let _guard = FlopCounterGuard {
+        previous: FLOP_COUNTER.with(|c| c.replace(Some(0))),
+    };
+    let _ys = module.forward_t(xs, false);
+    FLOP_COUNTER.with(|c| c.get()).unwrap_or(0)
+}
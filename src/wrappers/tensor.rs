@@ -19,6 +19,11 @@ extern "C" fn add_callback(data: *mut c_void, name: *const c_char, c_tensor: *mu
     v.push((name.to_owned(), Tensor { c_tensor }))
 }
 
+extern "C" fn bytes_callback(data: *mut c_void, bytes: *const c_char, size: usize) {
+    let v: &mut Vec<u8> = unsafe { &mut *(data as *mut Vec<u8>) };
+    v.extend_from_slice(unsafe { std::slice::from_raw_parts(bytes as *const u8, size) })
+}
+
 impl Tensor {
     /// Creates a new tensor.
     pub fn new() -> Tensor {
@@ -39,6 +44,28 @@ impl Tensor {
         sz
     }
 
+    /// Returns the stride of the input tensor, i.e. the number of elements
+    /// to skip in the storage to move by one along each dimension.
+    pub fn stride(&self) -> Vec<i64> {
+        let dim = unsafe_torch!({ at_dim(self.c_tensor) });
+        let mut sz = vec![0i64; dim];
+        unsafe_torch!({ at_stride(self.c_tensor, sz.as_mut_ptr()) });
+        sz
+    }
+
+    /// Returns true if the tensor elements are laid out contiguously in memory.
+    ///
+    /// Views such as transposed tensors are usually not contiguous, `contiguous`
+    /// can be used to get a contiguous copy in this case.
+    pub fn is_contiguous(&self) -> bool {
+        unsafe_torch!({ at_is_contiguous(self.c_tensor) != 0 })
+    }
+
+    /// Returns the offset of the first tensor element in the underlying storage.
+    pub fn storage_offset(&self) -> i64 {
+        unsafe_torch!({ at_storage_offset(self.c_tensor) })
+    }
+
     /// Returns the tensor size for single dimension tensors.
     pub fn size1(&self) -> Fallible<i64> {
         match self.size().as_slice() {
@@ -117,16 +144,72 @@ impl Tensor {
         self.f_int64_value(idx).unwrap()
     }
 
+    /// Returns the value of a tensor holding a single element, e.g. a loss,
+    /// converted to `T`. An error is returned if the tensor does not have
+    /// exactly one element, whatever its number of dimensions. This is also
+    /// available through `TryFrom<&Tensor>`, e.g. `f64::try_from(&loss)?`.
+    pub fn f_item<T: kind::TensorKind + Copy + Default>(&self) -> Fallible<T> {
+        let numel = self.numel();
+        ensure!(
+            numel == 1,
+            "item: expected exactly one element, got {} for shape {:?}",
+            numel,
+            self.size()
+        );
+        let mut value = [T::default()];
+        self.f_to_kind(T::KIND)?.f_copy_data(&mut value, 1)?;
+        Ok(value[0])
+    }
+
+    /// Returns the value of a tensor holding a single element converted to
+    /// `T`. Panics otherwise.
+    pub fn item<T: kind::TensorKind + Copy + Default>(&self) -> T {
+        self.f_item().unwrap()
+    }
+
     /// Returns true if gradient are currently tracked for this tensor.
     pub fn requires_grad(&self) -> bool {
         unsafe_torch!({ at_requires_grad(self.c_tensor) }) != 0
     }
 
+    /// Returns true if the tensor is a leaf of the autograd graph, i.e. it has
+    /// not been computed by an operation tracking gradients.
+    ///
+    /// Only the gradients of leaf tensors are populated by backward unless
+    /// `retain_grad` is used.
+    pub fn is_leaf(&self) -> bool {
+        unsafe_torch!(at_is_leaf(self.c_tensor)) != 0
+    }
+
+    /// Makes backward populate the gradient of this non-leaf tensor so that it
+    /// can be read via `grad`. This has no effect on leaf tensors and returns
+    /// an error if the tensor does not require grad.
+    pub fn f_retain_grad(&self) -> Fallible<()> {
+        unsafe_torch_err!(at_retain_grad(self.c_tensor));
+        Ok(())
+    }
+
+    /// Makes backward populate the gradient of this non-leaf tensor, panics if
+    /// the tensor does not require grad.
+    pub fn retain_grad(&self) {
+        self.f_retain_grad().unwrap()
+    }
+
+    /// Returns true if `retain_grad` has been called on this non-leaf tensor.
+    pub fn retains_grad(&self) -> bool {
+        unsafe_torch!(at_retains_grad(self.c_tensor)) != 0
+    }
+
     /// Returns true is the tensor is defined.
     pub fn defined(&self) -> bool {
         unsafe_torch!({ at_defined(self.c_tensor) != 0 })
     }
 
+    /// Returns true if the tensor uses a sparse layout.
+    pub fn is_sparse(&self) -> bool {
+        unsafe_torch!({ at_is_sparse(self.c_tensor) != 0 })
+    }
+
     /// Zeroes the gradient tensor attached to this tensor if defined.
     pub fn zero_grad(&mut self) {
         let mut grad = self.grad();
@@ -220,9 +303,20 @@ impl Tensor {
         self.size().iter().product()
     }
 
+    /// Returns the size in bytes of a single element of the tensor.
+    pub fn element_size(&self) -> i64 {
+        i64::from(self.kind().elt_size_in_bytes())
+    }
+
+    /// Returns the number of bytes used by the tensor elements. For views this
+    /// can be smaller than the size of the underlying storage.
+    pub fn nbytes(&self) -> i64 {
+        self.numel() * self.element_size()
+    }
+
     // This is similar to vec_... but faster as it directly blits the data.
     /// Converts a slice to a tensor.
-    pub fn f_of_slice<T: kind::T>(data: &[T]) -> Fallible<Tensor> {
+    pub fn f_of_slice<T: kind::TensorKind>(data: &[T]) -> Fallible<Tensor> {
         let data_len = data.len();
         let data = data.as_ptr() as *const c_void;
         let c_tensor = unsafe_torch_err!({
@@ -238,7 +332,7 @@ impl Tensor {
     }
 
     /// Converts a slice to a tensor.
-    pub fn of_slice<T: kind::T>(data: &[T]) -> Tensor {
+    pub fn of_slice<T: kind::TensorKind>(data: &[T]) -> Tensor {
         Self::f_of_slice(data).unwrap()
     }
 
@@ -264,11 +358,33 @@ impl Tensor {
     }
 
     /// Returns a new tensor that share storage with the input tensor.
+    ///
+    /// The data is not copied so in-place operations on either tensor are
+    /// visible through the other one, use `copy` to get a tensor with its own
+    /// storage.
     pub fn shallow_clone(&self) -> Tensor {
         let c_tensor = unsafe_torch!({ at_shallow_clone(self.c_tensor) });
         Tensor { c_tensor }
     }
 
+    /// Moves a tensor to a specified device, the copy is asynchronous when
+    /// `non_blocking` applies, see `Tensor::to_device_non_blocking`.
+    pub fn f_to_device_non_blocking(&self, device: Device) -> Fallible<Tensor> {
+        let c_tensor = unsafe_torch_err!(at_to_device(self.c_tensor, device.c_int(), 1));
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Returns true if both tensors use the same underlying storage.
+    ///
+    /// This is the case for shallow clones and views such as the result of
+    /// `narrow` or `transpose`, even if the elements they cover do not
+    /// overlap. Undefined tensors do not share storage with any tensor.
+    pub fn shares_storage_with(&self, other: &Tensor) -> bool {
+        self.defined()
+            && other.defined()
+            && unsafe_torch!(at_is_alias_of(self.c_tensor, other.c_tensor)) == 1
+    }
+
     /// Gets the sub-tensor at the given index.
     pub fn f_get(&self, index: i64) -> Fallible<Tensor> {
         let c_tensor = unsafe_torch_err!({ at_get(self.c_tensor, index as c_int) });
@@ -280,6 +396,24 @@ impl Tensor {
         self.f_get(index).unwrap()
     }
 
+    /// Sums the product of the elements of the input tensors along the
+    /// dimensions specified by an equation in the Einstein summation notation,
+    /// e.g. `"ij,jk->ik"` for a matrix multiplication.
+    pub fn f_einsum<T: Borrow<Tensor>>(equation: &str, tensors: &[T]) -> Fallible<Tensor> {
+        let equation = std::ffi::CString::new(equation)?;
+        let tensors: Vec<_> = tensors.iter().map(|x| x.borrow().c_tensor).collect();
+        let c_tensor = unsafe_torch_err!({
+            at_einsum(equation.as_ptr(), tensors.as_ptr(), tensors.len() as c_int)
+        });
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Sums the product of the elements of the input tensors along the
+    /// dimensions specified by an equation in the Einstein summation notation.
+    pub fn einsum<T: Borrow<Tensor>>(equation: &str, tensors: &[T]) -> Tensor {
+        Tensor::f_einsum(equation, tensors).unwrap()
+    }
+
     /// Copies values from the argument tensor to the input tensor.
     pub fn f_copy_(&mut self, src: &Tensor) -> Fallible<()> {
         unsafe_torch_err!({ at_copy_(self.c_tensor, src.c_tensor) });
@@ -291,6 +425,34 @@ impl Tensor {
         self.f_copy_(src).unwrap()
     }
 
+    /// Replaces the data of this tensor with the data of the argument tensor.
+    ///
+    /// Contrary to `copy_`, the shape and kind of the input tensor can change.
+    /// Other tensors created via `shallow_clone` see the new data.
+    pub fn f_set_data(&mut self, src: &Tensor) -> Fallible<()> {
+        unsafe_torch_err!({ at_set_data(self.c_tensor, src.c_tensor) });
+        Ok(())
+    }
+
+    /// Replaces the data of this tensor with the data of the argument tensor.
+    pub fn set_data(&mut self, src: &Tensor) {
+        self.f_set_data(src).unwrap()
+    }
+
+    /// Resets the gradient of this tensor to an undefined tensor.
+    ///
+    /// The memory used by the gradient is released and the next backward pass
+    /// allocates a new gradient tensor.
+    pub fn f_reset_grad(&self) -> Fallible<()> {
+        unsafe_torch_err!({ at_reset_grad(self.c_tensor) });
+        Ok(())
+    }
+
+    /// Resets the gradient of this tensor to an undefined tensor.
+    pub fn reset_grad(&self) {
+        self.f_reset_grad().unwrap()
+    }
+
     /// Loads a tensor from a file.
     ///
     /// The file format is the same as the one used by the PyTorch C++ API.
@@ -349,6 +511,67 @@ impl Tensor {
         Ok(v)
     }
 
+    /// Serializes a tensor to a buffer of bytes.
+    ///
+    /// The format is the same as the one used by `save`.
+    pub fn save_to_bytes(&self) -> Fallible<Vec<u8>> {
+        let mut v: Vec<u8> = vec![];
+        unsafe_torch_err!(at_save_to_bytes(
+            self.c_tensor,
+            &mut v as *mut _ as *mut c_void,
+            bytes_callback
+        ));
+        Ok(v)
+    }
+
+    /// Deserializes a tensor from a buffer of bytes produced by `save_to_bytes`.
+    pub fn load_from_bytes(bytes: &[u8]) -> Fallible<Tensor> {
+        let c_tensor = unsafe_torch_err!(at_load_from_bytes(
+            bytes.as_ptr() as *const c_char,
+            bytes.len()
+        ));
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Serializes some named tensors to a buffer of bytes.
+    ///
+    /// The format is the same as the one used by `save_multi`.
+    pub fn save_multi_to_bytes<S: AsRef<str>, T: AsRef<Tensor>>(
+        named_tensors: &[(S, T)],
+    ) -> Fallible<Vec<u8>> {
+        let c_tensors = named_tensors
+            .iter()
+            .map(|nt| nt.1.as_ref().c_tensor)
+            .collect::<Vec<_>>();
+        let names = named_tensors
+            .iter()
+            .map(|nt| std::ffi::CString::new(nt.0.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let name_ptrs = names.iter().map(|n| n.as_ptr()).collect::<Vec<_>>();
+        let mut v: Vec<u8> = vec![];
+        unsafe_torch_err!(at_save_multi_to_bytes(
+            c_tensors.as_ptr(),
+            name_ptrs.as_ptr(),
+            names.len() as i32,
+            &mut v as *mut _ as *mut c_void,
+            bytes_callback
+        ));
+        Ok(v)
+    }
+
+    /// Deserializes some named tensors from a buffer of bytes produced by
+    /// `save_multi_to_bytes`.
+    pub fn load_multi_from_bytes(bytes: &[u8]) -> Fallible<Vec<(String, Tensor)>> {
+        let mut v: Vec<(String, Tensor)> = vec![];
+        unsafe_torch_err!(at_load_callback_from_bytes(
+            bytes.as_ptr() as *const c_char,
+            bytes.len(),
+            &mut v as *mut _ as *mut c_void,
+            add_callback
+        ));
+        Ok(v)
+    }
+
     /// Returns a string representation for the tensor.
     ///
     /// The representation will contain all the tensor element hence may be huge for
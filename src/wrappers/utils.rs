@@ -29,6 +29,16 @@ pub(super) unsafe fn ptr_to_string(ptr: *mut c_char) -> Option<String> {
     }
 }
 
+// Same as ptr_to_string but the pointer is not freed, this is used for
+// strings that are owned by the C library.
+pub(super) unsafe fn ptr_to_string_no_free(ptr: *const c_char) -> Option<String> {
+    if !ptr.is_null() {
+        Some(std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    } else {
+        None
+    }
+}
+
 pub(super) fn read_and_clean_error() -> Result<(), TorchError> {
     unsafe {
         match ptr_to_string(torch_sys::get_and_reset_last_err()) {
@@ -69,3 +79,54 @@ pub(super) fn path_to_cstring<T: AsRef<std::path::Path>>(path: T) -> Fallible<st
 pub fn manual_seed(seed: i64) {
     unsafe_torch!({ torch_sys::at_manual_seed(seed) })
 }
+
+/// Returns the state of the cpu random number generator as a uint8 tensor.
+///
+/// The state can be saved, e.g. along with a checkpoint, and restored later
+/// via `set_rng_state` so that the same random values get generated.
+/// Only the cpu generator is handled, see `get_device_rng_state` for the
+/// cuda generators.
+pub fn get_rng_state() -> crate::Tensor {
+    let c_tensor = unsafe_torch!({ torch_sys::at_get_rng_state() });
+    crate::Tensor { c_tensor }
+}
+
+/// Restores the state of the cpu random number generator.
+///
+/// The state should have been returned by `get_rng_state`, an error is
+/// returned if it is invalid.
+pub fn set_rng_state(state: &crate::Tensor) -> Fallible<()> {
+    unsafe_torch_err!({ torch_sys::at_set_rng_state(state.c_tensor) });
+    Ok(())
+}
+
+/// Returns the state of the random number generator of a device as a uint8
+/// tensor.
+///
+/// Each cuda device has its own generator, an error is returned for cuda
+/// devices when tch has been compiled without cuda support.
+pub fn get_device_rng_state(device: crate::Device) -> Fallible<crate::Tensor> {
+    match device {
+        crate::Device::Cpu => Ok(get_rng_state()),
+        crate::Device::Cuda(_) => {
+            let c_tensor = unsafe_torch_err!(torch_sys::atc_cuda_get_rng_state(device.c_int()));
+            Ok(crate::Tensor { c_tensor })
+        }
+    }
+}
+
+/// Restores the state of the random number generator of a device, the state
+/// should have been returned by `get_device_rng_state` for the same kind of
+/// device.
+pub fn set_device_rng_state(device: crate::Device, state: &crate::Tensor) -> Fallible<()> {
+    match device {
+        crate::Device::Cpu => set_rng_state(state),
+        crate::Device::Cuda(_) => {
+            unsafe_torch_err!(torch_sys::atc_cuda_set_rng_state(
+                device.c_int(),
+                state.c_tensor
+            ));
+            Ok(())
+        }
+    }
+}
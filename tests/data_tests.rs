@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::io::Write;
 use tch::{data, Tensor};
 
@@ -52,7 +53,71 @@ fn text() {
     for xs in text_data.iter_shuffle(2, 5) {
         let first_column_plus_one = (xs.narrow(1, 0, 1) + 1).fmod(10);
         let second_column = xs.narrow(1, 1, 1);
-        let err = i64::from((first_column_plus_one - second_column).pow(2).sum());
+        let err = i64::try_from((first_column_plus_one - second_column).pow(2).sum()).unwrap();
         assert_eq!(err, 0)
     }
 }
+
+#[test]
+fn random_split_and_kfold() {
+    let xs = Tensor::arange(10, tch::kind::INT64_CPU);
+    let ys = &xs * 2;
+    let splits = data::random_split(&xs, &ys, &[8, 2], 42).unwrap();
+    assert_eq!(splits.len(), 2);
+    assert_eq!((splits[0].len(), splits[1].len()), (8, 2));
+    let mut all: Vec<i64> = splits.iter().flat_map(|s| s.indexes().to_vec()).collect();
+    all.sort();
+    assert_eq!(all, (0..10).collect::<Vec<_>>());
+    // The same seed results in the same split.
+    let same = data::random_split(&xs, &ys, &[8, 2], 42).unwrap();
+    assert_eq!(same[1].indexes(), splits[1].indexes());
+    let (val_xs, val_ys) = splits[1].tensors();
+    assert_eq!(Vec::<i64>::from(&val_xs), splits[1].indexes());
+    assert_eq!(Vec::<i64>::from(&val_ys), Vec::<i64>::from(&(&val_xs * 2)));
+    let (x, y) = splits[0].get(3);
+    assert_eq!(i64::try_from(&x).unwrap() * 2, i64::try_from(&y).unwrap());
+    assert!(data::random_split(&xs, &ys, &[8, 3], 42).is_err());
+
+    let folds = data::kfold(&xs, &ys, 3, 0).unwrap();
+    let mut validation: Vec<i64> = vec![];
+    for (train, val) in folds.iter() {
+        assert_eq!(train.len() + val.len(), 10);
+        assert!(val.indexes().iter().all(|i| !train.indexes().contains(i)));
+        validation.extend_from_slice(val.indexes());
+        let batches = train.iter(2).count();
+        assert_eq!(batches, train.len() / 2);
+    }
+    validation.sort();
+    assert_eq!(validation, (0..10).collect::<Vec<_>>());
+    assert!(data::kfold(&xs, &ys, 11, 0).is_err());
+}
+
+#[test]
+fn iter2_prefetch() {
+    let vs: Vec<i64> = (0..103).collect();
+    let xs = Tensor::of_slice(&vs);
+    let ys = Tensor::of_slice(&vs.iter().map(|x| x * 2).collect::<Vec<_>>());
+    for &prefetch_factor in [1, 3, 50].iter() {
+        let mut iter = data::Iter2::new(&xs, &ys, 10);
+        iter.return_smaller_last_batch()
+            .prefetch(prefetch_factor)
+            .pin_memory();
+        let batches: Vec<(Vec<i64>, Vec<i64>)> = iter
+            .by_ref()
+            .map(|(xs, ys)| (Vec::<i64>::from(&xs), Vec::<i64>::from(&ys)))
+            .collect();
+        // An exhausted iterator does not restart the epoch.
+        assert!(iter.next().is_none());
+        assert_eq!(batches.len(), 11);
+        assert_eq!(batches[10].0, vec![100, 101, 102]);
+        let xs: Vec<i64> = batches.iter().flat_map(|(xs, _)| xs.clone()).collect();
+        let ys: Vec<i64> = batches.iter().flat_map(|(_, ys)| ys.clone()).collect();
+        assert_eq!(xs, vs);
+        assert_eq!(ys, vs.iter().map(|x| x * 2).collect::<Vec<_>>());
+    }
+    // Dropping the iterator before the end of the epoch stops the worker.
+    let mut iter = data::Iter2::new(&xs, &ys, 10);
+    let first = iter.prefetch(2).next().unwrap();
+    assert_eq!(Vec::<i64>::from(&first.0), (0..10).collect::<Vec<_>>());
+    drop(iter);
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use tch::{Device, Tensor};
 
 #[test]
@@ -5,3 +6,92 @@ fn tensor_device() {
     let t = Tensor::of_slice(&[3, 1, 4]);
     assert_eq!(t.device(), Device::Cpu)
 }
+
+#[test]
+fn cuda_stream() {
+    assert!(tch::CudaStream::new(Device::Cpu).is_err());
+    if !tch::Cuda::is_available() {
+        return;
+    }
+    let device = Device::Cuda(0);
+    let stream = match tch::CudaStream::new(device) {
+        Ok(stream) => stream,
+        // tch has been compiled without cuda streams support.
+        Err(_) => return,
+    };
+    assert_eq!(stream.device(), device);
+    let xs = Tensor::ones(&[64, 64], (tch::Kind::Float, device));
+    let ys = stream.with_stream(|| xs.matmul(&xs));
+    stream.synchronize();
+    assert_eq!(f64::try_from(ys.mean()).unwrap(), 64.);
+}
+
+#[test]
+fn new_tensors_inherit_kind_and_device() {
+    let device = Device::cuda_if_available();
+    let xs = Tensor::ones(&[2], (tch::Kind::Half, device));
+    let zeros = xs.new_zeros(&[3, 4]);
+    assert_eq!(
+        (zeros.kind(), zeros.device(), zeros.size()),
+        (tch::Kind::Half, device, vec![3, 4])
+    );
+    let ones = xs.new_ones(&[5]);
+    assert_eq!((ones.kind(), ones.device()), (tch::Kind::Half, device));
+    assert_eq!(Vec::<f64>::from(&ones.to_kind(tch::Kind::Float)), [1.; 5]);
+    let empty = xs.new_empty(&[0, 2]);
+    assert_eq!((empty.kind(), empty.device()), (tch::Kind::Half, device));
+    let ys = Tensor::of_slice(&[1i64, 2]);
+    let full = ys.new_full(&[2, 2], 7);
+    assert_eq!(full.kind(), tch::Kind::Int64);
+    assert_eq!(Vec::<Vec<i64>>::from(&full), [[7, 7], [7, 7]]);
+}
+
+#[test]
+fn oom_fallback() {
+    // Simulate a GPU running out of memory on inputs above 4 elements by
+    // failing on the first attempt only.
+    let mut attempts = 0;
+    let mut f = |xs: &[Tensor]| {
+        attempts += 1;
+        if attempts == 1 && xs[0].numel() > 4 {
+            Err(failure::err_msg(
+                "CUDA out of memory. Tried to allocate 2.00 GiB",
+            ))
+        } else {
+            xs[0].f_add(&xs[1])
+        }
+    };
+    let xs = Tensor::of_slice(&[1., 2., 3., 4., 5.]);
+    let ys = tch::oom_fallback(&[&xs, &xs], &mut f);
+    assert_eq!(Vec::<f64>::from(&ys), [2., 4., 6., 8., 10.]);
+    assert_eq!(ys.device(), xs.device());
+    assert_eq!(attempts, 2);
+    // Other errors are not retried.
+    let mut attempts = 0;
+    let res = tch::f_oom_fallback(&[&xs], |xs| {
+        attempts += 1;
+        xs[0].f_matmul(&xs[0].view(&[1, 5]))
+    });
+    assert!(res.is_err());
+    assert_eq!(attempts, 1);
+}
+
+#[test]
+fn peer_to_peer_copy() {
+    use tch::Cuda;
+    if Cuda::device_count() < 2 {
+        return;
+    }
+    let xs = Tensor::of_slice(&[3.0f32, 1., 4., 1., 5.]).to_device(Device::Cuda(0));
+    // The copy goes through the host when peer access is not available.
+    let ys = xs.to_device(Device::Cuda(1));
+    assert_eq!(ys.device(), Device::Cuda(1));
+    assert_eq!(Vec::<f32>::from(&ys), [3., 1., 4., 1., 5.]);
+    if Cuda::can_access_peer(1, 0) {
+        Cuda::set_peer_access(1, 0, true);
+        Cuda::set_peer_access(1, 0, true);
+        let ys = xs.to_device(Device::Cuda(1));
+        assert_eq!(Vec::<f32>::from(&ys), [3., 1., 4., 1., 5.]);
+        Cuda::set_peer_access(1, 0, false);
+    }
+}
@@ -0,0 +1,43 @@
+use tch::{Kind, Tensor};
+
+#[test]
+fn display() {
+    let xs = Tensor::of_slice(&[0.5f32, 1., 2., 3.25, 4., 5.]).view(&[2, 3]);
+    let s = format!("{}", xs);
+    assert_eq!(
+        s,
+        "Tensor[[2, 3], Float, Cpu]\n[[0.5000, 1.0000, 2.0000],\n [3.2500, 4.0000, 5.0000]]"
+    );
+    let s = format!("{}", Tensor::of_slice(&[3i64, -14, 15]));
+    assert_eq!(s, "Tensor[[3], Int64, Cpu]\n[  3, -14,  15]");
+    assert_eq!(
+        format!("{}", Tensor::from(42i64)),
+        "Tensor[[], Int64, Cpu]\n42"
+    );
+    // Integers above 2^53 are not exactly representable as f64.
+    assert_eq!(
+        format!("{}", Tensor::from(9_007_199_254_740_993i64)),
+        "Tensor[[], Int64, Cpu]\n9007199254740993"
+    );
+}
+
+#[test]
+fn display_summarized() {
+    let options = tch::get_print_options();
+    assert_eq!(options.edge_items, 3);
+    let xs = Tensor::arange(10_000, (Kind::Int64, tch::Device::Cpu)).view(&[100, 100]);
+    let s = format!("{}", xs);
+    assert!(s.starts_with("Tensor[[100, 100], Int64, Cpu]\n"), "{}", s);
+    assert!(
+        s.contains("[   0,    1,    2, ...,   97,   98,   99],"),
+        "{}",
+        s
+    );
+    assert!(
+        s.contains("[9900, 9901, 9902, ..., 9997, 9998, 9999]]"),
+        "{}",
+        s
+    );
+    assert!(!s.contains("500"), "{}", s);
+    assert_eq!(s.lines().count(), 8);
+}
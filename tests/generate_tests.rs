@@ -0,0 +1,38 @@
+use tch::{generate, Tensor};
+
+// Returns a step function that always favors the next token of `sequence`.
+fn sequence_step_fn(sequence: &'static [i64], vocab_size: i64) -> impl FnMut(&Tensor) -> Tensor {
+    move |tokens: &Tensor| {
+        let (beams, len) = tokens.size2().unwrap();
+        let next = sequence[std::cmp::min(len as usize, sequence.len() - 1)];
+        let mut logits = vec![0f32; (beams * vocab_size) as usize];
+        for b in 0..beams {
+            logits[(b * vocab_size + next) as usize] = 10.;
+        }
+        Tensor::of_slice(&logits).view(&[beams, vocab_size])
+    }
+}
+
+#[test]
+fn beam_search() {
+    let step_fn = sequence_step_fn(&[3, 1, 4, 0], 5);
+    let hypotheses = generate::beam_search(step_fn, 2, 10, 0, 1.);
+    assert_eq!(hypotheses.len(), 2);
+    assert_eq!(hypotheses[0], [3, 1, 4]);
+    // Without an end of sequence token, decoding stops at max_len.
+    let step_fn = sequence_step_fn(&[2, 2, 2], 5);
+    let hypotheses = generate::beam_search(step_fn, 3, 4, 0, 0.);
+    assert_eq!(hypotheses.len(), 3);
+    assert_eq!(hypotheses[0], [2, 2, 2, 2]);
+}
+
+#[test]
+fn beam_search_nan_scores() {
+    // NaN logits must not make the sorting of the hypotheses panic.
+    let step_fn = |tokens: &Tensor| {
+        let beams = tokens.size()[0];
+        Tensor::of_slice(&vec![std::f32::NAN; (beams * 5) as usize]).view(&[beams, 5])
+    };
+    let hypotheses = generate::beam_search(step_fn, 2, 3, 0, 1.);
+    assert_eq!(hypotheses.len(), 2);
+}
@@ -0,0 +1,39 @@
+use tch::gnn::{self, Aggregation};
+use tch::Tensor;
+
+#[test]
+fn message_passing() {
+    // Edges 0 -> 1, 2 -> 1, 1 -> 2, node 3 is isolated.
+    let edge_index = Tensor::of_slice(&[0i64, 2, 1, 1, 1, 2]).view(&[2, 3]);
+    let xs = Tensor::of_slice(&[1f64, 10., 2., 20., 3., 30., 4., 40.]).view(&[4, 2]);
+    let aggregate = |aggregation, self_loops| {
+        let mp = gnn::message_passing(aggregation, self_loops);
+        Vec::<Vec<f64>>::from(mp.aggregate(&edge_index, &xs))
+    };
+    assert_eq!(
+        aggregate(Aggregation::Sum, false),
+        [[0., 0.], [4., 40.], [2., 20.], [0., 0.]]
+    );
+    assert_eq!(
+        aggregate(Aggregation::Mean, false),
+        [[0., 0.], [2., 20.], [2., 20.], [0., 0.]]
+    );
+    assert_eq!(
+        aggregate(Aggregation::Max, true),
+        [[1., 10.], [3., 30.], [3., 30.], [4., 40.]]
+    );
+    assert_eq!(
+        aggregate(Aggregation::Mean, true),
+        [[1., 10.], [2., 20.], [2.5, 25.], [4., 40.]]
+    );
+
+    // The messages can depend on both endpoints and change the feature size.
+    let mp = gnn::message_passing(Aggregation::Sum, false);
+    let ys = mp.propagate(&edge_index, &xs, |xs_src, xs_dst| {
+        (xs_src - xs_dst).narrow(1, 0, 1)
+    });
+    assert_eq!(Vec::<Vec<f64>>::from(&ys), [[0.], [0.], [-1.], [0.]]);
+    assert!(mp
+        .f_propagate(&edge_index.get(0), &xs, |x, _| x.shallow_clone())
+        .is_err());
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "image")]
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat};
+use std::convert::TryFrom;
+use tch::{Kind, Tensor};
+
+#[test]
+fn image_round_trip() {
+    let pixels: Vec<u8> = (0..2 * 3 * 3).map(|v| (v * 15) as u8).collect();
+    let rgb = DynamicImage::ImageRgb8(ImageBuffer::from_raw(3, 2, pixels.clone()).unwrap());
+    let mut png = vec![];
+    rgb.write_to(&mut png, ImageFormat::PNG).unwrap();
+    let decoded = image::load_from_memory(&png).unwrap();
+
+    let xs = Tensor::from_image(&decoded);
+    assert_eq!(xs.size(), [3, 2, 3]);
+    assert_eq!(xs.kind(), Kind::Float);
+    // The red channel of the second pixel of the first row.
+    assert!((f64::try_from(xs.get(0).get(0).get(1)).unwrap() - 45. / 255.).abs() < 1e-6);
+    let image = xs.to_image().unwrap();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.raw_pixels(), pixels);
+
+    let gray = DynamicImage::ImageLuma8(ImageBuffer::from_raw(2, 1, vec![0u8, 255]).unwrap());
+    let xs = Tensor::from_image(&gray);
+    assert_eq!(xs.size(), [1, 1, 2]);
+    assert_eq!(Vec::<f64>::from(&xs.view(&[-1])), [0., 1.]);
+    assert_eq!(xs.to_image().unwrap().raw_pixels(), [0, 255]);
+    assert_eq!(
+        Tensor::from_image(&DynamicImage::ImageRgba8(rgb.to_rgba())).size(),
+        [4, 2, 3]
+    );
+    assert!(Tensor::zeros(&[5, 2, 2], tch::kind::FLOAT_CPU)
+        .to_image()
+        .is_err());
+}
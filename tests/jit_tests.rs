@@ -0,0 +1,19 @@
+use std::convert::TryFrom;
+use tch::{CModule, Tensor};
+
+#[test]
+fn freeze() {
+    let source = "def forward(x):\n    scale = 2.0 * 3.0\n    return torch.relu(x * scale + 1.0)\n";
+    let xs = Tensor::of_slice(&[-1f32, 0., 2.]);
+    let expected = CModule::compile(source).unwrap().forward(&[&xs]).unwrap();
+    assert_eq!(Vec::<f64>::from(&expected), [0., 1., 13.]);
+    let mut frozen = CModule::compile(source).unwrap();
+    frozen.freeze();
+    let ys = frozen.forward(&[&xs]).unwrap();
+    assert!(f64::try_from((&ys - &expected).abs().max()).unwrap() < 1e-6);
+    let mut optimized = CModule::compile(source).unwrap();
+    optimized.optimize_for_inference();
+    let ys = optimized.forward(&[&xs]).unwrap();
+    assert!(f64::try_from((&ys - &expected).abs().max()).unwrap() < 1e-6);
+    assert!(CModule::compile("def forward(x):\n    return x +\n").is_err());
+}
@@ -0,0 +1,24 @@
+use tch::{metrics, Tensor};
+
+#[test]
+fn accuracy_and_confusion_matrix() {
+    let logits = Tensor::of_slice(&[
+        0.9f32, 0.1, 0.0, // predicted 0
+        0.2, 0.3, 0.5, // predicted 2
+        0.1, 0.8, 0.1, // predicted 1
+        0.4, 0.1, 0.5, // predicted 2
+    ])
+    .view(&[4, 3]);
+    let targets = Tensor::of_slice(&[0i64, 1, 1, 2]);
+    assert_eq!(metrics::accuracy(&logits, &targets), 0.75);
+    assert_eq!(metrics::topk_accuracy(&logits, &targets, 2), 1.0);
+    let cm = metrics::confusion_matrix(&logits, &targets, 3);
+    assert_eq!(cm.size(), [3, 3]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&cm),
+        [[1, 0, 0], [0, 1, 1], [0, 0, 1]]
+    );
+    let preds = Tensor::of_slice(&[0i64, 2, 1, 2]);
+    let cm2 = metrics::confusion_matrix(&preds, &targets, 3);
+    assert_eq!(Vec::<i64>::from(&cm2), Vec::<i64>::from(&cm));
+}
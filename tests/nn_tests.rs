@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use tch::{nn, nn::OptimizerConfig, Device, Kind, Tensor};
 
 #[test]
@@ -18,15 +19,15 @@ fn save_and_load_var_store() {
         u1 += 42.0;
         v1 *= 2.0;
     });
-    assert_eq!(f64::from(&u1.mean()), 42.0);
-    assert_eq!(f64::from(&v1.mean()), 2.0);
-    assert_eq!(f64::from(&u2.mean()), 0.0);
-    assert_eq!(f64::from(&v2.mean()), 1.0);
+    assert_eq!(f64::try_from(&u1.mean()).unwrap(), 42.0);
+    assert_eq!(f64::try_from(&v1.mean()).unwrap(), 2.0);
+    assert_eq!(f64::try_from(&u2.mean()).unwrap(), 0.0);
+    assert_eq!(f64::try_from(&v2.mean()).unwrap(), 1.0);
     vs1.save(&filename).unwrap();
     vs2.load(&filename).unwrap();
-    assert_eq!(f64::from(&u1.mean()), 42.0);
-    assert_eq!(f64::from(&u2.mean()), 42.0);
-    assert_eq!(f64::from(&v2.mean()), 2.0);
+    assert_eq!(f64::try_from(&u1.mean()).unwrap(), 42.0);
+    assert_eq!(f64::try_from(&u2.mean()).unwrap(), 42.0);
+    assert_eq!(f64::try_from(&v2.mean()).unwrap(), 2.0);
 }
 
 #[test]
@@ -48,7 +49,7 @@ fn optimizer_test() {
     let opt = nn::Sgd::default().build(&vs, 1e-2).unwrap();
 
     let loss = xs.apply(&linear).mse_loss(&ys, 1);
-    let initial_loss = f64::from(&loss);
+    let initial_loss = f64::try_from(&loss).unwrap();
     assert!(initial_loss > 1.0, "initial loss {}", initial_loss);
 
     // Optimization loop.
@@ -57,7 +58,7 @@ fn optimizer_test() {
         opt.backward_step(&loss);
     }
     let loss = xs.apply(&linear).mse_loss(&ys, 1);
-    let final_loss = f64::from(loss);
+    let final_loss = f64::try_from(loss).unwrap();
     assert!(final_loss < 0.25, "final loss {}", final_loss);
 
     // Reset the weights to their initial values.
@@ -65,7 +66,7 @@ fn optimizer_test() {
         linear.ws.init(nn::Init::Const(0.));
         linear.bs.init(nn::Init::Const(0.));
     });
-    let initial_loss2 = f64::from(xs.apply(&linear).mse_loss(&ys, 1));
+    let initial_loss2 = f64::try_from(xs.apply(&linear).mse_loss(&ys, 1)).unwrap();
     assert_eq!(initial_loss, initial_loss2)
 }
 
@@ -83,14 +84,1069 @@ fn var_store_test() {
     let uniform = vs
         .root()
         .var("t5", &[100], nn::Init::Uniform { lo: 1.0, up: 2.0 });
-    let uniform_min = f64::from(&uniform.min());
-    let uniform_max = f64::from(&uniform.max());
+    let uniform_min = f64::try_from(&uniform.min()).unwrap();
+    let uniform_max = f64::try_from(&uniform.max()).unwrap();
     assert!(uniform_min >= 1., "min {}", uniform_min);
     assert!(uniform_max <= 2., "max {}", uniform_max);
-    let uniform_std = f64::from(&uniform.std(true));
+    let uniform_std = f64::try_from(&uniform.std(true)).unwrap();
     assert!(
         uniform_std > 0.15 && uniform_std < 0.35,
         "std {}",
         uniform_std
     );
 }
+
+#[test]
+fn var_store_set_kind() {
+    let mut vs = nn::VarStore::new(Device::Cpu);
+    let linear = nn::linear(vs.root(), 3, 2, Default::default());
+    assert_eq!(linear.ws.kind(), Kind::Float);
+    vs.bfloat16();
+    assert_eq!(linear.ws.kind(), Kind::BFloat16);
+    assert_eq!(linear.bs.kind(), Kind::BFloat16);
+    vs.float();
+    assert_eq!(linear.ws.kind(), Kind::Float);
+}
+
+#[test]
+fn lr_schedulers() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let _linear = nn::linear(vs.root(), 2, 1, Default::default());
+    let mut opt = nn::Sgd::default().build(&vs, 0.1).unwrap();
+
+    let mut sched = nn::polynomial_lr(0.1, 10, 2.0);
+    assert_eq!(sched.get_lr(), 0.1);
+    for _ in 0..5 {
+        sched.step(&mut opt);
+    }
+    assert!((sched.get_lr() - 0.025).abs() < 1e-8, "{}", sched.get_lr());
+    for _ in 0..10 {
+        sched.step(&mut opt);
+    }
+    assert!(sched.get_lr().abs() < 1e-8, "{}", sched.get_lr());
+
+    let sched = nn::polynomial_lr(0.1, 0, 2.0);
+    assert_eq!(sched.get_lr(), 0.);
+
+    let mut sched = nn::exponential_lr(0.1, 0.5);
+    for _ in 0..3 {
+        sched.step(&mut opt);
+    }
+    assert!((sched.get_lr() - 0.0125).abs() < 1e-8, "{}", sched.get_lr());
+}
+
+#[test]
+fn sparse_embedding() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let cfg = nn::EmbeddingConfig {
+        sparse: true,
+        ws_init: nn::Init::Const(1.),
+        ..Default::default()
+    };
+    let embedding = nn::embedding(vs.root(), 5, 2, cfg);
+    let opt = nn::Sgd::default().build(&vs, 0.5).unwrap();
+    let xs = Tensor::of_slice(&[1i64, 3, 3]);
+    let ys = xs.apply(&embedding);
+    assert_eq!(ys.size(), [3, 2]);
+    opt.zero_grad();
+    ys.sum().backward();
+    let grad = embedding.ws.grad();
+    assert!(grad.is_sparse());
+    assert_eq!(
+        Vec::<f64>::from(&grad.to_dense()),
+        [0., 0., 1., 1., 0., 0., 2., 2., 0., 0.]
+    );
+    opt.step();
+    assert_eq!(
+        Vec::<f64>::from(&embedding.ws),
+        [1., 1., 0.5, 0.5, 1., 1., 0., 0., 1., 1.]
+    );
+}
+
+#[test]
+fn randn_seeded() {
+    let vs1 = nn::VarStore::new(Device::Cpu);
+    let vs2 = nn::VarStore::new(Device::Cpu);
+    let t1 = vs1.root().randn_seeded("w", &[3, 4], 0., 0.02, 42);
+    let _other = vs2.root().randn_standard("other", &[10]);
+    let t2 = vs2.root().randn_seeded("w", &[3, 4], 0., 0.02, 42);
+    assert_eq!(t1.size(), [3, 4]);
+    assert_eq!(Vec::<f64>::from(&t1), Vec::<f64>::from(&t2));
+    let t3 = vs2.root().randn_seeded("w3", &[3, 4], 0., 0.02, 43);
+    assert_ne!(Vec::<f64>::from(&t1), Vec::<f64>::from(&t3));
+}
+
+#[test]
+fn grad_norm() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let xs = vs.root().zeros("xs", &[2]);
+    let ys = vs.root().zeros("ys", &[3]);
+    let opt = nn::Sgd::default().build(&vs, 1e-2).unwrap();
+    assert_eq!(opt.grad_norm(), 0.);
+    let loss = (&xs * Tensor::of_slice(&[3f32, 4.])).sum();
+    opt.zero_grad();
+    loss.backward();
+    assert!((opt.grad_norm() - 5.).abs() < 1e-6, "{}", opt.grad_norm());
+    // The gradients are left untouched.
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [3., 4.]);
+    let loss = (&ys * 2.).sum();
+    loss.backward();
+    let expected = (25f64 + 12.).sqrt();
+    assert!((opt.grad_norm() - expected).abs() < 1e-6);
+}
+
+#[test]
+fn focal_loss() {
+    let logits = Tensor::of_slice(&[4f32, 0., 0., 0.5, 0.3, 0.2]).view(&[2, 3]);
+    let targets = Tensor::of_slice(&[0i64, 2]);
+    let ce = nn::FocalLoss {
+        gamma: 0.,
+        ..Default::default()
+    };
+    let diff = ce.forward(&logits, &targets) - logits.cross_entropy_for_logits(&targets);
+    assert!(f64::try_from(diff.abs()).unwrap() < 1e-6);
+
+    // The first example is easy, the second one is hard.
+    let focal = nn::FocalLoss {
+        gamma: 2.,
+        reduction: tch::Reduction::None,
+        ..Default::default()
+    };
+    let ce = nn::FocalLoss {
+        gamma: 0.,
+        reduction: tch::Reduction::None,
+        ..Default::default()
+    };
+    let ratio = Vec::<f64>::from(focal.forward(&logits, &targets) / ce.forward(&logits, &targets));
+    assert!(ratio[0] < 0.01, "{:?}", ratio);
+    assert!(ratio[1] > 0.4, "{:?}", ratio);
+
+    // The binary version matches the binary cross-entropy when gamma is 0.
+    let logits = Tensor::of_slice(&[3f32, -2., 0.5, 100.]);
+    let targets = Tensor::of_slice(&[1f32, 0., 0., 1.]);
+    let sigmoid = logits.sigmoid();
+    let bce = -(&targets * sigmoid.log() + (1. - &targets) * (1. - &sigmoid).log());
+    let bce = bce.narrow(0, 0, 3);
+    let losses = ce.binary(&logits, &targets);
+    assert!(f64::try_from((losses.narrow(0, 0, 3) - bce).abs().max()).unwrap() < 1e-5);
+    assert!(f64::try_from(losses.get(3)).unwrap() < 1e-6);
+    let focal = nn::FocalLoss {
+        gamma: 2.,
+        alpha: Some(0.25),
+        reduction: tch::Reduction::Sum,
+    };
+    assert!(
+        f64::try_from(focal.binary(&logits, &targets)).unwrap()
+            < f64::try_from(ce.binary(&logits, &targets).sum()).unwrap()
+    );
+}
+
+#[test]
+fn var_shared() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-shared-{}", std::process::id()));
+    let build = |vs: &nn::Path| {
+        let ws = vs.randn_standard("embedding", &[5, 3]);
+        let tied = vs.var_shared("projection", &ws);
+        (ws, tied)
+    };
+    let vs = nn::VarStore::new(Device::Cpu);
+    let (ws, tied) = build(&vs.root());
+    assert_eq!(vs.trainable_variables().len(), 1);
+    let initial = Vec::<f64>::from(&ws);
+    let opt = nn::Sgd::default().build(&vs, 0.1).unwrap();
+    let xs = Tensor::of_slice(&[1i64, 3]);
+    let loss = ws.index_select(0, &xs).sum() + tied.sum();
+    opt.backward_step(&loss);
+    let updated = Vec::<f64>::from(&ws);
+    assert_ne!(initial, updated);
+    assert_eq!(updated, Vec::<f64>::from(&tied));
+
+    vs.save(&filename).unwrap();
+    assert_eq!(Tensor::load_multi(&filename).unwrap().len(), 1);
+    let mut vs2 = nn::VarStore::new(Device::Cpu);
+    let (ws2, tied2) = build(&vs2.root());
+    vs2.load(&filename).unwrap();
+    assert_eq!(Vec::<f64>::from(&ws2), updated);
+    assert_eq!(Vec::<f64>::from(&tied2), updated);
+}
+
+#[test]
+fn non_persistent_buffer() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-buffer-{}", std::process::id()));
+    let build = |vs: &nn::Path| {
+        let mask = Tensor::ones(&[3, 3], tch::kind::FLOAT_CPU).tril(0);
+        let ws = vs.randn_standard("ws", &[3]);
+        (ws, vs.buffer_non_persistent("mask", &mask))
+    };
+    let vs = nn::VarStore::new(Device::Cpu);
+    let (ws, mask) = build(&vs.root());
+    assert_eq!(vs.trainable_variables().len(), 1);
+    assert_eq!(vs.variables().len(), 2);
+    assert!(!mask.requires_grad());
+    vs.save(&filename).unwrap();
+    let saved = Tensor::load_multi(&filename).unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].0, "ws");
+
+    let mut vs2 = nn::VarStore::new(Device::Cpu);
+    let (ws2, mask2) = build(&vs2.root());
+    vs2.load(&filename).unwrap();
+    assert_eq!(Vec::<f64>::from(&ws2), Vec::<f64>::from(&ws));
+    assert_eq!(Vec::<f64>::from(&mask2), Vec::<f64>::from(&mask));
+}
+
+#[test]
+fn optimizer_add_parameters() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let xs = vs.root().ones("xs", &[3]);
+    let mut opt = nn::Adam::default().build(&vs, 0.1).unwrap();
+    for _ in 0..3 {
+        opt.backward_step(&xs.sum());
+    }
+    assert!(f64::try_from(xs.get(0)).unwrap() < 1.);
+
+    // Adds a parameter after the optimizer has been created.
+    let adapter = vs.root().ones("adapter", &[2]);
+    opt.add_parameters(&[adapter.shallow_clone()]);
+    opt.backward_step(&(xs.sum() + adapter.sum()));
+    assert!(f64::try_from(adapter.get(0)).unwrap() < 1.);
+    // The gradients of the new parameter also get reset by zero_grad.
+    opt.zero_grad();
+    assert_eq!(Vec::<f64>::from(&adapter.grad()), [0., 0.]);
+}
+
+#[test]
+fn load_with_mapping() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-mapping-{}", std::process::id()));
+    let vs1 = nn::VarStore::new(Device::Cpu);
+    let w1 = vs1.root().sub("backbone").randn_standard("w", &[2, 3]);
+    let _b1 = vs1.root().sub("backbone").sub("bn").ones("b", &[3]);
+    let _h1 = vs1.root().sub("head").zeros("w", &[4]);
+    vs1.save(&filename).unwrap();
+
+    let mut vs2 = nn::VarStore::new(Device::Cpu);
+    let w2 = vs2.root().sub("encoder").zeros("w", &[2, 3]);
+    let b2 = vs2.root().sub("encoder").sub("bn").zeros("b", &[3]);
+    let mapping = |name: &str| {
+        if name.starts_with("backbone|") {
+            Some(name.replacen("backbone|", "encoder|", 1))
+        } else {
+            None
+        }
+    };
+    vs2.load_with_mapping(&filename, &mapping).unwrap();
+    assert_eq!(Vec::<f64>::from(&w2), Vec::<f64>::from(&w1));
+    assert_eq!(Vec::<f64>::from(&b2), [1., 1., 1.]);
+
+    // Variables that are not covered by the mapping result in an error.
+    let _other = vs2.root().zeros("other", &[1]);
+    assert!(vs2.load_with_mapping(&filename, &mapping).is_err());
+}
+
+#[test]
+fn zero_grad_set_to_none() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let xs = vs.root().ones("xs", &[2]);
+    let opt = nn::Sgd::default().build(&vs, 0.1).unwrap();
+    (&xs * 3.).sum().backward();
+    opt.zero_grad_set_to_none(false);
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [0., 0.]);
+    (&xs * 3.).sum().backward();
+    opt.zero_grad_set_to_none(true);
+    assert!(!xs.grad().defined());
+    // The next backward pass allocates a new gradient.
+    (&xs * 2.).sum().backward();
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [2., 2.]);
+}
+
+#[test]
+fn conv1d() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = nn::ConvConfig {
+        stride: 2,
+        padding: nn::Padding::Explicit(1),
+        ..Default::default()
+    };
+    let conv = nn::conv1d(vs.root().sub("conv"), 4, 8, 3, config);
+    assert_eq!(conv.ws.size(), [8, 4, 3]);
+    let xs = Tensor::zeros(&[2, 4, 11], tch::kind::FLOAT_CPU);
+    // (11 + 2 * 1 - 3) / 2 + 1 = 6
+    assert_eq!(xs.apply(&conv).size(), [2, 8, 6]);
+
+    // A causal convolution using left padding only.
+    let config = nn::ConvConfig {
+        dilation: 2,
+        bias: false,
+        ws_init: nn::Init::Const(1.),
+        ..Default::default()
+    };
+    let causal = nn::conv1d(vs.root().sub("causal"), 1, 1, 3, config);
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4., 5.]).view(&[1, 1, 5]);
+    let ys = xs.constant_pad_nd(&[4, 0]).apply(&causal);
+    assert_eq!(ys.size(), [1, 1, 5]);
+    assert_eq!(Vec::<f64>::from(&ys), [1., 2., 4., 6., 9.]);
+}
+
+#[test]
+fn load_half_precision() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-half-{}", std::process::id()));
+    let mut vs1 = nn::VarStore::new(Device::Cpu);
+    let xs1 = vs1.root().randn_standard("xs", &[5]);
+    let values = Vec::<f64>::from(&xs1);
+    vs1.half();
+    assert_eq!(xs1.kind(), Kind::Half);
+    vs1.save(&filename).unwrap();
+
+    let mut vs2 = nn::VarStore::new(Device::Cpu);
+    let xs2 = vs2.root().zeros("xs", &[5]);
+    vs2.load(&filename).unwrap();
+    assert_eq!(xs2.kind(), Kind::Float);
+    for (v1, v2) in values.iter().zip(Vec::<f64>::from(&xs2).iter()) {
+        assert!((v1 - v2).abs() < 1e-2 * v1.abs().max(1.), "{} {}", v1, v2);
+    }
+
+    let mut vs3 = nn::VarStore::new(Device::Cpu);
+    let _xs3 = vs3.root().zeros("xs", &[4]);
+    let err = vs3.load(&filename).unwrap_err();
+    assert!(format!("{}", err).contains("shape"), "{}", err);
+}
+
+#[test]
+fn identity() {
+    let xs = Tensor::of_slice(&[1f32, 2., 3.]);
+    let mut ys = xs.apply(&nn::Identity);
+    assert_eq!(Vec::<f64>::from(&ys), [1., 2., 3.]);
+    // The output shares the input data.
+    let _ = ys.fill_(42.);
+    assert_eq!(Vec::<f64>::from(&xs), [42., 42., 42.]);
+    let seq = nn::seq().add(nn::Identity).add_fn(|xs| xs * 2.);
+    assert_eq!(Vec::<f64>::from(&xs.apply(&seq)), [84., 84., 84.]);
+}
+
+#[test]
+fn save_versioned() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-versioned-{}", std::process::id()));
+    let vs1 = nn::VarStore::new(Device::Cpu);
+    let _w1 = vs1.root().randn_standard("w", &[3, 2]);
+    let b1 = vs1.root().sub("a").randn_standard("b", &[4]);
+    vs1.save_versioned(&filename).unwrap();
+    let header = nn::VarStore::read_versioned_header(&filename).unwrap();
+    assert_eq!(header.tch_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(header.tensor_count, 2);
+
+    let mut vs2 = nn::VarStore::new(Device::Cpu);
+    let _w2 = vs2.root().zeros("w", &[3, 2]);
+    let b2 = vs2.root().sub("a").zeros("b", &[4]);
+    vs2.load_versioned(&filename).unwrap();
+    assert_eq!(Vec::<f64>::from(&b1), Vec::<f64>::from(&b2));
+
+    // Plain save files have no header.
+    vs1.save(&filename).unwrap();
+    assert!(vs2.load_versioned(&filename).is_err());
+    std::fs::write(&filename, "TCH-VARSTORE 1 0.0.1 2\nnot a torch archive").unwrap();
+    let err = vs2.load_versioned(&filename).unwrap_err();
+    assert!(
+        format!("{}", err).contains("saved with tch 0.0.1"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn register_hook() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let xs = vs.root().zeros("xs", &[2]);
+    let opt = nn::Sgd::default().build(&vs, 0.1).unwrap();
+    let handle = xs.register_hook(|grad| -grad);
+    // Without the hook, minimizing this loss would decrease xs.
+    opt.backward_step(&(&xs * Tensor::of_slice(&[1f32, 2.])).sum());
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [-1., -2.]);
+    assert_eq!(Vec::<f64>::from(&xs), [0.1, 0.2]);
+    handle.remove();
+    opt.backward_step(&(&xs * Tensor::of_slice(&[1f32, 2.])).sum());
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [1., 2.]);
+    assert!(Tensor::zeros(&[2], tch::kind::FLOAT_CPU)
+        .f_register_hook(|grad| grad.shallow_clone())
+        .is_err());
+}
+
+#[test]
+fn register_hook_panic() {
+    let xs = Tensor::ones(&[2], tch::kind::FLOAT_CPU).set_requires_grad(true);
+    let _handle = xs.register_hook(|_grad| panic!("hook failure"));
+    assert!((&xs * 2.).sum().f_backward().is_err());
+}
+
+#[test]
+fn gradient_reversal() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let xs = vs.root().ones("xs", &[2]);
+    let ys = xs.apply(&nn::gradient_reversal(0.5));
+    assert_eq!(Vec::<f64>::from(&ys), [1., 1.]);
+    // Only the gradient flowing through the layer is reversed.
+    ((&ys * 4.).sum() + (&xs * 3.).sum()).backward();
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [1., 1.]);
+}
+
+#[test]
+fn batch_norm_frozen_stats() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let mut bn = nn::batch_norm1d(vs.root(), 2, Default::default());
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4., 5., 6., 7., 9.]).view(&[2, 2, 2]);
+    let _ = xs.apply_t(&bn, true);
+    let running_mean = Vec::<f64>::from(&bn.running_mean);
+    assert_ne!(running_mean, [0., 0.]);
+    bn.frozen_stats = true;
+    let ys = xs.apply_t(&bn, true);
+    let _ = xs.apply_t(&bn, true);
+    assert_eq!(Vec::<f64>::from(&bn.running_mean), running_mean);
+    // The output matches evaluation mode and the affine parameters get gradients.
+    let eval_ys = xs.apply_t(&bn, false);
+    assert_eq!(
+        Vec::<f64>::from(&ys.view(&[-1])),
+        Vec::<f64>::from(&eval_ys.view(&[-1]))
+    );
+    ys.sum().backward();
+    assert_eq!(Vec::<f64>::from(&bn.bs.grad()), [4., 4.]);
+}
+
+#[test]
+fn batch_norm_eps_and_batch_stats() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = nn::BatchNormConfig {
+        eps: 1e-3,
+        track_running_stats: false,
+        ws_init: nn::Init::Const(1.),
+        ..Default::default()
+    };
+    let bn = nn::batch_norm1d(vs.root(), 2, config);
+    assert!(!bn.running_mean.defined());
+    assert!(vs.variables().get("running_mean").is_none());
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4., 5., 6., 7., 9.]).view(&[2, 2, 2]);
+    // The batch statistics are used in both modes.
+    let mean = xs.mean2(&[0, 2], true);
+    let var = (&xs - &mean).pow(2).mean2(&[0, 2], true);
+    let expected = (&xs - &mean) / (var + 1e-3).sqrt();
+    for &train in [true, false].iter() {
+        let ys = xs.apply_t(&bn, train);
+        let diff = f64::try_from((ys - &expected).abs().max()).unwrap();
+        assert!(diff < 1e-5, "train {} diff {}", train, diff);
+    }
+}
+
+#[test]
+fn linear_no_bias() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let ws_init = nn::Init::Randn {
+        mean: 0.,
+        stdev: 0.02,
+    };
+    let linear = nn::linear_no_bias(vs.root(), 200, 100, ws_init);
+    assert_eq!(vs.trainable_variables().len(), 1);
+    let std = f64::try_from(linear.ws.std(true)).unwrap();
+    assert!((std - 0.02).abs() < 1e-3, "std {}", std);
+    let xs = Tensor::ones(&[3, 200], tch::kind::FLOAT_CPU);
+    let ys = xs.apply(&linear);
+    assert_eq!(ys.size(), [3, 100]);
+    let expected = linear.ws.sum2(&[1], false);
+    assert!(f64::try_from((ys.get(0) - expected).abs().max()).unwrap() < 1e-5);
+}
+
+#[test]
+fn per_sample_grads() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let linear = nn::linear(vs.root(), 3, 2, Default::default());
+    let xs = Tensor::randn(&[5, 3], tch::kind::FLOAT_CPU);
+    let ys = Tensor::randn(&[5, 2], tch::kind::FLOAT_CPU);
+    let grads = nn::per_sample_grads(&vs, &[&xs, &ys], |batch| {
+        batch[0].apply(&linear).mse_loss(&batch[1], 1)
+    })
+    .unwrap();
+    assert_eq!(grads.len(), 2);
+    assert_eq!(grads["weight"].size(), [5, 2, 3]);
+    assert_eq!(grads["bias"].size(), [5, 2]);
+    // The per-sample gradients are not accumulated in the variables.
+    assert!(!linear.ws.grad().defined());
+
+    xs.apply(&linear).mse_loss(&ys, 1).backward();
+    for (name, var) in [("weight", &linear.ws), ("bias", &linear.bs)].iter() {
+        let mean = grads[*name].mean2(&[0], false);
+        let diff = f64::try_from((mean - var.grad()).abs().max()).unwrap();
+        assert!(diff < 1e-5, "{} {}", name, diff);
+    }
+    assert!(nn::per_sample_grads(&vs, &[&xs, &ys.narrow(0, 0, 4)], |b| b[0].sum()).is_err());
+}
+
+#[test]
+fn multihead_attention() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let mha = nn::multihead_attention(vs.root(), 8, 2, Default::default());
+    let variables = vs.variables();
+    assert_eq!(variables["in_proj_weight"].size(), [24, 8]);
+    assert_eq!(variables["in_proj_bias"].size(), [24]);
+    assert_eq!(variables["out_proj|weight"].size(), [8, 8]);
+    assert_eq!(variables.len(), 4);
+    let query = Tensor::randn(&[3, 2, 8], tch::kind::FLOAT_CPU);
+    let key = Tensor::randn(&[4, 2, 8], tch::kind::FLOAT_CPU);
+    let (ys, weights) = mha.forward(&query, &key, &key, None, None);
+    assert_eq!(ys.size(), [3, 2, 8]);
+    assert_eq!(weights.size(), [2, 3, 4]);
+    let sums = weights.sum2(&[-1], false);
+    assert!(f64::try_from((sums - 1.).abs().max()).unwrap() < 1e-5);
+
+    // The first query attends to no key, the last key is padding for the
+    // second batch element.
+    let attn_mask = Tensor::of_slice(&[1u8, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]).view(&[3, 4]);
+    let key_padding_mask = Tensor::of_slice(&[0u8, 0, 0, 0, 0, 0, 0, 1]).view(&[2, 4]);
+    let (_, weights) = mha.forward(
+        &query,
+        &key,
+        &key,
+        Some(&key_padding_mask),
+        Some(&attn_mask),
+    );
+    let uniform = (weights.get(0).get(0) - 0.25).abs().max();
+    assert!(f64::try_from(uniform).unwrap() < 1e-5);
+    let padded = weights.get(1).narrow(-1, 3, 1).abs().max();
+    assert!(f64::try_from(padded).unwrap() < 1e-6);
+}
+
+#[test]
+fn gradient_penalty() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let cfg = nn::LinearConfig {
+        ws_init: nn::Init::Const(1.),
+        ..Default::default()
+    };
+    let linear = nn::linear(vs.root(), 4, 1, cfg);
+    let real = Tensor::randn(&[8, 2, 2], tch::kind::FLOAT_CPU);
+    let fake = Tensor::randn(&[8, 2, 2], tch::kind::FLOAT_CPU);
+    let disc = |xs: &Tensor| xs.view(&[-1, 4]).apply(&linear);
+    let penalty = nn::gradient_penalty(&disc, &real, &fake, 10.);
+    // The gradient of a linear discriminator is its weight whose norm is 2.
+    assert!(
+        (f64::try_from(&penalty).unwrap() - 10.).abs() < 1e-4,
+        "{:?}",
+        penalty
+    );
+    // The penalty can be back-propagated to the discriminator weights,
+    // d/dw 10 * (|w| - 1)^2 = 20 * (|w| - 1) * w / |w|.
+    penalty.backward();
+    let grad = Vec::<f64>::from(&linear.ws.grad().view(&[-1]));
+    for g in grad.iter() {
+        assert!((g - 10.).abs() < 1e-4, "{:?}", grad);
+    }
+}
+
+#[test]
+fn flatten_and_adaptive_pooling() {
+    let xs = Tensor::arange(2 * 3 * 4 * 4, (Kind::Float, Device::Cpu)).view(&[2, 3, 4, 4]);
+    assert_eq!(xs.apply(&nn::Flatten::default()).size(), [2, 48]);
+    let flatten = nn::Flatten {
+        start_dim: 2,
+        end_dim: 3,
+    };
+    assert_eq!(xs.apply(&flatten).size(), [2, 3, 16]);
+    let ys = xs.apply(&nn::AdaptiveAvgPool2d([1, 1]));
+    assert_eq!(ys.size(), [2, 3, 1, 1]);
+    assert_eq!(f64::try_from(ys.get(0).get(0)).unwrap(), 7.5);
+    let ys = xs.apply(&nn::AdaptiveMaxPool2d([2, 1]));
+    assert_eq!(ys.size(), [2, 3, 2, 1]);
+    assert_eq!(Vec::<f64>::from(&ys.get(0).get(0).view(&[-1])), [7., 15.]);
+    let seq = nn::seq()
+        .add(nn::AdaptiveAvgPool2d([1, 1]))
+        .add(nn::Flatten::default());
+    assert_eq!(xs.apply(&seq).size(), [2, 3]);
+}
+
+#[test]
+fn load_with_permutations() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-perm-{}", std::process::id()));
+    // A dense layer weight stored as [in, out].
+    let weight = Tensor::of_slice(&[1f32, 2., 3., 4., 5., 6.]).view(&[3, 2]);
+    let bias = Tensor::of_slice(&[7f32, 8.]);
+    Tensor::save_multi(&[("weight", &weight), ("bias", &bias)], &filename).unwrap();
+    let mut vs = nn::VarStore::new(Device::Cpu);
+    let w = vs.root().zeros("weight", &[2, 3]);
+    let b = vs.root().zeros("bias", &[2]);
+    assert!(vs.load(&filename).is_err());
+    let mut permutations = std::collections::HashMap::new();
+    permutations.insert("weight".to_string(), vec![1, 0]);
+    vs.load_with_permutations(&filename, &permutations).unwrap();
+    assert_eq!(Vec::<Vec<f64>>::from(&w), [[1., 3., 5.], [2., 4., 6.]]);
+    assert_eq!(Vec::<f64>::from(&b), [7., 8.]);
+    permutations.insert("weight".to_string(), vec![0, 2]);
+    assert!(vs.load_with_permutations(&filename, &permutations).is_err());
+    permutations.insert("weight".to_string(), vec![1, 0, 2]);
+    assert!(vs.load_with_permutations(&filename, &permutations).is_err());
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn activation() {
+    use nn::Activation;
+    let xs = Tensor::of_slice(&[-1f64, 0., 2.]);
+    let apply = |activation: Activation| Vec::<f64>::from(&activation.apply(&xs));
+    let close = |lhs: Vec<f64>, rhs: &[f64]| {
+        lhs.iter()
+            .zip(rhs.iter())
+            .all(|(l, r)| (l - r).abs() < 1e-4)
+    };
+    assert_eq!(apply(Activation::Relu), [0., 0., 2.]);
+    assert_eq!(apply(Activation::LeakyRelu(0.1)), [-0.1, 0., 2.]);
+    assert!(close(apply(Activation::Gelu), &[-0.1587, 0., 1.9545]));
+    assert!(close(apply(Activation::GeluTanh), &[-0.1588, 0., 1.9546]));
+    assert!(close(apply(Activation::Silu), &[-0.2689, 0., 1.7616]));
+    assert!(close(apply(Activation::Mish), &[-0.3034, 0., 1.9440]));
+    assert!(close(apply(Activation::Tanh), &[-0.7616, 0., 0.9640]));
+    assert!(close(apply(Activation::Sigmoid), &[0.2689, 0.5, 0.8808]));
+    let seq = nn::seq().add(Activation::Relu).add_fn(|xs| xs * 2.);
+    assert_eq!(Vec::<f64>::from(&xs.apply(&seq)), [0., 0., 4.]);
+    let seq = nn::seq_t()
+        .add_fn(|xs| xs - 1.)
+        .add_activation(Activation::LeakyRelu(0.5));
+    assert_eq!(Vec::<f64>::from(&xs.apply_t(&seq, true)), [-1., -0.5, 1.]);
+}
+
+#[test]
+fn bilinear() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let layer = nn::bilinear(vs.root(), 3, 2, 4, Default::default());
+    assert_eq!(layer.ws.size(), [4, 3, 2]);
+    let xs1 = Tensor::randn(&[5, 3], (Kind::Float, Device::Cpu));
+    let xs2 = Tensor::randn(&[5, 2], (Kind::Float, Device::Cpu));
+    assert_eq!(layer.forward(&xs1, &xs2).size(), [5, 4]);
+    assert!(layer.f_forward(&xs1, &xs1).is_err());
+    assert!(layer.f_forward(&xs1, &xs2.narrow(0, 0, 4)).is_err());
+
+    let config = nn::BilinearConfig {
+        ws_init: Some(nn::Init::Const(0.)),
+        bs_init: Some(nn::Init::Const(0.5)),
+        bias: true,
+    };
+    let mut layer = nn::bilinear(&vs.root() / "tiny", 2, 2, 1, config);
+    tch::no_grad(|| {
+        layer
+            .ws
+            .copy_(&Tensor::of_slice(&[1f32, 2., 3., 4.]).view(&[1, 2, 2]))
+    });
+    let xs1 = Tensor::of_slice(&[1f32, -1.]).view(&[1, 2]);
+    let xs2 = Tensor::of_slice(&[2f32, 1.]).view(&[1, 2]);
+    // [1, -1] . [[1, 2], [3, 4]] . [2, 1] + 0.5 = [-2, -2] . [2, 1] + 0.5
+    assert_eq!(
+        Vec::<f64>::from(&layer.forward(&xs1, &xs2).view(&[-1])),
+        [-5.5]
+    );
+}
+
+#[test]
+fn hooked_seq() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    let mut seq = nn::hooked_seq()
+        .add_fn(|xs| xs + 1.)
+        .add_named("double", nn::func(|xs| xs * 2.))
+        .add_fn(|xs| xs - 3.);
+    let captured: Rc<RefCell<Vec<Tensor>>> = Rc::new(RefCell::new(vec![]));
+    let captured_ = captured.clone();
+    seq.register_forward_hook("double", move |xs| {
+        captured_.borrow_mut().push(xs.shallow_clone())
+    })
+    .unwrap();
+    let first: Rc<RefCell<Option<Tensor>>> = Rc::new(RefCell::new(None));
+    let first_ = first.clone();
+    seq.register_forward_hook("0", move |xs| *first_.borrow_mut() = Some(xs.copy()))
+        .unwrap();
+    assert!(seq.register_forward_hook("3", |_| ()).is_err());
+    let xs = Tensor::of_slice(&[1f32, 2.]);
+    let ys = xs.apply_t(&seq, false);
+    assert_eq!(Vec::<f64>::from(&ys), [1., 3.]);
+    assert_eq!(captured.borrow().len(), 1);
+    assert_eq!(Vec::<f64>::from(&captured.borrow()[0]), [4., 6.]);
+    assert_eq!(Vec::<f64>::from(first.borrow().as_ref().unwrap()), [2., 3.]);
+    seq.clear_forward_hooks();
+    let _ys = xs.apply_t(&seq, false);
+    assert_eq!(captured.borrow().len(), 1);
+}
+
+#[test]
+fn conv_padding_modes() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let same = nn::ConvConfig {
+        padding: nn::Padding::Same,
+        ..Default::default()
+    };
+    let xs = Tensor::zeros(&[2, 3, 7, 8], tch::kind::FLOAT_CPU);
+    let conv = nn::conv2d(&vs.root() / "c3", 3, 4, 3, same);
+    assert_eq!(xs.apply(&conv).size(), [2, 4, 7, 8]);
+    // An even kernel size requires more padding on the right.
+    let conv = nn::conv2d(&vs.root() / "c4", 3, 4, 4, same);
+    assert_eq!(xs.apply(&conv).size(), [2, 4, 7, 8]);
+    let config = nn::ConvConfig {
+        dilation: 2,
+        bias: false,
+        ws_init: nn::Init::Const(1.),
+        ..same
+    };
+    let conv = nn::conv1d(&vs.root() / "c1", 1, 1, 2, config);
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4.]).view(&[1, 1, 4]);
+    // The output i sums the inputs i - 1 and i + 1.
+    assert_eq!(Vec::<f64>::from(&xs.apply(&conv)), [2., 4., 6., 3.]);
+    let valid = nn::ConvConfig {
+        padding: nn::Padding::Valid,
+        ..Default::default()
+    };
+    let conv = nn::conv1d(&vs.root() / "valid", 1, 1, 3, valid);
+    assert_eq!(xs.apply(&conv).size(), [1, 1, 2]);
+}
+
+#[test]
+fn conv_same_padding_with_stride() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = nn::ConvConfig {
+        stride: 2,
+        padding: nn::Padding::Same,
+        ..Default::default()
+    };
+    assert!(nn::f_conv2d(vs.root(), 3, 4, 3, config).is_err());
+}
+
+#[test]
+#[should_panic(expected = "same padding requires a stride of 1")]
+fn conv_same_padding_with_stride_panics() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = nn::ConvConfig {
+        stride: 2,
+        padding: nn::Padding::Same,
+        ..Default::default()
+    };
+    let _conv = nn::conv2d(vs.root(), 3, 4, 3, config);
+}
+
+#[test]
+fn var_store_flatten() {
+    let mut vs = nn::VarStore::new(Device::Cpu);
+    let linear = nn::linear(&vs.root() / "fc", 3, 2, Default::default());
+    let _stats = vs.root().zeros_no_train("stats", &[5]);
+    let flat = vs.flatten();
+    assert_eq!(flat.size(), [3 * 2 + 2]);
+    // The bias comes first as variables are sorted by name.
+    let bias = Vec::<f64>::from(&linear.bs);
+    assert_eq!(Vec::<f64>::from(&flat.narrow(0, 0, 2)), bias);
+    assert_eq!(
+        Vec::<f64>::from(&vs.flatten()),
+        Vec::<f64>::from(&flat),
+        "flatten is deterministic"
+    );
+    vs.unflatten(&flat.zeros_like());
+    assert_eq!(f64::try_from(linear.ws.abs().sum()).unwrap(), 0.);
+    assert_eq!(f64::try_from(linear.bs.abs().sum()).unwrap(), 0.);
+    vs.unflatten(&flat);
+    assert_eq!(Vec::<f64>::from(&linear.bs), bias);
+    assert!(vs.f_unflatten(&flat.narrow(0, 0, 7)).is_err());
+}
+
+#[test]
+fn grad_scaler_accumulation() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let ws = vs.root().ones("ws", &[2]);
+    let opt = nn::Sgd::default().build(&vs, 0.1).unwrap();
+    let mut scaler = nn::grad_scaler(1024.);
+    scaler.growth_interval = 1;
+    let xs = Tensor::of_slice(&[1f32, 2.]);
+    // Two micro-batches, the inf only appears in the second one.
+    opt.zero_grad();
+    scaler.scale(&(&ws * &xs).sum()).backward();
+    scaler.scale(&(&ws * &xs).sum()).backward();
+    let _t = ws.grad().get(0).fill_(std::f64::INFINITY);
+    assert!(!scaler.step(&opt));
+    scaler.update();
+    assert_eq!(Vec::<f64>::from(&ws), [1., 1.]);
+    assert_eq!(scaler.get_scale(), 512.);
+    // The gradients are unscaled once for the whole effective batch.
+    opt.zero_grad();
+    scaler.scale(&(&ws * &xs).sum()).backward();
+    scaler.scale(&(&ws * &xs).sum()).backward();
+    assert!(!scaler.unscale_(&opt));
+    assert_eq!(Vec::<f64>::from(&ws.grad()), [2., 4.]);
+    scaler.unscale_(&opt);
+    assert_eq!(Vec::<f64>::from(&ws.grad()), [2., 4.]);
+    assert!(scaler.step(&opt));
+    scaler.update();
+    let ws = Vec::<f64>::from(&ws);
+    assert!((ws[0] - 0.8).abs() < 1e-6 && (ws[1] - 0.6).abs() < 1e-6);
+    assert_eq!(scaler.get_scale(), 1024.);
+}
+
+#[test]
+fn layer_scale() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let layer = nn::layer_scale(vs.root(), 3, 1e-6);
+    assert_eq!(vs.trainable_variables().len(), 1);
+    assert!(layer.gamma.requires_grad());
+    assert_eq!(Vec::<f32>::from(&layer.gamma), [1e-6, 1e-6, 1e-6]);
+    let xs = Tensor::ones(&[2, 4, 3], tch::kind::FLOAT_CPU);
+    let ys = xs.apply(&layer);
+    assert_eq!(ys.size(), [2, 4, 3]);
+    ys.sum().backward();
+    assert_eq!(Vec::<f64>::from(&layer.gamma.grad()), [8., 8., 8.]);
+}
+
+#[test]
+fn checkpoint_manager() {
+    let dir = std::env::temp_dir().join(format!("tch-ckpt-{}", std::process::id()));
+    let mut vs = nn::VarStore::new(Device::Cpu);
+    let mut xs = vs.root().zeros("xs", &[1]);
+    let mut manager = nn::CheckpointManager::new(&dir, nn::MetricMode::Min, 2).unwrap();
+    for (step, &loss) in [3., 1., 4., 0.5, 2.].iter().enumerate() {
+        tch::no_grad(|| xs.copy_(&Tensor::from(loss as f32).view(&[1])));
+        manager.maybe_save(&vs, step as i64, loss).unwrap();
+    }
+    assert_eq!(manager.best(), Some((3, 0.5)));
+    assert_eq!(
+        manager.paths(),
+        [dir.join("checkpoint-3.ot"), dir.join("checkpoint-1.ot")]
+    );
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["checkpoint-1.ot", "checkpoint-3.ot"]);
+    assert!(manager.maybe_save(&vs, 5, 7.).unwrap().is_none());
+    manager.load_best(&mut vs).unwrap();
+    assert_eq!(Vec::<f64>::from(&xs), [0.5]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checkpoint_manager_same_step() {
+    let dir = std::env::temp_dir().join(format!("tch-ckpt-step-{}", std::process::id()));
+    let vs = nn::VarStore::new(Device::Cpu);
+    let _xs = vs.root().zeros("xs", &[1]);
+    let mut manager = nn::CheckpointManager::new(&dir, nn::MetricMode::Min, 2).unwrap();
+    manager.maybe_save(&vs, 1, 1.).unwrap();
+    manager.maybe_save(&vs, 2, 2.).unwrap();
+    // A better metric moves the step to the front.
+    assert!(manager.maybe_save(&vs, 2, 0.5).unwrap().is_some());
+    assert_eq!(manager.best(), Some((2, 0.5)));
+    assert_eq!(manager.paths().len(), 2);
+    // A worse metric for the same step is still kept as there is some room
+    // once the previous entry for this step is discarded.
+    assert!(manager.maybe_save(&vs, 2, 2.5).unwrap().is_some());
+    assert_eq!(manager.best(), Some((1, 1.)));
+    assert_eq!(
+        manager.paths(),
+        [dir.join("checkpoint-1.ot"), dir.join("checkpoint-2.ot")]
+    );
+    assert!(manager.maybe_save(&vs, 3, 3.).unwrap().is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn positional_encoding() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let config = nn::PositionalEncodingConfig::Sinusoidal {
+        max_len: 10,
+        d_model: 5,
+    };
+    let pe = nn::positional_encoding(&vs.root() / "sin", config);
+    assert!(vs.trainable_variables().is_empty());
+    // At position 0, the sines are 0 and the cosines are 1.
+    assert_eq!(Vec::<f64>::from(&pe.encoding.get(0)), [0., 1., 0., 1., 0.]);
+    let expected = [
+        1f64.sin(),
+        1f64.cos(),
+        (1. / 10000f64.powf(2. / 5.)).sin(),
+        (1. / 10000f64.powf(2. / 5.)).cos(),
+        (1. / 10000f64.powf(4. / 5.)).sin(),
+    ];
+    for (v, e) in Vec::<f64>::from(&pe.encoding.get(1))
+        .iter()
+        .zip(expected.iter())
+    {
+        assert!((v - e).abs() < 1e-6, "{} {}", v, e)
+    }
+    let xs = Tensor::zeros(&[2, 3, 5], tch::kind::FLOAT_CPU);
+    let ys = xs.apply(&pe);
+    assert_eq!(ys.size(), [2, 3, 5]);
+    assert_eq!(
+        Vec::<f64>::from(&ys.get(1).view(&[-1])),
+        Vec::<f64>::from(&pe.encoding.narrow(0, 0, 3).view(&[-1]))
+    );
+
+    let config = nn::PositionalEncodingConfig::Learned {
+        max_len: 10,
+        d_model: 5,
+    };
+    let pe = nn::positional_encoding(&vs.root() / "learned", config);
+    assert_eq!(vs.trainable_variables().len(), 1);
+    xs.apply(&pe).sum().backward();
+    let grad = pe.encoding.grad();
+    assert_eq!(
+        f64::try_from(grad.narrow(0, 0, 3).sum()).unwrap(),
+        2. * 3. * 5.
+    );
+    assert_eq!(f64::try_from(grad.narrow(0, 3, 7).sum()).unwrap(), 0.);
+}
+
+#[test]
+fn gelu_approximations() {
+    let xs = Tensor::of_slice(&[-3f64, 1., 3.]);
+    let exact = Vec::<f64>::from(&xs.apply(&nn::Activation::Gelu));
+    let approx = Vec::<f64>::from(&xs.apply(&nn::Activation::GeluTanh));
+    let expected_exact = [-0.004_050, 0.841_345, 2.995_950];
+    let expected_approx = [-0.003_637, 0.841_192, 2.996_363];
+    for i in 0..3 {
+        assert!((exact[i] - expected_exact[i]).abs() < 1e-5, "{:?}", exact);
+        assert!(
+            (approx[i] - expected_approx[i]).abs() < 1e-5,
+            "{:?}",
+            approx
+        );
+        assert!((exact[i] - approx[i]).abs() > 1e-4);
+    }
+    assert_eq!(Vec::<f64>::from(&xs.gelu("none")), exact);
+    assert_eq!(Vec::<f64>::from(&xs.gelu("tanh")), approx);
+    assert!(xs.f_gelu("erf").is_err());
+}
+
+#[test]
+fn dropout_with_mask() {
+    use nn::ModuleT;
+    let mut dropout = nn::dropout(0.5);
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4.]);
+    let mask = Tensor::of_slice(&[1u8, 0, 0, 1]);
+    let ys = dropout.forward_with_mask(&xs, &mask);
+    assert_eq!(Vec::<f64>::from(&ys), [2., 0., 0., 8.]);
+    assert_eq!(
+        Vec::<f64>::from(&xs.apply_t(&dropout, false)),
+        [1., 2., 3., 4.]
+    );
+    assert!(dropout.last_mask().is_none());
+
+    dropout.record_mask = true;
+    let xs = Tensor::ones(&[100], tch::kind::FLOAT_CPU);
+    let ys = dropout.forward_t(&xs, true);
+    let mask = dropout.last_mask().unwrap();
+    assert_eq!(mask.size(), [100]);
+    assert_eq!(
+        Vec::<f64>::from(&ys),
+        Vec::<f64>::from(&dropout.forward_with_mask(&xs, &mask))
+    );
+}
+
+#[test]
+fn drop_path() {
+    use nn::ModuleT;
+    let drop_path = nn::drop_path(0.25);
+    let xs = Tensor::ones(&[2000, 3, 2], tch::kind::FLOAT_CPU);
+    let ys = drop_path.forward_t(&xs, false);
+    assert_eq!(Vec::<f64>::from(&ys), Vec::<f64>::from(&xs));
+    let ys = drop_path.forward_t(&xs, true).view(&[2000, 6]);
+    let mut dropped = 0;
+    for sample in Vec::<Vec<f64>>::from(&ys).iter() {
+        if sample[0] == 0. {
+            dropped += 1;
+        } else {
+            assert!((sample[0] - 1. / 0.75).abs() < 1e-5, "{:?}", sample);
+        }
+        assert!(sample.iter().all(|&v| v == sample[0]), "{:?}", sample);
+    }
+    let rate = f64::from(dropped) / 2000.;
+    assert!((rate - 0.25).abs() < 0.05, "{}", rate);
+    let ys = nn::drop_path(1.).forward_t(&xs, true);
+    assert_eq!(f64::try_from(ys.abs().sum()).unwrap(), 0.);
+}
+
+#[test]
+fn lr_finder() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let ws = vs.root().zeros("ws", &[1]);
+    let mut opt = nn::Sgd::default().build(&vs, 1.).unwrap();
+    let xs = Tensor::ones(&[4, 1], tch::kind::FLOAT_CPU);
+    let ys = &xs * 2.;
+    let batches = (0..).map(|_| (xs.shallow_clone(), ys.shallow_clone()));
+    let loss_fn = |xs: &Tensor, ys: &Tensor| (xs * &ws - ys).pow(2).mean();
+    let history = nn::lr_finder(&vs, &mut opt, batches, loss_fn, 1e-4, 10., 100);
+    // The learning rate increases geometrically and the sweep stops when the
+    // loss explodes for learning rates above 1.
+    assert!(history.len() < 100);
+    assert!((history[0].0 - 1e-4).abs() < 1e-12);
+    assert!(history.windows(2).all(|w| w[0].0 < w[1].0));
+    let (best, _) = history
+        .iter()
+        .enumerate()
+        .min_by(|(_, (_, l1)), (_, (_, l2))| l1.partial_cmp(l2).unwrap())
+        .unwrap();
+    assert!(0 < best && best < history.len() - 1);
+    assert!(history[best].1 < history[0].1);
+    assert_eq!(Vec::<f64>::from(&ws), [0.]);
+}
+
+#[test]
+fn optimizer_reattach() {
+    let filename = std::env::temp_dir().join(format!("tch-reattach-{}", std::process::id()));
+    let vs = nn::VarStore::new(Device::Cpu);
+    let _ws = vs.root().ones("ws", &[2]);
+    vs.save(&filename).unwrap();
+    let mut opt = nn::Sgd::default().build(&vs, 0.5).unwrap();
+    opt.set_lr(0.1);
+
+    // A new var-store is created and loaded, e.g. when resuming training.
+    let mut vs = nn::VarStore::new(Device::Cpu);
+    let ws = vs.root().zeros("ws", &[2]);
+    vs.load(&filename).unwrap();
+    opt.reattach(&vs);
+    opt.zero_grad();
+    (&ws * Tensor::of_slice(&[1f32, 2.])).sum().backward();
+    opt.step();
+    let ws = Vec::<f64>::from(&ws);
+    assert!((ws[0] - 0.9).abs() < 1e-6 && (ws[1] - 0.8).abs() < 1e-6);
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn softmax_modules() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let seq = nn::seq()
+        .add(nn::linear(vs.root(), 4, 3, Default::default()))
+        .add(nn::Softmax(-1));
+    let xs = Tensor::randn(&[5, 4], tch::kind::FLOAT_CPU);
+    let ys = xs.apply(&seq);
+    assert_eq!(ys.size(), [5, 3]);
+    for sum in Vec::<f64>::from(&ys.sum2(&[1], false)).iter() {
+        assert!((sum - 1.).abs() < 1e-5, "{}", sum);
+    }
+    let log_ys = xs.apply(&nn::LogSoftmax(1));
+    let diff = (log_ys.exp() - xs.apply(&nn::Softmax(1))).abs().max();
+    assert!(f64::try_from(diff).unwrap() < 1e-6);
+}
+
+#[test]
+fn clip_grad_utils() {
+    let xs = Tensor::of_slice(&[3f64, 4.]).set_requires_grad(true);
+    let ys = Tensor::of_slice(&[12f64]).set_requires_grad(true);
+    let unused = Tensor::of_slice(&[1f64]).set_requires_grad(true);
+    let tensors = [xs.shallow_clone(), ys.shallow_clone(), unused];
+    let loss = (&xs * &xs).sum() / 2. + (&ys * &ys).sum() / 2.;
+    loss.backward();
+    // The gradients are [3, 4] and [12] so their global L2 norm is 13.
+    let norm = nn::utils::clip_grad_norm(&tensors, 1.3, 2.);
+    assert!((norm - 13.).abs() < 1e-9);
+    let grads: Vec<f64> = [xs.grad(), ys.grad()]
+        .iter()
+        .flat_map(Vec::<f64>::from)
+        .collect();
+    let clipped = grads.iter().map(|g| g * g).sum::<f64>().sqrt();
+    assert!((clipped - 1.3).abs() < 1e-5, "{:?}", grads);
+    let norm = nn::utils::clip_grad_norm(&tensors, 10., std::f64::INFINITY);
+    assert!((norm - 1.2).abs() < 1e-5);
+    assert!((f64::try_from(ys.grad()).unwrap() - 1.2).abs() < 1e-5);
+
+    nn::utils::clip_grad_value(&tensors, 0.35);
+    let grads = Vec::<f64>::from(&xs.grad());
+    assert!((grads[0] - 0.3).abs() < 1e-5, "{:?}", grads);
+    assert_eq!(grads[1], 0.35);
+    assert_eq!(f64::try_from(ys.grad()).unwrap(), 0.35);
+}
@@ -0,0 +1,95 @@
+use tch::{kind, profiler, Tensor};
+
+#[test]
+fn profile_matmul() {
+    let xs = Tensor::randn(&[64, 32], kind::FLOAT_CPU);
+    let ys = Tensor::randn(&[32, 16], kind::FLOAT_CPU);
+    let (zs, result) = profiler::profile(false, || xs.matmul(&ys));
+    assert_eq!(zs.size(), [64, 16]);
+    let stats = result.op_stats();
+    assert!(
+        stats
+            .iter()
+            .any(|s| s.name.contains("matmul") && s.count >= 1),
+        "{:?}",
+        stats
+    );
+    // Operations run outside of the closure are not recorded.
+    let (_, result) = profiler::profile(false, || ());
+    assert!(result.events().is_empty());
+    let _zs = xs.matmul(&ys);
+
+    let filename = std::env::temp_dir().join(format!("tch-trace-{}.json", std::process::id()));
+    let (_, result) = profiler::profile(false, || xs.matmul(&ys));
+    result.export_chrome_trace(&filename).unwrap();
+    let trace = std::fs::read_to_string(&filename).unwrap();
+    assert!(trace.contains("matmul"));
+}
+
+#[test]
+fn count_flops() {
+    let vs = tch::nn::VarStore::new(tch::Device::Cpu);
+    let linear = tch::nn::linear(vs.root(), 32, 16, Default::default());
+    let xs = Tensor::randn(&[1, 32], kind::FLOAT_CPU);
+    assert_eq!(profiler::count_flops(&linear, &xs), 32 * 16);
+    let xs = Tensor::randn(&[8, 32], kind::FLOAT_CPU);
+    assert_eq!(profiler::count_flops(&linear, &xs), 8 * 32 * 16);
+
+    let conv = tch::nn::conv2d(&vs.root() / "conv", 3, 4, 3, Default::default());
+    let seq = tch::nn::seq()
+        .add(conv)
+        .add_fn(|xs| xs.flatten(1, -1))
+        .add(tch::nn::linear(
+            &vs.root() / "fc",
+            4 * 6 * 6,
+            10,
+            Default::default(),
+        ));
+    let xs = Tensor::randn(&[2, 3, 8, 8], kind::FLOAT_CPU);
+    let conv_macs = 2 * 4 * 6 * 6 * (3 * 3 * 3);
+    let linear_macs = 2 * 4 * 6 * 6 * 10;
+    assert_eq!(profiler::count_flops(&seq, &xs), conv_macs + linear_macs);
+    // Nothing is counted outside of count_flops.
+    assert!(!profiler::is_counting_flops());
+
+    let ws = Tensor::randn(&[5, 4, 6], kind::FLOAT_CPU);
+    let bmm = tch::nn::func(move |xs| {
+        profiler::add_matmul_flops(xs, &ws);
+        xs.matmul(&ws)
+    });
+    let xs = Tensor::randn(&[3, 1, 2, 4], kind::FLOAT_CPU);
+    assert_eq!(profiler::count_flops(&bmm, &xs), 3 * 5 * 2 * 4 * 6);
+    let xs = Tensor::randn(&[4], kind::FLOAT_CPU);
+    assert_eq!(profiler::count_flops(&bmm, &xs), 5 * 4 * 6);
+}
+
+#[test]
+fn profile_memory() {
+    let xs = Tensor::ones(&[256], kind::FLOAT_CPU);
+    let (sum, profile) = profiler::profile_memory(|| {
+        let ys = Tensor::ones(&[1024, 2], kind::DOUBLE_CPU);
+        let zs = &xs * 2.;
+        (ys.sum() + zs.sum()).double_value(&[])
+    })
+    .unwrap();
+    assert_eq!(sum, 2048. + 512.);
+    let events = profile.events();
+    assert!(
+        events.iter().any(|e| e.bytes == 1024 * 2 * 8),
+        "{:?}",
+        events
+    );
+    assert!(
+        events.iter().any(|e| e.bytes == -1024 * 2 * 8),
+        "{:?}",
+        events
+    );
+    assert!(profile.peak_bytes() >= 1024 * 2 * 8 + 256 * 4);
+    assert!(profile.average_bytes() <= profile.peak_bytes() as f64);
+
+    let filename = std::env::temp_dir().join(format!("tch-memory-{}.json", std::process::id()));
+    profile.export_chrome_trace(&filename).unwrap();
+    let trace = std::fs::read_to_string(&filename).unwrap();
+    assert!(trace.contains("CPU Memory"));
+    std::fs::remove_file(&filename).unwrap();
+}
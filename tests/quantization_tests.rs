@@ -0,0 +1,67 @@
+use std::convert::TryFrom;
+use tch::{quantization, Kind, Tensor};
+
+#[test]
+fn kl_calibrate() {
+    tch::manual_seed(42);
+    let xs = Tensor::randn(&[100_000], tch::kind::FLOAT_CPU);
+    let max_abs = f64::try_from(xs.abs().max()).unwrap();
+    let (scale, zero_point) = quantization::kl_calibrate(&[xs.shallow_clone()], 512);
+    assert_eq!(zero_point, 0);
+    let threshold = scale * 127.;
+    assert!(
+        threshold > 2.5 && threshold < max_abs,
+        "{} {}",
+        threshold,
+        max_abs
+    );
+
+    // A single outlier should not extend the quantization range.
+    let outlier = Tensor::of_slice(&[50f32]);
+    let (scale, _) = quantization::kl_calibrate(&[xs, outlier.shallow_clone()], 512);
+    assert!(scale * 127. < 15., "{}", scale * 127.);
+
+    // Fewer bins than int8 levels cannot be calibrated.
+    assert!(quantization::f_kl_calibrate(&[outlier], 64).is_err());
+}
+
+#[test]
+fn quantize_per_channel() {
+    let xs = Tensor::of_slice(&[0.5f32, -1.0, 0.25, 40.0, -20.0, 10.0]).view(&[2, 3]);
+    let scales = [0.01, 0.5];
+    let qs = xs.quantize_per_channel(&scales, &[0, 10], 0, Kind::Int8);
+    assert_eq!(qs.int_repr().kind(), Kind::Int8);
+    assert_eq!(
+        Vec::<i64>::from(&qs.int_repr().view(&[-1])),
+        [50, -100, 25, 90, -30, 30]
+    );
+    let ys = qs.dequantize();
+    assert_eq!(ys.kind(), Kind::Float);
+    assert!(f64::try_from((&ys - &xs).abs().max()).unwrap() < 1e-5);
+
+    let ws = Tensor::randn(&[4, 2, 3, 3], tch::kind::FLOAT_CPU)
+        * Tensor::of_slice(&[0.1f32, 1., 10., 100.]).view(&[4, 1, 1, 1]);
+    let scales: Vec<f64> = (0..4)
+        .map(|c| f64::try_from(ws.get(c).abs().max()).unwrap() / 127.)
+        .collect();
+    let qs = ws.quantize_per_channel(&scales, &[0; 4], 0, Kind::Int8);
+    let err = (qs.dequantize() - &ws).abs();
+    for c in 0..4 {
+        assert!(f64::try_from(err.get(c).max()).unwrap() <= scales[c as usize] * 0.501);
+    }
+    assert!(xs
+        .f_quantize_per_channel(&scales, &[0], 0, Kind::Int8)
+        .is_err());
+    assert!(xs
+        .f_quantize_per_channel(&scales, &[0, 0], 1, Kind::Int8)
+        .is_err());
+    assert!(xs
+        .f_quantize_per_channel(&scales, &[0, 0], 0, Kind::Float)
+        .is_err());
+    assert!(xs
+        .f_quantize_per_channel(&[0.1, f64::NAN], &[0, 0], 0, Kind::Int8)
+        .is_err());
+    assert!(xs
+        .f_quantize_per_channel(&[0.1, -0.1], &[0, 0], 0, Kind::Int8)
+        .is_err());
+}
@@ -0,0 +1,39 @@
+// The tests in this file use the global random number generator so they are
+// kept apart from the other tests which may run in parallel.
+use tch::{Kind, Tensor};
+
+#[test]
+fn rng_state() {
+    tch::manual_seed(42);
+    let _xs = Tensor::rand(&[3], tch::kind::FLOAT_CPU);
+    let state = tch::get_rng_state();
+    assert_eq!(state.kind(), Kind::Uint8);
+    let xs = Vec::<f64>::from(Tensor::randn(&[10], tch::kind::FLOAT_CPU));
+    let perm = Vec::<i64>::from(Tensor::randperm(10, tch::kind::INT64_CPU));
+    tch::set_rng_state(&state).unwrap();
+    let ys = Vec::<f64>::from(Tensor::randn(&[10], tch::kind::FLOAT_CPU));
+    let perm2 = Vec::<i64>::from(Tensor::randperm(10, tch::kind::INT64_CPU));
+    assert_eq!(xs, ys);
+    assert_eq!(perm, perm2);
+    assert!(tch::set_rng_state(&Tensor::zeros(&[3], tch::kind::FLOAT_CPU)).is_err());
+}
+
+#[test]
+fn device_rng_state() {
+    use tch::{Cuda, Device};
+    let state = tch::get_device_rng_state(Device::Cpu).unwrap();
+    assert_eq!(state.size(), tch::get_rng_state().size());
+    if !Cuda::is_available() {
+        assert!(tch::get_device_rng_state(Device::Cuda(0)).is_err());
+        return;
+    }
+    let device = Device::Cuda(0);
+    let state = tch::get_device_rng_state(device).unwrap();
+    let xs = Tensor::randn(&[10], (Kind::Float, device));
+    tch::set_device_rng_state(device, &state).unwrap();
+    let ys = Tensor::randn(&[10], (Kind::Float, device));
+    assert_eq!(
+        Vec::<f64>::from(&xs.to_device(Device::Cpu)),
+        Vec::<f64>::from(&ys.to_device(Device::Cpu))
+    );
+}
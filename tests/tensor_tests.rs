@@ -1,4 +1,5 @@
-use tch::Tensor;
+use std::convert::TryFrom;
+use tch::{Contraction, GridPaddingMode, GridSampleMode, Kind, Tensor};
 
 #[test]
 fn assign_ops() {
@@ -79,7 +80,7 @@ fn grad_grad() {
     let dy_over_dx = &dy_over_dx[0];
     dy_over_dx.backward();
     let dy_over_dx2 = x.grad();
-    assert_eq!(f64::from(&dy_over_dx2), 254.0);
+    assert_eq!(f64::try_from(&dy_over_dx2).unwrap(), 254.0);
 }
 
 #[test]
@@ -120,7 +121,26 @@ fn save_and_load_multi() {
     assert_eq!(named_tensors.len(), 2);
     assert_eq!(named_tensors[0].0, "pi");
     assert_eq!(named_tensors[1].0, "e");
-    assert_eq!(i64::from(&named_tensors[1].1.sum()), 57);
+    assert_eq!(i64::try_from(&named_tensors[1].1.sum()).unwrap(), 57);
+}
+
+#[test]
+fn save_and_load_bytes() {
+    let t1 = Tensor::of_slice(&[3.0, 1.0, 4.0, 1.0, 5.0]).view(&[5, 1]);
+    let bytes = t1.save_to_bytes().unwrap();
+    let t2 = Tensor::load_from_bytes(&bytes).unwrap();
+    assert_eq!(t2.size(), [5, 1]);
+    assert_eq!(Vec::<f64>::from(&t2.view(&[-1])), [3.0, 1.0, 4.0, 1.0, 5.0]);
+    assert!(Tensor::load_from_bytes(&bytes[..bytes.len() / 2]).is_err());
+
+    let pi = Tensor::of_slice(&[3.0, 1.0, 4.0, 1.0, 5.0]);
+    let e = Tensor::of_slice(&[2, 7, 1, 8, 2, 8, 1, 8, 2, 8, 4, 6]);
+    let bytes = Tensor::save_multi_to_bytes(&[(&"pi", &pi), (&"e", &e)]).unwrap();
+    let named_tensors = Tensor::load_multi_from_bytes(&bytes).unwrap();
+    assert_eq!(named_tensors.len(), 2);
+    assert_eq!(named_tensors[0].0, "pi");
+    assert_eq!(named_tensors[1].0, "e");
+    assert_eq!(i64::try_from(&named_tensors[1].1.sum()).unwrap(), 57);
 }
 
 #[test]
@@ -162,3 +182,972 @@ fn broadcast() {
     assert_eq!(Vec::<i64>::from(&tensors[0]), vec![4, 5, 3]);
     assert_eq!(Vec::<i64>::from(&tensors[1]), vec![42, 42, 42]);
 }
+
+#[test]
+fn stride_and_contiguous() {
+    let xs = Tensor::of_slice(&[0, 1, 2, 3, 4, 5]).view(&[2, 3]);
+    assert_eq!(xs.stride(), [3, 1]);
+    assert!(xs.is_contiguous());
+    assert_eq!(xs.storage_offset(), 0);
+    let ys = xs.tr();
+    assert_eq!(ys.size(), [3, 2]);
+    assert_eq!(ys.stride(), [1, 3]);
+    assert!(!ys.is_contiguous());
+    assert!(ys.contiguous().is_contiguous());
+    let zs = xs.narrow(1, 1, 2);
+    assert_eq!(zs.storage_offset(), 1);
+}
+
+#[test]
+fn bfloat16() {
+    let xs = Tensor::of_slice(&[1.0f32, 2.5, -3.25, 3.14159]);
+    let bf = xs.to_kind(Kind::BFloat16);
+    assert_eq!(bf.kind(), Kind::BFloat16);
+    let ys = bf.to_kind(Kind::Float);
+    assert_eq!(ys.kind(), Kind::Float);
+    let ys = Vec::<f32>::from(&ys);
+    assert_eq!(&ys[..3], [1.0, 2.5, -3.25]);
+    assert!((ys[3] - 3.14159).abs() < 1e-2, "{:?}", ys);
+}
+
+#[test]
+fn clamp_tensor() {
+    let xs = Tensor::of_slice(&[1., 5., 3., -2., 0., 7.]).view(&[2, 3]);
+    let min = Tensor::of_slice(&[0., 1., 2.]);
+    let max = Tensor::of_slice(&[4., 4., 4.]);
+    let ys = xs.clamp_tensor(Some(&min), Some(&max));
+    assert_eq!(Vec::<f64>::from(&ys), [1., 4., 3., 0., 1., 4.]);
+    let ys = xs.clamp_tensor(Some(&min), None);
+    assert_eq!(Vec::<f64>::from(&ys), [1., 5., 3., 0., 1., 7.]);
+    let ys = xs.clamp_tensor(None, Some(&max));
+    assert_eq!(Vec::<f64>::from(&ys), [1., 4., 3., -2., 0., 4.]);
+    assert!(xs.f_clamp_tensor(None, None).is_err());
+}
+
+#[test]
+fn grid_sample() {
+    let img = Tensor::of_slice(&(0..24).collect::<Vec<_>>())
+        .to_kind(Kind::Float)
+        .view(&[1, 2, 3, 4]);
+    let theta = Tensor::of_slice(&[1f32, 0., 0., 0., 1., 0.]).view(&[1, 2, 3]);
+    let grid = Tensor::affine_grid_generator(&theta, &[1, 2, 3, 4]);
+    let out = img.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridPaddingMode::Zeros,
+        true,
+    );
+    assert_eq!(out.size(), [1, 2, 3, 4]);
+    let err = f64::try_from((&out - &img).abs().max()).unwrap();
+    assert!(err < 1e-5, "{}", err);
+
+    // Without aligned corners, the pixel centers are at (2i + 1) / size - 1.
+    let xs = (Tensor::arange(4, tch::kind::FLOAT_CPU) * 2. + 1.) / 4. - 1.;
+    let ys = (Tensor::arange(3, tch::kind::FLOAT_CPU) * 2. + 1.) / 3. - 1.;
+    let grid = Tensor::stack(
+        &[
+            xs.view(&[1, 4]).expand(&[3, 4], false),
+            ys.view(&[3, 1]).expand(&[3, 4], false),
+        ],
+        -1,
+    )
+    .unsqueeze(0);
+    let out = img.grid_sample(
+        &grid,
+        GridSampleMode::Nearest,
+        GridPaddingMode::Border,
+        false,
+    );
+    let err = f64::try_from((&out - &img).abs().max()).unwrap();
+    assert!(err < 1e-5, "{}", err);
+
+    let bad_grid = Tensor::zeros(&[1, 3, 4, 3], tch::kind::FLOAT_CPU);
+    assert!(img
+        .f_grid_sample(
+            &bad_grid,
+            GridSampleMode::Bilinear,
+            GridPaddingMode::Zeros,
+            true
+        )
+        .is_err());
+
+    // Out of range coordinates are reflected at the pixel edges, the pixel
+    // coordinates here are -1, 4, -1.5 and 4.5.
+    let img = Tensor::of_slice(&(0..8).collect::<Vec<_>>())
+        .to_kind(Kind::Float)
+        .view(&[1, 1, 2, 4]);
+    let grid =
+        Tensor::of_slice(&[-1.25f32, -0.5, 1.25, -0.5, -1.5, -0.5, 1.5, -0.5]).view(&[1, 1, 4, 2]);
+    let out = img.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridPaddingMode::Reflection,
+        false,
+    );
+    let expected = Tensor::of_slice(&[0f32, 3., 0.5, 2.5]).view(&[1, 1, 1, 4]);
+    let err = f64::try_from((&out - expected).abs().max()).unwrap();
+    assert!(err < 1e-5, "{:?}", out);
+
+    // Spatial dimensions of size 1 cannot be sampled without aligned corners.
+    let img = Tensor::ones(&[1, 1, 1, 4], tch::kind::FLOAT_CPU);
+    let grid = Tensor::zeros(&[1, 1, 4, 2], tch::kind::FLOAT_CPU);
+    assert!(img
+        .f_grid_sample(
+            &grid,
+            GridSampleMode::Bilinear,
+            GridPaddingMode::Zeros,
+            false
+        )
+        .is_err());
+    let out = img.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridPaddingMode::Zeros,
+        true,
+    );
+    assert_eq!(Vec::<f64>::from(&out), [1., 1., 1., 1.]);
+}
+
+#[test]
+fn sparse_coo() {
+    let indices = Tensor::of_slice(&[0i64, 2, 1, 0]).view(&[2, 2]);
+    let values = Tensor::of_slice(&[3., 4.]);
+    let xs = Tensor::sparse_coo(&indices, &values, &[3, 2]);
+    assert!(xs.is_sparse());
+    let dense = xs.to_dense();
+    assert!(!dense.is_sparse());
+    assert_eq!(Vec::<f64>::from(&dense), [0., 3., 0., 0., 4., 0.]);
+    assert_eq!(
+        Vec::<f64>::from(&dense.to_sparse().to_dense()),
+        Vec::<f64>::from(&dense)
+    );
+    assert!(Tensor::f_sparse_coo(&indices, &values, &[3]).is_err());
+}
+
+#[test]
+fn sparse_csr() {
+    // [[1, 0, 2], [0, 0, 0], [0, 3, 0]]
+    let crow = Tensor::of_slice(&[0i64, 2, 2, 3]);
+    let col = Tensor::of_slice(&[0i64, 2, 1]);
+    let values = Tensor::of_slice(&[1f64, 2., 3.]);
+    let csr = Tensor::sparse_csr(&crow, &col, &values, &[3, 3]);
+    assert_eq!(csr.nnz(), 3);
+    let dense = csr.to_dense();
+    assert_eq!(
+        Vec::<f64>::from(&dense),
+        [1., 0., 2., 0., 0., 0., 0., 3., 0.]
+    );
+    assert_eq!(
+        Vec::<f64>::from(&csr.to_sparse_coo().to_dense()),
+        Vec::<f64>::from(&dense)
+    );
+    let vs = Tensor::of_slice(&[1f64, 10., 100.]);
+    assert_eq!(Vec::<f64>::from(&csr.matmul(&vs)), [201., 0., 30.]);
+    let ms = Tensor::randn(&[3, 4], tch::kind::DOUBLE_CPU);
+    let diff = csr.mm(&ms) - dense.mm(&ms);
+    assert!(f64::try_from(diff.abs().max()).unwrap() < 1e-12);
+
+    let csr = dense.to_sparse_csr();
+    assert_eq!(Vec::<i64>::from(csr.crow_indices()), [0, 2, 2, 3]);
+    assert_eq!(Vec::<i64>::from(csr.col_indices()), [0, 2, 1]);
+    assert_eq!(Vec::<f64>::from(csr.values()), [1., 2., 3.]);
+    assert!(Tensor::f_sparse_csr(&crow, &col, &values, &[2, 3]).is_err());
+    assert!(Tensor::f_sparse_csr(&crow, &col, &values, &[3, 2]).is_err());
+    assert!(csr
+        .f_matmul(&Tensor::ones(&[2], tch::kind::DOUBLE_CPU))
+        .is_err());
+}
+
+#[test]
+fn index_select_checked() {
+    let xs = Tensor::of_slice(&[0, 1, 2, 3, 4, 5]).view(&[3, 2]);
+    let index = Tensor::of_slice(&[2i64, 0]);
+    let ys = xs.f_index_select_checked(0, &index).unwrap();
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[4, 5], [0, 1]]);
+    let ys = xs
+        .f_index_select_checked(-1, &Tensor::of_slice(&[1i64]))
+        .unwrap();
+    assert_eq!(Vec::<i64>::from(&ys), [1, 3, 5]);
+    assert!(xs
+        .f_index_select_checked(0, &Tensor::of_slice(&[3i64]))
+        .is_err());
+    assert!(xs
+        .f_index_select_checked(0, &Tensor::of_slice(&[-1i64]))
+        .is_err());
+    assert!(xs.f_index_select_checked(2, &index).is_err());
+
+    let ys = xs.f_take_checked(&Tensor::of_slice(&[5i64, -6])).unwrap();
+    assert_eq!(Vec::<i64>::from(&ys), [5, 0]);
+    assert!(xs.f_take_checked(&Tensor::of_slice(&[6i64])).is_err());
+}
+
+#[test]
+fn movedim() {
+    let xs = Tensor::zeros(&[2, 3, 4, 5], tch::kind::FLOAT_CPU);
+    assert_eq!(xs.movedim(&[1], &[3]).size(), [2, 4, 5, 3]);
+    assert_eq!(xs.movedim(&[-3], &[-1]).size(), [2, 4, 5, 3]);
+    assert_eq!(xs.movedim(&[3], &[1]).size(), [2, 5, 3, 4]);
+    assert_eq!(xs.movedim(&[0, 1], &[-1, 0]).size(), [3, 4, 5, 2]);
+    assert!(xs.f_movedim(&[0, 0], &[1, 2]).is_err());
+    assert!(xs.f_movedim(&[4], &[0]).is_err());
+    assert_eq!(xs.swapaxes(0, -1).size(), [5, 3, 4, 2]);
+
+    let xs = Tensor::of_slice(&[0, 1, 2, 3, 4, 5]).view(&[1, 2, 3]);
+    let ys = xs.movedim(&[1], &[2]);
+    assert_eq!(Vec::<i64>::from(&ys.contiguous()), [0, 3, 1, 4, 2, 5]);
+}
+
+#[test]
+fn unfold() {
+    // `unfold` returns a view with the windows in a new trailing dimension.
+    let xs = Tensor::of_slice(&[0i64, 1, 2, 3, 4]);
+    let windows = xs.unfold(0, 3, 1);
+    assert_eq!(windows.size(), [3, 3]);
+    assert_eq!(windows.stride(), [1, 1]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&windows),
+        [[0, 1, 2], [1, 2, 3], [2, 3, 4]]
+    );
+
+    let xs = Tensor::arange(9, tch::kind::FLOAT_CPU).view(&[1, 1, 3, 3]);
+    let cols = xs.unfold2d(&[2, 2], &[1, 1], &[0, 0], &[1, 1]);
+    assert_eq!(cols.size(), [1, 4, 4]);
+    assert_eq!(
+        Vec::<Vec<f64>>::from(&cols.get(0)),
+        [
+            [0., 1., 3., 4.],
+            [1., 2., 4., 5.],
+            [3., 4., 6., 7.],
+            [4., 5., 7., 8.]
+        ]
+    );
+    let cols = xs.unfold2d(&[2, 2], &[2, 2], &[1, 1], &[2, 2]);
+    assert_eq!(cols.size(), [1, 4, 4]);
+    assert_eq!(Vec::<f64>::from(&cols.get(0).get(0)), [0., 0., 0., 4.]);
+    assert!(xs.f_unfold2d(&[4, 4], &[1, 1], &[0, 0], &[1, 1]).is_err());
+}
+
+#[test]
+fn diag() {
+    let xs = Tensor::arange(9, tch::kind::INT64_CPU).view(&[3, 3]);
+    assert_eq!(Vec::<i64>::from(&xs.diag(0)), [0, 4, 8]);
+    assert_eq!(Vec::<i64>::from(&xs.diag(1)), [1, 5]);
+    assert_eq!(Vec::<i64>::from(&xs.diag(-1)), [3, 7]);
+    let ys = xs.diag(0).diag(0);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&ys),
+        [[0, 0, 0], [0, 4, 0], [0, 0, 8]]
+    );
+    assert_eq!(Vec::<i64>::from(&ys.diag(0)), [0, 4, 8]);
+
+    let xs = Tensor::arange(8, tch::kind::INT64_CPU).view(&[2, 2, 2]);
+    let diagonals = xs.diagonal(0, 1, 2);
+    assert_eq!(Vec::<Vec<i64>>::from(&diagonals), [[0, 3], [4, 7]]);
+    let embedded = diagonals.diag_embed(0, 1, 2);
+    assert_eq!(embedded.size(), [2, 2, 2]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&embedded.diagonal(0, 1, 2)),
+        [[0, 3], [4, 7]]
+    );
+    let embedded = diagonals.diag_embed(-1, -2, -1);
+    assert_eq!(embedded.size(), [2, 3, 3]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&embedded.get(1)),
+        [[0, 0, 0], [4, 0, 0], [0, 7, 0]]
+    );
+}
+
+#[test]
+fn flatten() {
+    let xs = Tensor::arange(2 * 3 * 4 * 5, tch::kind::FLOAT_CPU).view(&[2, 3, 4, 5]);
+    assert_eq!(xs.flatten(2, 3).size(), [2, 3, 20]);
+    assert_eq!(xs.flatten(1, 2).size(), [2, 12, 5]);
+    assert_eq!(xs.flatten(1, -1).size(), [2, 60]);
+    assert_eq!(xs.flatten(-3, -2).size(), [2, 12, 5]);
+    assert_eq!(xs.flatten(0, -1).size(), [120]);
+    assert_eq!(xs.flatten(2, 2).size(), [2, 3, 4, 5]);
+    // The elements keep their row-major order.
+    assert_eq!(
+        Vec::<f64>::from(&xs.flatten(1, 2).get(1).get(1)),
+        Vec::<f64>::from(&xs.get(1).get(0).get(1))
+    );
+    assert!(xs.f_flatten(2, 1).is_err());
+    assert!(xs.f_flatten(0, 4).is_err());
+}
+
+#[test]
+fn broadcast_to() {
+    let xs = Tensor::of_slice(&[1i64, 2, 3]).view(&[3, 1]);
+    let ys = Tensor::of_slice(&[10i64, 20, 30, 40]).view(&[1, 4]);
+    let bs = Tensor::f_broadcast_tensors(&[&xs, &ys]).unwrap();
+    assert_eq!(bs.len(), 2);
+    assert_eq!(bs[0].size(), [3, 4]);
+    assert_eq!(bs[1].size(), [3, 4]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&(&bs[0] + &bs[1])),
+        [[11, 21, 31, 41], [12, 22, 32, 42], [13, 23, 33, 43]]
+    );
+    let zs = Tensor::zeros(&[2, 3], tch::kind::INT64_CPU);
+    assert!(Tensor::f_broadcast_tensors(&[&xs, &ys, &zs]).is_err());
+
+    let bs = xs.broadcast_to(&[2, 3, 4]);
+    assert_eq!(bs.size(), [2, 3, 4]);
+    assert_eq!(bs.stride(), [0, 1, 0]);
+    assert_eq!(xs.broadcast_to(&[-1, 2]).size(), [3, 2]);
+    assert!(xs.f_broadcast_to(&[4, 2]).is_err());
+    assert!(xs.f_broadcast_to(&[3]).is_err());
+    assert!(xs.f_broadcast_to(&[-1, 3, 2]).is_err());
+}
+
+#[test]
+fn masked_select() {
+    let xs = Tensor::arange(6, tch::kind::INT64_CPU).view(&[3, 2]);
+    let mask = xs.gt(2);
+    assert_eq!(Vec::<i64>::from(&xs.masked_select(&mask)), [3, 4, 5]);
+
+    let rows = Tensor::of_slice(&[1u8, 0, 1]);
+    let ys = xs.filter_rows(&rows);
+    assert_eq!(ys.size(), [2, 2]);
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[0, 1], [4, 5]]);
+    let no_rows = xs.filter_rows(&Tensor::zeros(&[3], (tch::Kind::Uint8, tch::Device::Cpu)));
+    assert_eq!(no_rows.size(), [0, 2]);
+    assert!(xs.f_filter_rows(&Tensor::of_slice(&[1u8, 0])).is_err());
+}
+
+#[test]
+fn cross_entropy_weighted() {
+    let logits = Tensor::of_slice(&[0f64, 0., 2., 0., 1., 3., 0., 0.]).view(&[4, 2]);
+    let targets = Tensor::of_slice(&[0i64, 1, -100, 0]);
+    let weights = Tensor::of_slice(&[1f64, 3.]);
+    let loss = logits.cross_entropy_for_logits_weighted(&targets, Some(&weights), -100);
+    // Per-sample losses for the non-ignored samples 0, 1 and 3.
+    let l0 = 2f64.ln();
+    let l1 = (1. + 2f64.exp()).ln();
+    let l3 = 2f64.ln();
+    let expected = (l0 + 3. * l1 + l3) / (1. + 3. + 1.);
+    assert!((f64::try_from(&loss).unwrap() - expected).abs() < 1e-9);
+    let nll = logits
+        .log_softmax(-1)
+        .g_nll_loss(&targets, Some(&weights), 1, -100);
+    assert!((f64::try_from(&loss).unwrap() - f64::try_from(&nll).unwrap()).abs() < 1e-12);
+    // Without weights, the ignored sample is excluded from the mean.
+    let loss = logits.cross_entropy_for_logits_weighted(&targets, None, -100);
+    assert!((f64::try_from(&loss).unwrap() - (l0 + l1 + l3) / 3.).abs() < 1e-9);
+    assert!(logits
+        .f_cross_entropy_for_logits_weighted(
+            &targets,
+            Some(&Tensor::ones(&[3], tch::kind::DOUBLE_CPU)),
+            -100
+        )
+        .is_err());
+}
+
+#[test]
+fn shares_storage() {
+    let mut xs = Tensor::of_slice(&[1f64, 2., 3.]);
+    let ys = xs.shallow_clone();
+    assert!(xs.shares_storage_with(&ys));
+    assert!(xs.narrow(0, 1, 2).shares_storage_with(&ys));
+    let _t = xs.fill_(4.);
+    assert_eq!(Vec::<f64>::from(&ys), [4., 4., 4.]);
+    let zs = xs.copy();
+    assert!(!zs.shares_storage_with(&xs));
+    let _t = xs.fill_(5.);
+    assert_eq!(Vec::<f64>::from(&ys), [5., 5., 5.]);
+    assert_eq!(Vec::<f64>::from(&zs), [4., 4., 4.]);
+    assert!(!Tensor::new().shares_storage_with(&xs));
+}
+
+#[test]
+fn scatter_reduce() {
+    use tch::ScatterReduce;
+    let src = Tensor::of_slice(&[1f64, 5., 2., 7., 3., 4.]);
+    let index = Tensor::of_slice(&[0i64, 0, 1, 2, 2, 2]);
+    let xs = Tensor::of_slice(&[6f64, 0., 0., -1.]);
+    let reduce =
+        |op, include_self| Vec::<f64>::from(xs.scatter_reduce(0, &index, &src, op, include_self));
+    assert_eq!(reduce(ScatterReduce::Amax, false), [5., 2., 7., -1.]);
+    assert_eq!(reduce(ScatterReduce::Amax, true), [6., 2., 7., -1.]);
+    assert_eq!(reduce(ScatterReduce::Amin, false), [1., 2., 3., -1.]);
+    assert_eq!(reduce(ScatterReduce::Amin, true), [1., 0., 0., -1.]);
+    assert_eq!(reduce(ScatterReduce::Sum, true), [12., 2., 14., -1.]);
+    assert_eq!(reduce(ScatterReduce::Prod, false), [5., 2., 84., -1.]);
+    assert_eq!(reduce(ScatterReduce::Mean, false), [3., 2., 14. / 3., -1.]);
+    assert_eq!(reduce(ScatterReduce::Mean, true), [4., 1., 3.5, -1.]);
+
+    // Mean of integers rounds towards minus infinity.
+    let ys = Tensor::of_slice(&[0i64, 0]).scatter_reduce(
+        0,
+        &Tensor::of_slice(&[0i64, 0, 1]),
+        &Tensor::of_slice(&[-1i64, -2, 3]),
+        ScatterReduce::Mean,
+        false,
+    );
+    assert_eq!(Vec::<i64>::from(&ys), [-2, 3]);
+
+    // Along the second dimension of a 2D tensor.
+    let src = Tensor::of_slice(&[1f64, 2., 3., 4., 5., 6.]).view(&[2, 3]);
+    let index = Tensor::of_slice(&[1i64, 0, 1, 0, 0, 0]).view(&[2, 3]);
+    let ys = Tensor::zeros(&[2, 2], tch::kind::DOUBLE_CPU).scatter_reduce(
+        1,
+        &index,
+        &src,
+        ScatterReduce::Amax,
+        false,
+    );
+    assert_eq!(Vec::<Vec<f64>>::from(&ys), [[2., 3.], [6., 0.]]);
+    let bad_index = Tensor::of_slice(&[4i64, 0, 0]);
+    assert!(xs
+        .f_scatter_reduce(0, &bad_index, &xs, ScatterReduce::Sum, true)
+        .is_err());
+}
+
+#[test]
+fn nonzero() {
+    let mask = Tensor::of_slice(&[0u8, 1, 1, 0, 0, 1]).view(&[2, 3]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&mask.nonzero()),
+        [[0, 1], [0, 2], [1, 2]]
+    );
+    let indexes = mask.nonzero_numpy();
+    assert_eq!(indexes.len(), 2);
+    assert_eq!(Vec::<i64>::from(&indexes[0]), [0, 0, 1]);
+    assert_eq!(Vec::<i64>::from(&indexes[1]), [1, 2, 2]);
+    let scalar = Tensor::from(3i64).nonzero_numpy();
+    assert_eq!(scalar.len(), 1);
+    assert_eq!(Vec::<i64>::from(&scalar[0]), [0]);
+}
+
+#[test]
+fn channels_last() {
+    use tch::MemoryFormat;
+    let xs = Tensor::arange(2 * 3 * 4 * 5, tch::kind::FLOAT_CPU).view(&[2, 3, 4, 5]);
+    assert!(xs.is_contiguous_in(MemoryFormat::Contiguous));
+    assert!(!xs.is_contiguous_in(MemoryFormat::ChannelsLast));
+    let ys = xs.to_memory_format(MemoryFormat::ChannelsLast);
+    assert_eq!(ys.size(), [2, 3, 4, 5]);
+    assert_eq!(ys.stride(), [60, 1, 15, 3]);
+    assert!(ys.is_contiguous_in(MemoryFormat::ChannelsLast));
+    assert!(!ys.is_contiguous());
+    assert_eq!(Vec::<f64>::from(&ys), Vec::<f64>::from(&xs));
+    let zs = ys.to_memory_format(MemoryFormat::Contiguous);
+    assert_eq!(zs.stride(), [60, 20, 5, 1]);
+    assert!(Tensor::zeros(&[3, 4], tch::kind::FLOAT_CPU)
+        .f_to_memory_format(MemoryFormat::ChannelsLast)
+        .is_err());
+}
+
+#[test]
+fn logsumexp_and_softmax() {
+    let xs = Tensor::of_slice(&[1f32, 2., 3., -1., 0., 0.5]).view(&[2, 3]);
+    let lse = xs.logsumexp_dim(1, false);
+    let naive = xs.exp().sum2(&[1], false).log();
+    assert!(f64::try_from((&lse - naive).abs().max()).unwrap() < 1e-5);
+    assert_eq!(xs.logsumexp_dim(-1, true).size(), [2, 1]);
+
+    // Large values would overflow with the naive computation.
+    let xs = Tensor::of_slice(&[1000f32, 1000.]);
+    let lse = f64::try_from(xs.logsumexp_dim(0, false)).unwrap();
+    assert!((lse - 1000. - 2f64.ln()).abs() < 1e-3, "{}", lse);
+    assert!(f64::try_from(xs.exp().sum().log()).unwrap().is_infinite());
+
+    let xs = Tensor::of_slice(&[1f32, 2., 3.]);
+    let sm = xs.softmax_dim(0, None);
+    assert!((f64::try_from(sm.sum()).unwrap() - 1.).abs() < 1e-6);
+    let sm = xs.softmax_dim(-1, Some(Kind::Double));
+    assert_eq!(sm.kind(), Kind::Double);
+    let lsm = xs.log_softmax_dim(0, None);
+    assert!(f64::try_from((lsm.exp() - xs.softmax_dim(0, None)).abs().max()).unwrap() < 1e-6);
+    assert!(xs.f_softmax_dim(1, None).is_err());
+    assert!(Tensor::of_slice(&[1i64, 2])
+        .f_log_softmax_dim(0, None)
+        .is_err());
+    assert!(Tensor::of_slice(&[1i64, 2])
+        .f_log_softmax_dim(0, Some(Kind::Float))
+        .is_ok());
+}
+
+#[test]
+fn tensor_split() {
+    let xs = Tensor::arange(7, tch::kind::INT64_CPU);
+    let chunks = xs.tensor_split(3, 0);
+    let sizes: Vec<_> = chunks.iter().map(|c| c.size()[0]).collect();
+    assert_eq!(sizes, [3, 2, 2]);
+    assert_eq!(Vec::<i64>::from(&chunks[1]), [3, 4]);
+    let sizes: Vec<_> = xs.tensor_split(9, 0).iter().map(|c| c.size()[0]).collect();
+    assert_eq!(sizes, [1, 1, 1, 1, 1, 1, 1, 0, 0]);
+
+    let xs = Tensor::arange(12, tch::kind::INT64_CPU).view(&[2, 6]);
+    let chunks = xs.tensor_split_indices(&[1, 4], 1);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(Vec::<Vec<i64>>::from(&chunks[0]), [[0], [6]]);
+    assert_eq!(Vec::<Vec<i64>>::from(&chunks[1]), [[1, 2, 3], [7, 8, 9]]);
+    assert_eq!(chunks[2].size(), [2, 2]);
+    let sizes: Vec<_> = xs
+        .tensor_split_indices(&[4, 2, -1, 10], -1)
+        .iter()
+        .map(|c| c.size()[1])
+        .collect();
+    assert_eq!(sizes, [4, 0, 3, 1, 0]);
+    assert!(xs.f_tensor_split(0, 0).is_err());
+}
+
+#[test]
+fn narrow_checked() {
+    let xs = Tensor::arange(12, tch::kind::INT64_CPU).view(&[3, 4]);
+    let ys = xs.f_narrow_checked(1, -3, 2).unwrap();
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[1, 2], [5, 6], [9, 10]]);
+    let ys = xs.f_narrow_checked(-2, 1, 2).unwrap();
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[4, 5, 6, 7], [8, 9, 10, 11]]);
+    assert_eq!(xs.f_narrow_checked(1, 4, 0).unwrap().size(), [3, 0]);
+    assert!(xs.f_narrow_checked(1, 2, 3).is_err());
+    assert!(xs.f_narrow_checked(1, -5, 1).is_err());
+    assert!(xs.f_narrow_checked(0, 1, -1).is_err());
+    assert!(xs.f_narrow_checked(2, 0, 1).is_err());
+}
+
+#[test]
+fn bmm_checked() {
+    let xs = Tensor::arange(12, tch::kind::FLOAT_CPU).view(&[2, 2, 3]);
+    let ys = Tensor::arange(12, tch::kind::FLOAT_CPU).view(&[2, 3, 2]) - 5.;
+    let zs = xs.f_bmm_checked(&ys).unwrap();
+    assert_eq!(zs.size(), [2, 2, 2]);
+    for b in 0..2 {
+        let expected = xs.get(b).matmul(&ys.get(b));
+        assert_eq!(Vec::<f64>::from(&zs.get(b)), Vec::<f64>::from(&expected));
+    }
+    let err = xs.f_bmm_checked(&xs).unwrap_err();
+    assert!(format!("{}", err).contains("inner dimensions"), "{}", err);
+    let err = xs.f_bmm_checked(&ys.narrow(0, 0, 1)).unwrap_err();
+    assert!(format!("{}", err).contains("batch sizes"), "{}", err);
+    assert!(xs.f_bmm_checked(&ys.get(0)).is_err());
+
+    let input = Tensor::ones(&[2, 2], tch::kind::FLOAT_CPU);
+    let res = input.f_baddbmm_checked(&xs, &ys, 0.5, 2.).unwrap();
+    assert_eq!(Vec::<f64>::from(&res), Vec::<f64>::from(&(&zs * 2. + 0.5)));
+    assert!(Tensor::ones(&[3], tch::kind::FLOAT_CPU)
+        .f_baddbmm_checked(&xs, &ys, 1., 1.)
+        .is_err());
+}
+
+#[test]
+fn cosine_similarity_and_distances() {
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 1., 0., 0., 0., 0., 0.]).view(&[3, 3]);
+    let ys = Tensor::of_slice(&[1f32, 2., 3., 0., 1., 0., 1., 1., 1.]).view(&[3, 3]);
+    let sim = Vec::<f64>::from(Tensor::cosine_similarity(&xs, &ys, 1, 1e-8));
+    assert!((sim[0] - 1.).abs() < 1e-6, "{:?}", sim);
+    assert!(sim[1].abs() < 1e-6, "{:?}", sim);
+    // The eps avoids a division by zero for the zero vector.
+    assert_eq!(sim[2], 0.);
+
+    let dist = Tensor::pairwise_distance(&xs, &ys, 2., 0., false);
+    let dist = Vec::<f64>::from(dist);
+    assert!(
+        dist[0].abs() < 1e-6 && (dist[1] - 2f64.sqrt()).abs() < 1e-6,
+        "{:?}",
+        dist
+    );
+
+    let all_pairs = Tensor::cdist(&xs, &ys.narrow(0, 0, 2), 1.);
+    assert_eq!(all_pairs.size(), [3, 2]);
+    assert_eq!(
+        Vec::<Vec<f64>>::from(&all_pairs),
+        [[0., 5.], [5., 2.], [6., 1.]]
+    );
+}
+
+#[test]
+fn amax_amin() {
+    let xs = Tensor::arange(48, tch::kind::FLOAT_CPU).view(&[2, 2, 3, 4]);
+    let max = xs.amax(&[2, 3], false);
+    assert_eq!(max.size(), [2, 2]);
+    assert_eq!(Vec::<f64>::from(&max.view(&[-1])), [11., 23., 35., 47.]);
+    let min = xs.amin(&[-1, -2], true);
+    assert_eq!(min.size(), [2, 2, 1, 1]);
+    assert_eq!(Vec::<f64>::from(&min.view(&[-1])), [0., 12., 24., 36.]);
+    assert_eq!(f64::try_from(&xs.amax(&[], false)).unwrap(), 47.);
+    assert!(xs.f_amax(&[2, -2], false).is_err());
+    assert!(xs.f_amax(&[4], false).is_err());
+    let ys = Tensor::of_slice(&[3, 1, 4, 1, 5, 9, 2, 6]).view(&[2, 4]);
+    assert_eq!(i64::try_from(&ys.argmax_dim(None, false)).unwrap(), 5);
+    assert_eq!(ys.argmin_dim(None, true).size(), [1, 1]);
+    assert_eq!(Vec::<i64>::from(&ys.argmax_dim(Some(1), false)), [2, 1]);
+    assert_eq!(ys.argmin_dim(Some(0), true).size(), [1, 4]);
+}
+
+#[test]
+fn take_along_dim() {
+    let logits = Tensor::of_slice(&[1f32, 3., 2., 0.5, 0.1, 0.2]).view(&[2, 3]);
+    let probs = logits.softmax(-1);
+    let top1 = probs.argmax(-1, true);
+    let values = probs.take_along_dim(&top1, -1);
+    assert_eq!(values.size(), [2, 1]);
+    assert_eq!(
+        Vec::<f64>::from(&values.view(&[-1])),
+        Vec::<f64>::from(&probs.max2(-1, false).0)
+    );
+    // The indices are broadcast over the other dimensions.
+    let xs = Tensor::arange(6, tch::kind::FLOAT_CPU).view(&[2, 3]);
+    let indices = Tensor::of_slice(&[2i64, 0]).view(&[1, 2]);
+    let ys = xs.take_along_dim(&indices, 1);
+    assert_eq!(Vec::<Vec<f64>>::from(&ys), [[2., 0.], [5., 3.]]);
+    assert!(xs.f_take_along_dim(&indices.view(&[2]), 1).is_err());
+    assert!(xs
+        .f_take_along_dim(&Tensor::zeros(&[3, 1], tch::kind::INT64_CPU), 1)
+        .is_err());
+}
+
+#[test]
+fn save_and_load_torch() {
+    let filename = std::env::temp_dir().join(format!("tch-save-torch-{}.pt", std::process::id()));
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4., 5., 6.]).view(&[2, 3]);
+    let ys = Tensor::of_slice(&[-1i64, 1 << 40, 42]);
+    // Non-contiguous tensors are written as contiguous ones.
+    let zs = Tensor::of_slice(&[1f64, 2., 3., 4.]).view(&[2, 2]).tr();
+    let hs = Tensor::of_slice(&[0.5f32, 1.5]).to_kind(Kind::Half);
+    Tensor::save_torch(
+        &[("xs", &xs), ("ys", &ys), ("zs", &zs), ("hs", &hs)],
+        &filename,
+    )
+    .unwrap();
+    let named_tensors = Tensor::load_torch(&filename).unwrap();
+    let names: Vec<&str> = named_tensors.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, ["xs", "ys", "zs", "hs"]);
+    let (_, xs2) = &named_tensors[0];
+    assert_eq!(xs2.size(), [2, 3]);
+    assert_eq!(Vec::<Vec<f64>>::from(xs2), Vec::<Vec<f64>>::from(&xs));
+    let (_, ys2) = &named_tensors[1];
+    assert_eq!(ys2.kind(), Kind::Int64);
+    assert_eq!(Vec::<i64>::from(ys2), [-1, 1 << 40, 42]);
+    let (_, zs2) = &named_tensors[2];
+    assert_eq!(Vec::<Vec<f64>>::from(zs2), [[1., 3.], [2., 4.]]);
+    let (_, hs2) = &named_tensors[3];
+    assert_eq!(hs2.kind(), Kind::Half);
+    assert_eq!(Vec::<f64>::from(&hs2.to_kind(Kind::Float)), [0.5, 1.5]);
+    let complex = Tensor::zeros(&[2], (Kind::ComplexFloat, tch::Device::Cpu));
+    assert!(Tensor::save_torch(&[("c", &complex)], &filename).is_err());
+}
+
+#[test]
+fn reduce() {
+    use tch::ReduceOp;
+    let xs = Tensor::of_slice(&[3f64, 1., 4., 1., 5., 9., 2., 6., 5., 3., 5., 8.]).view(&[2, 3, 2]);
+    let ops = [
+        ReduceOp::Sum,
+        ReduceOp::Mean,
+        ReduceOp::Max,
+        ReduceOp::Min,
+        ReduceOp::Prod,
+        ReduceOp::Std,
+        ReduceOp::Var,
+    ];
+    for &op in ops.iter() {
+        let expected = match op {
+            ReduceOp::Sum => xs.sum2(&[1], true),
+            ReduceOp::Mean => xs.mean2(&[1], true),
+            ReduceOp::Max => xs.max2(1, true).0,
+            ReduceOp::Min => xs.min2(1, true).0,
+            ReduceOp::Prod => xs.prod2(1, true),
+            ReduceOp::Std => xs.std1(&[1], true, true),
+            ReduceOp::Var => xs.var1(&[1], true, true),
+        };
+        let ys = xs.reduce(op, &[1], true);
+        assert_eq!(ys.size(), [2, 1, 2]);
+        let diff = f64::try_from((ys - expected).abs().max()).unwrap();
+        assert!(diff < 1e-9, "{:?} {}", op, diff);
+    }
+    // Empty dims reduce over all the dimensions.
+    assert_eq!(
+        f64::try_from(xs.reduce(ReduceOp::Sum, &[], false)).unwrap(),
+        52.
+    );
+    assert_eq!(xs.reduce(ReduceOp::Max, &[], true).size(), [1, 1, 1]);
+    assert_eq!(xs.reduce(ReduceOp::Mean, &[0, 2], false).size(), [3]);
+    assert!(xs.f_reduce(ReduceOp::Sum, &[3], false).is_err());
+}
+
+#[test]
+fn tile() {
+    let xs = Tensor::of_slice(&[1, 2, 3, 4, 5, 6]).view(&[2, 3]);
+    let ys = xs.tile(&[2]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&ys),
+        [[1, 2, 3, 1, 2, 3], [4, 5, 6, 4, 5, 6]]
+    );
+    let ys = xs.tile(&[2, 1]);
+    assert_eq!(
+        Vec::<Vec<i64>>::from(&ys),
+        [[1, 2, 3], [4, 5, 6], [1, 2, 3], [4, 5, 6]]
+    );
+    // Longer repetitions add leading dimensions.
+    assert_eq!(xs.tile(&[2, 1, 1]).size(), [2, 2, 3]);
+    assert_eq!(xs.tile(&[]).size(), [2, 3]);
+}
+
+#[test]
+fn repeat_interleave() {
+    let xs = Tensor::of_slice(&[1, 2, 3]);
+    let ys = xs.repeat_interleave_scalar(2, None);
+    assert_eq!(Vec::<i64>::from(&ys), [1, 1, 2, 2, 3, 3]);
+    let ys = xs.repeat_interleave_dim(&Tensor::of_slice(&[1i64, 0, 3]), Some(0));
+    assert_eq!(Vec::<i64>::from(&ys), [1, 3, 3, 3]);
+    let xs = Tensor::of_slice(&[1, 2, 3, 4]).view(&[2, 2]);
+    let ys = xs.repeat_interleave_scalar(2, Some(-1));
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[1, 1, 2, 2], [3, 3, 4, 4]]);
+    let ys = xs.repeat_interleave_dim(&Tensor::of_slice(&[2i64, 1]), Some(0));
+    assert_eq!(Vec::<Vec<i64>>::from(&ys), [[1, 2], [1, 2], [3, 4]]);
+    assert_eq!(
+        Vec::<i64>::from(&xs.repeat_interleave_scalar(2, None)),
+        [1, 1, 2, 2, 3, 3, 4, 4]
+    );
+    assert!(xs
+        .f_repeat_interleave_dim(&Tensor::of_slice(&[1i64, 2, 3]), Some(0))
+        .is_err());
+    assert!(xs.f_repeat_interleave_scalar(-1, Some(0)).is_err());
+    assert!(xs.f_repeat_interleave_scalar(2, Some(2)).is_err());
+}
+
+#[test]
+fn argmax_tie_breaking() {
+    let xs = Tensor::of_slice(&[1f32, 3., 0., 3., 2., 2., 2., 1.]).view(&[2, 4]);
+    assert_eq!(Vec::<i64>::from(&xs.argmax_first(1, false)), [1, 0]);
+    assert_eq!(Vec::<i64>::from(&xs.argmax_last(1, false)), [3, 2]);
+    assert_eq!(xs.argmax_last(-1, true).size(), [2, 1]);
+    assert_eq!(Vec::<i64>::from(&xs.argmax_first(0, false)), [1, 0, 1, 0]);
+    assert!(xs.f_argmax_first(2, false).is_err());
+    let nan = f32::NAN;
+    let xs = Tensor::of_slice(&[1f32, nan, 3., nan, 2., 2., 0., 1.]).view(&[2, 4]);
+    assert_eq!(Vec::<i64>::from(&xs.argmax_first(1, false)), [1, 0]);
+    assert_eq!(Vec::<i64>::from(&xs.argmax_last(1, false)), [3, 1]);
+}
+
+#[test]
+fn pad() {
+    let xs = Tensor::of_slice(&[1f32, 2., 3., 4.]);
+    assert_eq!(Vec::<f64>::from(&xs.pad(&[1, -1], 0.)), [0., 1., 2., 3.]);
+    assert_eq!(Vec::<f64>::from(&xs.pad(&[-2, 1], 9.)), [3., 4., 9.]);
+    assert_eq!(Vec::<f64>::from(&xs.pad(&[-1, -1], 0.)), [2., 3.]);
+    let xs = Tensor::arange(6, tch::kind::FLOAT_CPU).view(&[2, 3]);
+    let ys = xs.pad(&[0, -1, 1, 0], -1.);
+    assert_eq!(Vec::<Vec<f64>>::from(&ys), [[-1., -1.], [0., 1.], [3., 4.]]);
+    assert!(xs.f_pad(&[-2, -2], 0.).is_err());
+    assert!(xs.f_pad(&[1], 0.).is_err());
+    // The values are copied exactly, including for large fill values.
+    let xs = Tensor::of_slice(&[1f32, 2.]);
+    assert_eq!(Vec::<f32>::from(&xs.pad(&[1, 0], 1e8)), [1e8, 1., 2.]);
+    let xs = Tensor::of_slice(&[1i64, 2]);
+    assert_eq!(Vec::<i64>::from(&xs.pad(&[0, 2], 7.)), [1, 2, 7, 7]);
+    let xs = Tensor::of_slice(&[1f64, 2.]).set_requires_grad(true);
+    xs.pad(&[1, 1], 5.).sum().backward();
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [1., 1.]);
+}
+
+#[test]
+fn index_fill_and_copy() {
+    let mut xs = Tensor::zeros(&[3, 2], tch::kind::FLOAT_CPU);
+    let index = Tensor::of_slice(&[0i64, 2]);
+    let _ = xs.index_fill_(0, &index, 7.);
+    assert_eq!(Vec::<Vec<f64>>::from(&xs), [[7., 7.], [0., 0.], [7., 7.]]);
+    let source = Tensor::of_slice(&[1f32, 2., 3., 4.]).view(&[2, 2]);
+    let _ = xs.index_copy_(0, &Tensor::of_slice(&[2i64, 1]), &source);
+    assert_eq!(Vec::<Vec<f64>>::from(&xs), [[7., 7.], [3., 4.], [1., 2.]]);
+    // The source has to match the indexed slices.
+    let source = Tensor::ones(&[2, 3], tch::kind::FLOAT_CPU);
+    assert!(xs.f_index_copy_(0, &index, &source).is_err());
+}
+
+#[test]
+fn nbytes() {
+    let xs = Tensor::zeros(&[2, 5], tch::kind::FLOAT_CPU);
+    assert_eq!(xs.numel(), 10);
+    assert_eq!(xs.element_size(), 4);
+    assert_eq!(xs.nbytes(), 40);
+    let xs = Tensor::of_slice(&[1i64, 2, 3]);
+    assert_eq!(xs.nbytes(), 24);
+    assert_eq!(xs.to_kind(tch::Kind::Uint8).nbytes(), 3);
+}
+
+#[test]
+fn einsum_contraction() {
+    let xs = Tensor::rand(&[3, 40], tch::kind::FLOAT_CPU);
+    let ys = Tensor::rand(&[40, 50], tch::kind::FLOAT_CPU);
+    let zs = Tensor::rand(&[50, 2], tch::kind::FLOAT_CPU);
+    let equation = "ij,jk,kl->il";
+    let contraction = Contraction::compile(equation, &[&[3, 40], &[40, 50], &[50, 2]]);
+    // Contracting ys and zs first is cheaper than starting with xs and ys.
+    assert_eq!(contraction.cost(), 40. * 50. * 2. + 3. * 40. * 2.);
+    let expected = Tensor::einsum(equation, &[&xs, &ys, &zs]);
+    let result = contraction.run(&[&xs, &ys, &zs]);
+    assert_eq!(result.size(), [3, 2]);
+    assert!(f64::try_from((&result - expected).abs().max()).unwrap() < 1e-4);
+    // The output subscripts can be implicit.
+    let contraction = Contraction::compile("ij,jk", &[&[3, 40], &[40, 50]]);
+    let result = contraction.run(&[&xs, &ys]);
+    assert!(f64::try_from((&result - xs.matmul(&ys)).abs().max()).unwrap() < 1e-4);
+    let contraction = Contraction::compile("ii->i", &[&[40, 40]]);
+    let result = contraction.run(&[&ys.narrow(1, 0, 40)]);
+    assert!(f64::try_from((&result - ys.narrow(1, 0, 40).diag(0)).abs().max()).unwrap() < 1e-4);
+    assert!(contraction.f_run(&[&xs]).is_err());
+    assert!(Contraction::f_compile("ij,jk", &[&[3, 4], &[5, 6]]).is_err());
+    let err = Contraction::f_compile("iJ,Jk", &[&[3, 4], &[4, 6]]).unwrap_err();
+    assert!(format!("{}", err).contains("'J'"), "{}", err);
+}
+
+#[test]
+fn write_csv() {
+    let filename = std::env::temp_dir().join(format!("tch-csv-{}.csv", std::process::id()));
+    let xs = Tensor::of_slice(&[1.5f32, -2., 3.25, 4., 5., 6.]).view(&[2, 3]);
+    xs.write_csv(&filename, ',').unwrap();
+    let content = std::fs::read_to_string(&filename).unwrap();
+    assert_eq!(content, "1.5000,-2.0000,3.2500\n4.0000,5.0000,6.0000\n");
+    Tensor::of_slice(&[1i64, 2, 3])
+        .write_csv(&filename, '\t')
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&filename).unwrap(), "1\n2\n3\n");
+    Tensor::of_slice(&[9007199254740993i64, -1])
+        .write_csv(&filename, ',')
+        .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&filename).unwrap(),
+        "9007199254740993\n-1\n"
+    );
+    assert!(xs.view(&[1, 2, 3]).write_csv(&filename, ',').is_err());
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn retain_grad() {
+    let xs = Tensor::of_slice(&[1f32, 2., 3.]).set_requires_grad(true);
+    let ys = &xs * &xs;
+    assert!(xs.is_leaf() && !ys.is_leaf());
+    assert!(xs.requires_grad() && ys.requires_grad());
+    assert!(!ys.retains_grad());
+    ys.retain_grad();
+    ys.retain_grad();
+    assert!(ys.retains_grad());
+    (&ys * 2.).sum().backward();
+    assert_eq!(Vec::<f64>::from(&ys.grad()), [2., 2., 2.]);
+    assert_eq!(Vec::<f64>::from(&xs.grad()), [4., 8., 12.]);
+    // Leaf tensors and tensors not requiring grad.
+    xs.retain_grad();
+    assert!(!xs.retains_grad());
+    let zs = Tensor::of_slice(&[1f32, 2.]);
+    assert!(zs.is_leaf());
+    assert!(zs.f_retain_grad().is_err());
+}
+
+#[test]
+fn in_place_activations() {
+    let mut xs = Tensor::of_slice(&[-2f32, -0.5, 0., 1.5]);
+    let ys = xs.shallow_clone();
+    let _ = xs.relu_();
+    // The storage is modified so other tensors sharing it see the change.
+    assert_eq!(Vec::<f64>::from(&ys), [0., 0., 0., 1.5]);
+    let _ = xs.clamp_(0.5, 1.);
+    assert_eq!(Vec::<f64>::from(&xs), [0.5, 0.5, 0.5, 1.]);
+    let mut xs = Tensor::of_slice(&[-2f32, 3.]);
+    let _ = xs.abs_().clamp_max_(2.5);
+    assert_eq!(Vec::<f64>::from(&xs), [2., 2.5]);
+    let _ = xs.clamp_min_(2.2);
+    assert_eq!(Vec::<f64>::from(&xs), [2.2f32 as f64, 2.5]);
+    let mut xs = Tensor::of_slice(&[0f64]);
+    let _ = xs.sigmoid_();
+    assert_eq!(Vec::<f64>::from(&xs), [0.5]);
+    let _ = xs.tanh_();
+    assert_eq!(f64::try_from(&xs).unwrap(), 0.5f64.tanh());
+
+    // The chainable variants return the modified tensor itself.
+    let mut xs = Tensor::of_slice(&[-2f32, -0.5, 0., 1.5, 3.]);
+    let ys = xs.shallow_clone();
+    xs.abs_inplace().clamp_max_inplace(2.).relu_inplace();
+    assert_eq!(Vec::<f64>::from(&ys), [2., 0.5, 0., 1.5, 2.]);
+    xs.clamp_inplace(0.5, 1.5).clamp_min_inplace(1.);
+    assert_eq!(Vec::<f64>::from(&ys), [1.5, 1., 1., 1.5, 1.5]);
+    let mut xs = Tensor::of_slice(&[0f64]);
+    assert_eq!(f64::try_from(&*xs.sigmoid_inplace()).unwrap(), 0.5);
+    assert_eq!(f64::try_from(&*xs.tanh_inplace()).unwrap(), 0.5f64.tanh());
+}
+
+#[test]
+fn item() {
+    let xs = Tensor::of_slice(&[3.5f64, -1.25, 7.]);
+    assert_eq!(xs.double_value(&[1]), -1.25);
+    assert_eq!(xs.int64_value(&[2]), 7);
+    assert!(xs.f_double_value(&[3]).is_err());
+    assert_eq!(xs.get(1).item::<f64>(), -1.25);
+    assert_eq!(xs.narrow(0, 2, 1).item::<i64>(), 7);
+    assert_eq!(xs.sum().view(&[1, 1]).item::<f32>(), 9.25);
+    assert!(xs.f_item::<f64>().is_err());
+    assert_eq!(f64::try_from(xs.get(0)).unwrap(), 3.5);
+    assert_eq!(i64::try_from(&xs.sum()).unwrap(), 9);
+    assert!(f64::try_from(&xs).is_err());
+    assert!(i32::try_from(Tensor::zeros(&[0], tch::kind::INT64_CPU)).is_err());
+}
+
+#[test]
+fn tensor_kind() {
+    use tch::kind::TensorKind;
+    fn sum_of<T: TensorKind + Copy + Default>(data: &[T]) -> (Kind, T) {
+        let xs = Tensor::of_slice(data);
+        assert_eq!(xs.kind(), T::KIND);
+        (xs.kind(), xs.sum().to_kind(T::KIND).item::<T>())
+    }
+    assert_eq!(sum_of(&[1f32, 2.5]), (Kind::Float, 3.5));
+    assert_eq!(sum_of(&[1f64, 2.5]), (Kind::Double, 3.5));
+    assert_eq!(sum_of(&[1i64, 2]), (Kind::Int64, 3));
+    assert_eq!(sum_of(&[1i32, 2]), (Kind::Int, 3));
+    assert_eq!(sum_of(&[1u8, 2]), (Kind::Uint8, 3));
+}
+
+#[test]
+fn all_any() {
+    let xs = Tensor::of_slice(&[1f64, 0., 2., 3., 0., 0.]).view(&[2, 3]);
+    assert_eq!(i64::try_from(xs.count_nonzero(None)).unwrap(), 3);
+    assert_eq!(Vec::<i64>::from(xs.count_nonzero(Some(&[1]))), [2, 1]);
+    assert_eq!(Vec::<i64>::from(xs.count_nonzero(Some(&[0]))), [2, 0, 1]);
+    assert_eq!(Vec::<u8>::from(xs.all_dim(0, false)), [1, 0, 0]);
+    assert_eq!(Vec::<u8>::from(xs.any_dim(0, false)), [1, 0, 1]);
+    assert_eq!(xs.any_dim(1, true).size(), [2, 1]);
+    assert_eq!(Vec::<u8>::from(xs.all_dim(1, false)), [0, 0]);
+    assert!(!xs.all_nonzero());
+    assert!(xs.any_nonzero());
+    assert!(xs.narrow(1, 0, 1).all_nonzero());
+    assert!(!xs.narrow(1, 1, 1).any_nonzero());
+    assert!(!xs.isnan().any_nonzero());
+    assert!(Tensor::zeros(&[0], tch::kind::FLOAT_CPU).all_nonzero());
+}
+
+#[test]
+fn scaled_dot_product_attention() {
+    let q = Tensor::randn(&[2, 3, 4, 8], tch::kind::FLOAT_CPU);
+    let k = Tensor::randn(&[2, 3, 5, 8], tch::kind::FLOAT_CPU);
+    let v = Tensor::randn(&[2, 3, 5, 6], tch::kind::FLOAT_CPU);
+    let manual = |mask: &Tensor| {
+        let scores = q.matmul(&k.transpose(-2, -1)) / 8f64.sqrt() + mask;
+        scores.softmax(-1).matmul(&v)
+    };
+    let close = |a: &Tensor, b: &Tensor| f64::try_from((a - b).abs().max()).unwrap() < 1e-5;
+    let ys = q.scaled_dot_product_attention(&k, &v, None, 0., false);
+    assert_eq!(ys.size(), [2, 3, 4, 6]);
+    assert!(close(
+        &ys,
+        &manual(&Tensor::zeros(&[4, 5], tch::kind::FLOAT_CPU))
+    ));
+    let float_mask = Tensor::randn(&[4, 5], tch::kind::FLOAT_CPU);
+    let ys = q.scaled_dot_product_attention(&k, &v, Some(&float_mask), 0., false);
+    assert!(close(&ys, &manual(&float_mask)));
+    // Causal attention, query i attends to keys 0..=i.
+    let allowed = Tensor::ones(&[4, 5], tch::kind::FLOAT_CPU).tril(0);
+    let causal_mask = (1. - &allowed) * -1e9;
+    let ys = q.scaled_dot_product_attention(&k, &v, None, 0., true);
+    assert!(close(&ys, &manual(&causal_mask)));
+    let int_mask = allowed.to_kind(Kind::Uint8);
+    let ys_int = q.scaled_dot_product_attention(&k, &v, Some(&int_mask), 0., false);
+    assert!(close(&ys, &ys_int));
+    assert!(q
+        .f_scaled_dot_product_attention(&k, &v, Some(&int_mask), 0., true)
+        .is_err());
+    assert!(q
+        .f_scaled_dot_product_attention(&v, &k, None, 0., false)
+        .is_err());
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "test-utils")]
+use tch::{assert_tensor_eq, test_utils, Kind, Tensor};
+
+#[test]
+fn tensor_eq() {
+    let xs = Tensor::of_slice(&[1f64, 2., 3.]);
+    assert_tensor_eq!(xs, xs.to_kind(Kind::Float));
+    assert_tensor_eq!(xs, &xs + 1e-3, 1e-2, 0.);
+    assert!(test_utils::check_tensor_eq(&xs, &(&xs + 1e-3), 1e-5, 1e-8).is_err());
+    let err = test_utils::check_tensor_eq(&xs, &xs.view(&[1, 3]), 1e-5, 1e-8).unwrap_err();
+    assert!(err.contains("[3] vs [1, 3]"));
+    let err = test_utils::check_tensor_eq(&xs, &(&xs * 2.), 1e-5, 1e-8).unwrap_err();
+    assert!(err.contains("3 of 3 values"));
+    assert!(err.contains("max absolute difference 3"));
+}
+
+#[test]
+#[should_panic(expected = "shape mismatch")]
+fn tensor_eq_shape_mismatch() {
+    let xs = Tensor::of_slice(&[1f64, 2., 3.]);
+    assert_tensor_eq!(xs, xs.narrow(0, 0, 2));
+}
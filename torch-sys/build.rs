@@ -5,6 +5,8 @@
 //
 // On Linux, the TORCH_CUDA_VERSION environment variable can be used,
 // like 9.0, 90, or cu90 to specify the version of CUDA to use for libtorch.
+// This also enables cuda streams support, the CUDA_HOME environment variable
+// can be used to specify where the cuda headers are installed.
 #[macro_use]
 extern crate failure;
 
@@ -111,9 +113,16 @@ fn prepare_libtorch_dir() -> PathBuf {
     }
 }
 
-fn make<P: AsRef<Path>>(libtorch: P) {
+fn make<P: AsRef<Path>>(libtorch: P, use_cuda: bool) {
     let libtorch_cxx11_abi = env::var("LIBTORCH_CXX11_ABI").unwrap_or("0".to_string());
-    cc::Build::new()
+    let mut build = cc::Build::new();
+    if use_cuda {
+        let cuda_home = env::var("CUDA_HOME").unwrap_or("/usr/local/cuda".to_string());
+        build
+            .define("TCH_CUDA", None)
+            .include(PathBuf::from(cuda_home).join("include"));
+    }
+    build
         .cpp(true)
         .include(libtorch.as_ref().join("include"))
         .include(libtorch.as_ref().join("include/torch/csrc/api/include"))
@@ -141,6 +150,7 @@ fn cmake<P: AsRef<Path>>(libtorch: P) {
 
 fn main() {
     let libtorch = prepare_libtorch_dir();
+    let use_cuda = env::var("TORCH_CUDA_VERSION").is_ok();
     println!(
         "cargo:rustc-link-search=native={}",
         libtorch.join("lib").display()
@@ -149,10 +159,13 @@ fn main() {
     if env::var("LIBTORCH_USE_CMAKE").is_ok() {
         cmake(&libtorch)
     } else {
-        make(&libtorch)
+        make(&libtorch, use_cuda)
     }
 
     println!("cargo:rustc-link-lib=c10");
+    if use_cuda {
+        println!("cargo:rustc-link-lib=c10_cuda");
+    }
     println!("cargo:rustc-link-lib=caffe2");
     println!("cargo:rustc-link-lib=torch");
 }
@@ -1,6 +1,22 @@
 #include<torch/csrc/autograd/engine.h>
+#include<torch/csrc/autograd/profiler.h>
 #include<torch/torch.h>
 #include<torch/script.h>
+#include<torch/csrc/jit/passes/constant_propagation.h>
+#include<torch/csrc/jit/passes/dead_code_elimination.h>
+#include<torch/csrc/jit/passes/peephole.h>
+#include<ATen/CPUGenerator.h>
+#include<TH/THGenerator.hpp>
+#ifdef TCH_CUDA
+#include<c10/cuda/CUDAStream.h>
+#include<c10/cuda/CUDACachingAllocator.h>
+#include<c10/cuda/CUDAGuard.h>
+#include<THC/THC.h>
+#include<cuda_runtime_api.h>
+#endif
+#include<chrono>
+#include<mutex>
+#include<sstream>
 #include<stdexcept>
 #include<vector>
 #include "torch_api.h"
@@ -26,6 +42,36 @@ void at_manual_seed(int64_t seed) {
   torch::manual_seed(seed);
 }
 
+THGenerator *cpu_th_generator() {
+  auto &generator = at::globalContext().defaultGenerator(at::kCPU);
+  return static_cast<at::CPUGenerator&>(generator).generator;
+}
+
+tensor at_get_rng_state() {
+  PROTECT(
+    THGenerator *generator = cpu_th_generator();
+    std::lock_guard<std::mutex> lock(generator->mutex);
+    auto state = torch::empty({(int64_t)sizeof(THGeneratorState)}, torch::kByte);
+    THGeneratorState_copy((THGeneratorState*)state.data_ptr(), &generator->gen_state);
+    return new torch::Tensor(state);
+  )
+}
+
+void at_set_rng_state(tensor t) {
+  PROTECT(
+    if (t->scalar_type() != torch::kByte || t->numel() != (int64_t)sizeof(THGeneratorState))
+      throw std::invalid_argument("invalid rng state, expected a uint8 tensor with " +
+                                  std::to_string(sizeof(THGeneratorState)) + " elements");
+    auto state = t->contiguous().to(torch::kCPU);
+    auto gen_state = (THGeneratorState*)state.data_ptr();
+    if (!THGeneratorState_isValid(gen_state))
+      throw std::invalid_argument("invalid rng state");
+    THGenerator *generator = cpu_th_generator();
+    std::lock_guard<std::mutex> lock(generator->mutex);
+    THGeneratorState_copy(&generator->gen_state, gen_state);
+  )
+}
+
 vector<torch::Tensor> of_carray_tensor(torch::Tensor **vs, int len) {
   vector<torch::Tensor> result;
   for (int i = 0; i < len; ++i) result.push_back(*(vs[i]));
@@ -79,6 +125,10 @@ int at_defined(tensor t) {
   PROTECT(return t->defined();)
 }
 
+int at_is_sparse(tensor t) {
+  PROTECT(return t->is_sparse();)
+}
+
 size_t at_dim(tensor t) {
   PROTECT(return t->dim();)
 }
@@ -90,6 +140,29 @@ void at_shape(tensor t, int64_t *dims) {
   )
 }
 
+void at_stride(tensor t, int64_t *dims) {
+  PROTECT(
+    int i = 0;
+    for (int64_t dim : t->strides()) dims[i++] = dim;
+  )
+}
+
+int at_is_contiguous(tensor t) {
+  PROTECT(return t->is_contiguous();)
+}
+
+int64_t at_storage_offset(tensor t) {
+  PROTECT(return t->storage_offset();)
+}
+
+tensor at_to_device(tensor t, int device, int non_blocking) {
+  PROTECT(return new torch::Tensor(t->to(device_of_int(device), (bool)non_blocking));)
+}
+
+int at_is_alias_of(tensor t, tensor other) {
+  PROTECT(return t->is_alias_of(*other);)
+}
+
 int at_scalar_type(tensor t) {
   PROTECT(
     return static_cast<int>(t->scalar_type());
@@ -124,6 +197,10 @@ tensor at_get(tensor t, int index) {
   PROTECT(return new torch::Tensor((*t)[index]);)
 }
 
+tensor at_einsum(char *equation, tensor *tensors, int ntensors) {
+  PROTECT(return new torch::Tensor(torch::einsum(equation, of_carray_tensor(tensors, ntensors)));)
+}
+
 template<typename T>
 T at_value_at_indexes(tensor t, int64_t *indexes, int indexes_len) {
   PROTECT(
@@ -191,6 +268,129 @@ void at_copy_(tensor dst, tensor src) {
   )
 }
 
+void at_set_data(tensor dst, tensor src) {
+  PROTECT(
+    dst->set_data(*src);
+  )
+}
+
+void at_reset_grad(tensor t) {
+  PROTECT(
+    t->grad().reset();
+  )
+}
+
+// Calls the Rust callback on the gradient for the output [value_idx] of the
+// node. The callback is disabled when the associated handle is removed.
+class TchFunctionPreHook : public torch::autograd::FunctionPreHook {
+ public:
+  TchFunctionPreHook(std::shared_ptr<bool> enabled, int value_idx, void *data,
+                     tensor (*f)(void *, tensor), void (*free_data)(void *))
+      : enabled(enabled), value_idx(value_idx), data(data), f(f), free_data(free_data) {}
+  ~TchFunctionPreHook() override { free_data(data); }
+  torch::autograd::variable_list operator()(const torch::autograd::variable_list &grads) override {
+    if (!*enabled || !grads[value_idx].defined()) return grads;
+    torch::autograd::variable_list result(grads);
+    torch::Tensor grad(grads[value_idx]);
+    tensor new_grad = f(data, &grad);
+    if (new_grad == nullptr) {
+      throw std::runtime_error("a rust gradient hook panicked");
+    }
+    result[value_idx] = torch::autograd::as_variable_ref(*new_grad);
+    delete new_grad;
+    return result;
+  }
+ private:
+  std::shared_ptr<bool> enabled;
+  int value_idx;
+  void *data;
+  tensor (*f)(void *, tensor);
+  void (*free_data)(void *);
+};
+
+hook_handle at_register_hook(tensor t, void *data, tensor (*f)(void *, tensor), void (*free_data)(void *)) {
+  PROTECT(
+    auto &var = torch::autograd::as_variable_ref(*t);
+    if (!var.requires_grad()) {
+      throw std::invalid_argument("cannot register a hook on a tensor that does not require grad");
+    }
+    auto enabled = std::make_shared<bool>(true);
+    if (var.grad_fn()) {
+      var.grad_fn()->add_pre_hook(std::unique_ptr<torch::autograd::FunctionPreHook>(
+        new TchFunctionPreHook(enabled, var.output_nr(), data, f, free_data)));
+    } else {
+      // Leaf hooks are called by the gradient accumulator.
+      var.add_hook(std::make_shared<TchFunctionPreHook>(enabled, 0, data, f, free_data));
+    }
+    return new std::shared_ptr<bool>(enabled);
+  )
+}
+
+void atho_remove(hook_handle h) {
+  PROTECT(**h = false;)
+}
+
+void atho_free(hook_handle h) {
+  delete h;
+}
+
+// Stores the gradient for the output [value_idx] of the node in the grad field
+// of the variable. The variable is weakly referenced so that the hook, which is
+// owned by the graph, does not keep it alive.
+class TchRetainGradHook : public torch::autograd::FunctionPreHook {
+ public:
+  typedef c10::weak_intrusive_ptr<at::TensorImpl, at::UndefinedTensorImpl> weak_impl;
+  TchRetainGradHook(weak_impl impl, int value_idx) : impl(impl), value_idx(value_idx) {}
+  torch::autograd::variable_list operator()(const torch::autograd::variable_list &grads) override {
+    auto strong_impl = impl.lock();
+    if (!strong_impl || !grads[value_idx].defined()) return grads;
+    torch::Tensor var(strong_impl);
+    torch::Tensor &grad = var.grad();
+    if (grad.defined()) {
+      grad = grad + grads[value_idx];
+    } else {
+      grad = grads[value_idx].clone();
+    }
+    return grads;
+  }
+  bool retains(const torch::Tensor &var) const {
+    return impl.lock().get() == var.unsafeGetTensorImpl();
+  }
+ private:
+  weak_impl impl;
+  int value_idx;
+};
+
+int at_is_leaf(tensor t) {
+  PROTECT(return torch::autograd::as_variable_ref(*t).grad_fn() == nullptr;)
+}
+
+int at_retains_grad(tensor t) {
+  PROTECT(
+    auto &var = torch::autograd::as_variable_ref(*t);
+    if (!var.grad_fn()) return false;
+    for (auto &hook : var.grad_fn()->pre_hooks()) {
+      auto retain_hook = dynamic_cast<TchRetainGradHook *>(hook.get());
+      if (retain_hook && retain_hook->retains(var)) return true;
+    }
+    return false;
+  )
+}
+
+void at_retain_grad(tensor t) {
+  PROTECT(
+    auto &var = torch::autograd::as_variable_ref(*t);
+    if (!var.requires_grad()) {
+      throw std::invalid_argument("cannot retain the grad of a tensor that does not require grad");
+    }
+    // Leaf variables already accumulate their gradients.
+    if (!var.grad_fn() || at_retains_grad(t)) return;
+    TchRetainGradHook::weak_impl impl(var.getIntrusivePtr());
+    var.grad_fn()->add_pre_hook(std::unique_ptr<torch::autograd::FunctionPreHook>(
+      new TchRetainGradHook(impl, var.output_nr())));
+  )
+}
+
 void at_save(tensor t, char *filename) {
   PROTECT(torch::save(*t, filename);)
 }
@@ -248,6 +448,50 @@ void at_load_multi_(tensor *tensors, char **tensor_names, int ntensors, char *fi
   )
 }
 
+void at_save_to_bytes(tensor t, void *data, void (*f)(void *, const char *, size_t)) {
+  PROTECT(
+    std::ostringstream stream;
+    torch::save(*t, stream);
+    std::string bytes = stream.str();
+    f(data, bytes.data(), bytes.size());
+  )
+}
+
+void at_save_multi_to_bytes(tensor *tensors, char **tensor_names, int ntensors, void *data, void (*f)(void *, const char *, size_t)) {
+  PROTECT(
+    torch::serialize::OutputArchive archive;
+    for (int i = 0; i < ntensors; ++i)
+      archive.write(std::string(tensor_names[i]), *(tensors[i]), /* buffer=*/ false);
+    std::ostringstream stream;
+    archive.save_to(stream);
+    std::string bytes = stream.str();
+    f(data, bytes.data(), bytes.size());
+  )
+}
+
+tensor at_load_from_bytes(const char *bytes, size_t size) {
+  PROTECT(
+    std::istringstream stream(std::string(bytes, size));
+    torch::Tensor tensor;
+    torch::load(tensor, stream);
+    return new torch::Tensor(tensor);
+  )
+}
+
+void at_load_callback_from_bytes(const char *bytes, size_t size, void *data, void (*f)(void *, char *, tensor)) {
+  PROTECT(
+    std::istringstream stream(std::string(bytes, size));
+    shared_ptr<torch::jit::script::Module> module = torch::jit::load(stream);
+    if (module == nullptr)
+      throw std::invalid_argument("torch::jit::load returned a nullptr");
+    for (const auto &p : module->get_parameters()) {
+      auto v = p.value();
+      if (v.isTensor())
+        f(data, (char*)p.name().c_str(), new torch::Tensor(v.toTensor()));
+    }
+  )
+}
+
 tensor at_load(char *filename) {
   PROTECT(
     torch::Tensor tensor;
@@ -483,6 +727,239 @@ void atc_set_benchmark_cudnn(int b) {
   at::globalContext().setBenchmarkCuDNN(b);
 }
 
+void atc_cuda_empty_cache() {
+#ifdef TCH_CUDA
+  PROTECT(c10::cuda::CUDACachingAllocator::emptyCache();)
+#endif
+}
+
+#ifdef TCH_CUDA
+static void check_cuda(cudaError_t err) {
+  if (err != cudaSuccess) throw std::runtime_error(cudaGetErrorString(err));
+}
+#endif
+
+tensor atc_cuda_get_rng_state(int device) {
+#ifdef TCH_CUDA
+  PROTECT(
+    c10::cuda::CUDAGuard guard(device);
+    auto state = torch::empty({0}, torch::kByte);
+    THCRandom_getRNGState(at::globalContext().lazyInitCUDA(), state.unsafeGetTensorImpl());
+    return new torch::Tensor(state);
+  )
+#else
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda support");)
+#endif
+  return nullptr;
+}
+
+void atc_cuda_set_rng_state(int device, tensor t) {
+#ifdef TCH_CUDA
+  PROTECT(
+    if (t->scalar_type() != torch::kByte)
+      throw std::invalid_argument("invalid rng state, expected a uint8 tensor");
+    c10::cuda::CUDAGuard guard(device);
+    auto state = t->contiguous().to(torch::kCPU);
+    THCRandom_setRNGState(at::globalContext().lazyInitCUDA(), state.unsafeGetTensorImpl());
+  )
+#else
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda support");)
+#endif
+}
+
+int atc_cuda_can_access_peer(int device, int peer) {
+#ifdef TCH_CUDA
+  PROTECT(
+    int can_access = 0;
+    check_cuda(cudaDeviceCanAccessPeer(&can_access, device, peer));
+    return can_access;
+  )
+#endif
+  return 0;
+}
+
+void atc_cuda_set_peer_access(int device, int peer, int enabled) {
+#ifdef TCH_CUDA
+  PROTECT(
+    int current = 0;
+    check_cuda(cudaGetDevice(&current));
+    check_cuda(cudaSetDevice(device));
+    cudaError_t err = enabled ? cudaDeviceEnablePeerAccess(peer, 0) : cudaDeviceDisablePeerAccess(peer);
+    // Enabling or disabling twice is not an error.
+    if (err == cudaErrorPeerAccessAlreadyEnabled || err == cudaErrorPeerAccessNotEnabled) {
+      cudaGetLastError();
+      err = cudaSuccess;
+    }
+    check_cuda(cudaSetDevice(current));
+    check_cuda(err);
+  )
+#else
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda support");)
+#endif
+}
+
+#ifdef TCH_CUDA
+cuda_stream atcs_new(int device) {
+  PROTECT(return new c10::Stream(c10::cuda::getStreamFromPool(false, device).unwrap());)
+}
+
+cuda_stream atcs_current(int device) {
+  PROTECT(return new c10::Stream(c10::cuda::getCurrentCUDAStream(device).unwrap());)
+}
+
+cuda_stream atcs_exchange(cuda_stream s) {
+  PROTECT(
+    c10::cuda::CUDAStream stream(*s);
+    c10::Stream previous = c10::cuda::getCurrentCUDAStream(stream.device_index()).unwrap();
+    c10::cuda::setCurrentCUDAStream(stream);
+    return new c10::Stream(previous);
+  )
+}
+
+void atcs_synchronize(cuda_stream s) {
+  PROTECT(c10::cuda::CUDAStream(*s).synchronize();)
+}
+#else
+cuda_stream atcs_new(int) {
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda stream support");)
+}
+
+cuda_stream atcs_current(int) {
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda stream support");)
+}
+
+cuda_stream atcs_exchange(cuda_stream) {
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda stream support");)
+}
+
+void atcs_synchronize(cuda_stream) {
+  PROTECT(throw std::runtime_error("tch has been compiled without cuda stream support");)
+}
+#endif
+
+int atcs_device(cuda_stream s) {
+  PROTECT(return s->device_index();)
+}
+
+void atcs_free(cuda_stream s) {
+  delete(s);
+}
+
+void atp_enable_profiler(int use_cuda) {
+  PROTECT(
+    torch::autograd::profiler::enableProfiler(
+      use_cuda ? torch::autograd::profiler::ProfilerState::CUDA
+               : torch::autograd::profiler::ProfilerState::CPU);
+  )
+}
+
+void atp_disable_profiler(void *data, void (*f)(void *, char *, double, double, double, int64_t)) {
+  PROTECT(
+    using torch::autograd::profiler::Event;
+    auto event_lists = torch::autograd::profiler::disableProfiler();
+    Event *first = nullptr;
+    for (auto &events : event_lists)
+      for (auto &event : events)
+        if (first == nullptr && std::string(event.kind()) == "push") first = &event;
+    for (auto &events : event_lists) {
+      vector<Event*> stack;
+      for (auto &event : events) {
+        std::string kind(event.kind());
+        if (kind == "push") stack.push_back(&event);
+        else if (kind == "pop" && !stack.empty()) {
+          Event *start = stack.back();
+          stack.pop_back();
+          double cuda_us = start->has_cuda() ? start->cuda_elapsed_us(event) : 0.;
+          f(data,
+            (char*)start->name(),
+            first->cpu_elapsed_us(*start),
+            start->cpu_elapsed_us(event),
+            cuda_us,
+            start->thread_id());
+        }
+      }
+    }
+  )
+}
+
+// The memory profiler replaces the CPU allocator with one recording the
+// allocations and deallocations made while it is enabled. The tracked memory
+// stays valid after the profiler is disabled.
+struct TchMemoryEvent {
+  double time_us;
+  int64_t bytes;
+};
+
+struct TchMemoryProfilerState {
+  std::mutex mutex;
+  bool enabled = false;
+  std::chrono::steady_clock::time_point start;
+  vector<TchMemoryEvent> events;
+  at::Allocator *base = nullptr;
+
+  void record(int64_t bytes) {
+    std::lock_guard<std::mutex> lock(mutex);
+    if (!enabled) return;
+    std::chrono::duration<double, std::micro> elapsed = std::chrono::steady_clock::now() - start;
+    events.push_back({elapsed.count(), bytes});
+  }
+};
+
+static TchMemoryProfilerState memory_profiler_state;
+
+struct TchTrackedContext {
+  at::DataPtr data_ptr;
+  int64_t bytes;
+};
+
+static void tch_tracked_delete(void *ctx) {
+  auto tracked = static_cast<TchTrackedContext*>(ctx);
+  memory_profiler_state.record(-tracked->bytes);
+  delete tracked;
+}
+
+class TchTrackingAllocator : public at::Allocator {
+ public:
+  at::DataPtr allocate(size_t bytes) const override {
+    at::DataPtr data_ptr = memory_profiler_state.base->allocate(bytes);
+    void *data = data_ptr.get();
+    at::Device device = data_ptr.device();
+    memory_profiler_state.record(bytes);
+    auto ctx = new TchTrackedContext{std::move(data_ptr), (int64_t)bytes};
+    return at::DataPtr(data, ctx, &tch_tracked_delete, device);
+  }
+};
+
+static TchTrackingAllocator tracking_allocator;
+
+void atp_enable_memory_profiler() {
+  PROTECT(
+    std::lock_guard<std::mutex> lock(memory_profiler_state.mutex);
+    if (memory_profiler_state.enabled) {
+      throw std::runtime_error("the memory profiler is already enabled");
+    }
+    memory_profiler_state.base = c10::GetCPUAllocator();
+    memory_profiler_state.events.clear();
+    memory_profiler_state.start = std::chrono::steady_clock::now();
+    memory_profiler_state.enabled = true;
+    c10::SetCPUAllocator(&tracking_allocator);
+  )
+}
+
+void atp_disable_memory_profiler(void *data, void (*f)(void *, double, int64_t)) {
+  PROTECT(
+    vector<TchMemoryEvent> events;
+    {
+      std::lock_guard<std::mutex> lock(memory_profiler_state.mutex);
+      if (!memory_profiler_state.enabled) return;
+      c10::SetCPUAllocator(memory_profiler_state.base);
+      memory_profiler_state.enabled = false;
+      events.swap(memory_profiler_state.events);
+    }
+    for (auto &event : events) f(data, event.time_us, event.bytes);
+  )
+}
+
 module atm_load(char *filename) {
   PROTECT(
     return new std::shared_ptr<torch::jit::script::Module>(torch::jit::load(filename));
@@ -513,6 +990,33 @@ ivalue atm_forward_(module m,
   )
 }
 
+module atm_compile(char *source) {
+  PROTECT(
+    return new std::shared_ptr<torch::jit::script::Module>(torch::jit::compile(source));
+  )
+}
+
+// Switches the module to evaluation mode and simplifies the graph of each of
+// its methods, constants are propagated so that the training only branches,
+// e.g. dropout, get removed.
+static void optimize_methods(torch::jit::script::Module &m, bool peephole) {
+  m.eval();
+  for (auto &method : m.get_methods()) {
+    auto graph = method->graph();
+    torch::jit::ConstantPropagation(graph);
+    if (peephole) torch::jit::PeepholeOptimize(graph);
+    torch::jit::EliminateDeadCode(graph);
+  }
+}
+
+void atm_freeze(module m) {
+  PROTECT(optimize_methods(**m, false);)
+}
+
+void atm_optimize_for_inference(module m) {
+  PROTECT(optimize_methods(**m, true);)
+}
+
 void atm_free(module m) {
   delete(m);
 }
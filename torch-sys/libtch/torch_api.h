@@ -11,6 +11,8 @@ typedef torch::Scalar *scalar;
 typedef torch::optim::Optimizer *optimizer;
 typedef std::shared_ptr<torch::jit::script::Module> *module;
 typedef torch::jit::IValue *ivalue;
+typedef c10::Stream *cuda_stream;
+typedef std::shared_ptr<bool> *hook_handle;
 #define PROTECT(x) \
   try { \
     x \
@@ -23,19 +25,32 @@ typedef void *optimizer;
 typedef void *scalar;
 typedef void *module;
 typedef void *ivalue;
+typedef void *cuda_stream;
+typedef void *hook_handle;
 #endif
 
 char *get_and_reset_last_err(); // thread-local
 void at_manual_seed(int64_t);
+/* The state of the cpu random number generator as a uint8 tensor. */
+tensor at_get_rng_state();
+void at_set_rng_state(tensor);
 tensor at_new_tensor();
 tensor at_tensor_of_data(void *vs, int64_t *dims, int ndims, int element_size_in_bytes, int type);
 void at_copy_data(tensor tensor, void *vs, int64_t numel, int element_size_in_bytes);
 tensor at_shallow_clone(tensor);
 
 int at_defined(tensor);
+int at_is_sparse(tensor);
 int at_device(tensor);
 size_t at_dim(tensor);
 void at_shape(tensor, int64_t *);
+void at_stride(tensor, int64_t *);
+int at_is_contiguous(tensor);
+int64_t at_storage_offset(tensor);
+/* When non_blocking is set, copies from pinned host memory to a cuda device
+   are asynchronous with respect to the host. */
+tensor at_to_device(tensor, int device, int non_blocking);
+int at_is_alias_of(tensor, tensor);
 int at_scalar_type(tensor);
 
 void at_backward(tensor, int, int);
@@ -43,6 +58,7 @@ int at_requires_grad(tensor);
 int at_grad_set_enabled(int);
 
 tensor at_get(tensor, int index);
+tensor at_einsum(char *equation, tensor *tensors, int ntensors);
 void at_fill_double(tensor, double);
 void at_fill_int64(tensor, int64_t);
 
@@ -52,6 +68,20 @@ void at_set_double_value_at_indexes(tensor, int *indexes, int indexes_len, doubl
 void at_set_int64_value_at_indexes(tensor, int *indexes, int indexes_len, int64_t v);
 
 void at_copy_(tensor dst, tensor src);
+void at_set_data(tensor dst, tensor src);
+void at_reset_grad(tensor);
+/* [at_register_hook] registers [f] to be called on the gradient of the tensor
+   during backward, [f] returns the new gradient or null to make the backward
+   pass fail. [free_data] is called on [data] when the hook gets destroyed,
+   [data] is not freed on errors. */
+hook_handle at_register_hook(tensor, void *data, tensor (*f)(void *, tensor), void (*free_data)(void *));
+void atho_remove(hook_handle);
+void atho_free(hook_handle);
+int at_is_leaf(tensor);
+/* [at_retain_grad] makes backward populate the grad of a non-leaf tensor, this
+   is a no-op for leaf tensors. */
+void at_retain_grad(tensor);
+int at_retains_grad(tensor);
 
 void at_print(tensor);
 char *at_to_string(tensor, int line_size);
@@ -69,6 +99,12 @@ void at_load_multi_(tensor *tensors, char **tensor_names, int ntensors, char *fi
 
 void at_load_callback(char *filename, void *data, void (*f)(void *, char *, tensor));
 
+/* The serialized bytes are passed to [f] which has to copy them. */
+void at_save_to_bytes(tensor, void *data, void (*f)(void *, const char *, size_t));
+void at_save_multi_to_bytes(tensor *tensors, char **tensor_names, int ntensors, void *data, void (*f)(void *, const char *, size_t));
+tensor at_load_from_bytes(const char *bytes, size_t size);
+void at_load_callback_from_bytes(const char *bytes, size_t size, void *data, void (*f)(void *, char *, tensor));
+
 void at_free(tensor);
 
 void at_run_backward(tensor *tensors,
@@ -112,12 +148,53 @@ int atc_cuda_device_count();
 int atc_cuda_is_available();
 int atc_cudnn_is_available();
 void atc_set_benchmark_cudnn(int b);
+/* Releases the unused memory held by the cuda caching allocator, this is a
+   no-op when tch has been compiled without cuda support. */
+void atc_cuda_empty_cache();
+/* Returns 1 if device can directly access the memory of peer, 0 otherwise or
+   when tch has been compiled without cuda support. */
+/* The state of the random number generator of a cuda device, an error is
+   set when tch has been compiled without cuda support. */
+tensor atc_cuda_get_rng_state(int device);
+void atc_cuda_set_rng_state(int device, tensor);
+int atc_cuda_can_access_peer(int device, int peer);
+void atc_cuda_set_peer_access(int device, int peer, int enabled);
+
+/* The cuda stream functions return an error when tch has been compiled
+   without cuda support. */
+cuda_stream atcs_new(int device);
+cuda_stream atcs_current(int device);
+/* [atcs_exchange] makes [s] the current stream on its device and returns
+   the previously current stream. */
+cuda_stream atcs_exchange(cuda_stream s);
+void atcs_synchronize(cuda_stream s);
+int atcs_device(cuda_stream s);
+void atcs_free(cuda_stream s);
+
+void atp_enable_profiler(int use_cuda);
+/* [atp_disable_profiler] calls [f] on each recorded operation with its name,
+   its start time, its cpu and cuda durations in microseconds, and its thread id. */
+void atp_disable_profiler(void *data, void (*f)(void *, char *, double, double, double, int64_t));
+/* The memory profiler records the CPU allocations, [atp_disable_memory_profiler]
+   calls [f] on each event with its time in microseconds and the number of bytes
+   allocated, negative for deallocations. */
+void atp_enable_memory_profiler();
+void atp_disable_memory_profiler(void *data, void (*f)(void *, double, int64_t));
 
 module atm_load(char *);
 tensor atm_forward(module, tensor *tensors, int ntensors);
 ivalue atm_forward_(module,
                     ivalue *ivalues,
                     int nivalues);
+/* [atm_compile] creates a module from some TorchScript source code, each
+   function defined in [source] becomes a method of the module. */
+module atm_compile(char *source);
+/* [atm_freeze] switches the module to evaluation mode and runs the constant
+   propagation and dead code elimination passes on its methods,
+   [atm_optimize_for_inference] also runs the peephole optimizations. The
+   module is modified in place. */
+void atm_freeze(module);
+void atm_optimize_for_inference(module);
 void atm_free(module);
 
 ivalue ati_tensor(tensor);
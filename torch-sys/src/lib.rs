@@ -23,14 +23,27 @@ extern "C" {
     pub fn at_new_tensor() -> *mut C_tensor;
     pub fn at_shallow_clone(arg: *mut C_tensor) -> *mut C_tensor;
     pub fn at_copy_(dst: *mut C_tensor, src: *mut C_tensor);
+    pub fn at_set_data(dst: *mut C_tensor, src: *mut C_tensor);
+    pub fn at_reset_grad(arg: *mut C_tensor);
     pub fn at_defined(arg: *mut C_tensor) -> c_int;
+    pub fn at_is_sparse(arg: *mut C_tensor) -> c_int;
     pub fn at_backward(arg: *mut C_tensor, keep_graph: c_int, create_graph: c_int);
     pub fn at_print(arg: *mut C_tensor);
     pub fn at_to_string(arg: *mut C_tensor, line_size: c_int) -> *mut c_char;
     pub fn at_dim(arg: *mut C_tensor) -> size_t;
     pub fn at_get(arg: *mut C_tensor, index: c_int) -> *mut C_tensor;
+    pub fn at_einsum(
+        equation: *const c_char,
+        tensors: *const *mut C_tensor,
+        ntensors: c_int,
+    ) -> *mut C_tensor;
     pub fn at_requires_grad(arg: *mut C_tensor) -> c_int;
     pub fn at_shape(arg: *mut C_tensor, sz: *mut i64);
+    pub fn at_stride(arg: *mut C_tensor, sz: *mut i64);
+    pub fn at_is_contiguous(arg: *mut C_tensor) -> c_int;
+    pub fn at_storage_offset(arg: *mut C_tensor) -> i64;
+    pub fn at_to_device(arg: *mut C_tensor, device: c_int, non_blocking: c_int) -> *mut C_tensor;
+    pub fn at_is_alias_of(arg: *mut C_tensor, other: *mut C_tensor) -> c_int;
     pub fn at_double_value_at_indexes(arg: *mut C_tensor, idx: *const i64, idx_len: c_int) -> f64;
     pub fn at_int64_value_at_indexes(arg: *mut C_tensor, idx: *const i64, idx_len: c_int) -> i64;
     pub fn at_free(arg: *mut C_tensor);
@@ -72,8 +85,29 @@ extern "C" {
         data: *mut c_void,
         f: extern "C" fn(*mut c_void, name: *const c_char, t: *mut C_tensor),
     );
+    pub fn at_save_to_bytes(
+        arg: *mut C_tensor,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, bytes: *const c_char, size: usize),
+    );
+    pub fn at_save_multi_to_bytes(
+        args: *const *mut C_tensor,
+        names: *const *const c_char,
+        n: c_int,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, bytes: *const c_char, size: usize),
+    );
+    pub fn at_load_from_bytes(bytes: *const c_char, size: usize) -> *mut C_tensor;
+    pub fn at_load_callback_from_bytes(
+        bytes: *const c_char,
+        size: usize,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, name: *const c_char, t: *mut C_tensor),
+    );
 
     pub fn at_manual_seed(seed: i64);
+    pub fn at_get_rng_state() -> *mut C_tensor;
+    pub fn at_set_rng_state(arg: *mut C_tensor);
 }
 
 pub mod c_generated;
@@ -83,12 +117,63 @@ extern "C" {
     pub fn atc_cuda_is_available() -> c_int;
     pub fn atc_cudnn_is_available() -> c_int;
     pub fn atc_set_benchmark_cudnn(b: c_int);
+    pub fn atc_cuda_empty_cache();
+    pub fn atc_cuda_get_rng_state(device: c_int) -> *mut C_tensor;
+    pub fn atc_cuda_set_rng_state(device: c_int, arg: *mut C_tensor);
+    pub fn atc_cuda_can_access_peer(device: c_int, peer: c_int) -> c_int;
+    pub fn atc_cuda_set_peer_access(device: c_int, peer: c_int, enabled: c_int);
+}
+
+#[repr(C)]
+pub struct C_hook_handle {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    pub fn at_register_hook(
+        arg: *mut C_tensor,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, *mut C_tensor) -> *mut C_tensor,
+        free_data: extern "C" fn(*mut c_void),
+    ) -> *mut C_hook_handle;
+    pub fn atho_remove(h: *mut C_hook_handle);
+    pub fn atho_free(h: *mut C_hook_handle);
+    pub fn at_is_leaf(arg: *mut C_tensor) -> c_int;
+    pub fn at_retain_grad(arg: *mut C_tensor);
+    pub fn at_retains_grad(arg: *mut C_tensor) -> c_int;
+}
+
+#[repr(C)]
+pub struct C_cuda_stream {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    pub fn atcs_new(device: c_int) -> *mut C_cuda_stream;
+    pub fn atcs_current(device: c_int) -> *mut C_cuda_stream;
+    pub fn atcs_exchange(s: *mut C_cuda_stream) -> *mut C_cuda_stream;
+    pub fn atcs_synchronize(s: *mut C_cuda_stream);
+    pub fn atcs_device(s: *mut C_cuda_stream) -> c_int;
+    pub fn atcs_free(s: *mut C_cuda_stream);
 }
 
 extern "C" {
     pub fn get_and_reset_last_err() -> *mut c_char;
 }
 
+extern "C" {
+    pub fn atp_enable_profiler(use_cuda: c_int);
+    pub fn atp_disable_profiler(
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, name: *const c_char, f64, f64, f64, i64),
+    );
+    pub fn atp_enable_memory_profiler();
+    pub fn atp_disable_memory_profiler(
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, time_us: f64, bytes: i64),
+    );
+}
+
 #[repr(C)]
 pub struct C_optimizer {
     _private: [u8; 0],
@@ -157,5 +242,8 @@ extern "C" {
     pub fn atm_load(filename: *const c_char) -> *mut CModule_;
     pub fn atm_forward(m: *mut CModule_, args: *const *mut C_tensor, n: c_int) -> *mut C_tensor;
     pub fn atm_forward_(m: *mut CModule_, args: *const *mut CIValue, n: c_int) -> *mut CIValue;
+    pub fn atm_compile(source: *const c_char) -> *mut CModule_;
+    pub fn atm_freeze(m: *mut CModule_);
+    pub fn atm_optimize_for_inference(m: *mut CModule_);
     pub fn atm_free(m: *mut CModule_);
 }